@@ -0,0 +1,266 @@
+//! broadcast channel
+//! Two tabs of the same browser play each other without the WebSocket server.
+//! The messages go over the browser `BroadcastChannel` to all other tabs.
+//! The transport does the little work of the server: it answers the handshake,
+//! collects the online players, routes the messages by `other_ws_uid` and fetches the spellings.
+//! There is no leaderboard and no rating. A closed tab stays in the list of players until refresh.
+
+use crate::clienterror::ClientError;
+use crate::transport::{OnMessage, Transport};
+use futures::Future;
+use js_sys::Reflect;
+use mem3_common::{PlayerInfo, WsMessage, PROTOCOL_VERSION};
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{console, BroadcastChannel, Response};
+
+///all tabs of the game use the same channel
+const CHANNEL_NAME: &str = "mem3";
+
+///the transport over the browser BroadcastChannel
+pub struct BroadcastChannelTransport {
+    ///shared with the onmessage closure and the fetch futures
+    shared: Rc<Shared>,
+}
+
+///the state of this tab
+struct Shared {
+    ///the browser channel
+    channel: BroadcastChannel,
+    ///this tab as a player
+    me: RefCell<PlayerInfo>,
+    ///the other tabs
+    players: RefCell<Vec<PlayerInfo>>,
+    ///the callback set by `set_on_message`
+    on_message: RefCell<Option<OnMessage>>,
+}
+
+impl BroadcastChannelTransport {
+    ///open the channel. The tab gets the ws_uid from the caller, because there is no server.
+    pub fn new(my_ws_uid: usize, nickname: &str, avatar: &str) -> Result<Self, JsValue> {
+        let channel = BroadcastChannel::new(CHANNEL_NAME)?;
+        Ok(BroadcastChannelTransport {
+            shared: Rc::new(Shared {
+                channel,
+                me: RefCell::new(PlayerInfo {
+                    ws_uid: my_ws_uid,
+                    nickname: nickname.to_string(),
+                    avatar: avatar.to_string(),
+                    rating: 0,
+                }),
+                players: RefCell::new(Vec::new()),
+                on_message: RefCell::new(None),
+            }),
+        })
+    }
+}
+
+impl Transport for BroadcastChannelTransport {
+    fn send(&self, msg: &WsMessage) {
+        on_local_message(&self.shared, msg);
+    }
+    fn set_on_message(&self, on_message: OnMessage) {
+        *self.shared.on_message.borrow_mut() = Some(on_message);
+
+        let shared = Rc::clone(&self.shared);
+        let msg_recv_handler = Box::new(move |msg: JsValue| {
+            match Reflect::get(&msg, &"data".into())
+                .ok()
+                .and_then(|data| data.as_string())
+            {
+                Some(json) => on_channel_message(&shared, &json),
+                None => shared.deliver_error(ClientError::new(
+                    "channel message",
+                    "the field 'data' is missing or is not a string",
+                )),
+            }
+        });
+        let cb_mrh: Closure<dyn Fn(JsValue)> = Closure::wrap(msg_recv_handler);
+        self.shared
+            .channel
+            .set_onmessage(Some(cb_mrh.as_ref().unchecked_ref()));
+        //don't drop the eventlistener from memory
+        cb_mrh.forget();
+
+        //the handshake like on the WebSocket open
+        let (nickname, avatar) = {
+            let me = self.shared.me.borrow();
+            (me.nickname.clone(), me.avatar.clone())
+        };
+        on_local_message(
+            &self.shared,
+            &WsMessage::RequestWsUid {
+                nickname,
+                avatar,
+                version: PROTOCOL_VERSION,
+                session_token: String::new(),
+            },
+        );
+    }
+}
+
+impl Shared {
+    ///send to all other tabs
+    fn post(&self, msg: &WsMessage) {
+        let json = match serde_json::to_string(msg) {
+            Ok(json) => json,
+            Err(err) => {
+                self.deliver_error(ClientError::new(
+                    "post to BroadcastChannel",
+                    &err.to_string(),
+                ));
+                return;
+            }
+        };
+        if let Err(err) = self.channel.post_message(&JsValue::from_str(&json)) {
+            console::log_2(&"post to BroadcastChannel failed".into(), &err);
+            self.deliver_error(
+                ClientError::new("post to BroadcastChannel", "the channel is closed")
+                    .with_retry(msg.clone()),
+            );
+        }
+    }
+    ///deliver to this tab
+    fn deliver(&self, msg: WsMessage) {
+        if let Some(on_message) = self.on_message.borrow().as_ref() {
+            on_message(Ok(msg));
+        }
+    }
+    ///deliver an error to this tab
+    fn deliver_error(&self, client_error: ClientError) {
+        if let Some(on_message) = self.on_message.borrow().as_ref() {
+            on_message(Err(client_error));
+        }
+    }
+    ///this tab and all the other tabs
+    fn player_list(&self) -> Vec<PlayerInfo> {
+        let mut players = self.players.borrow().clone();
+        players.push(self.me.borrow().clone());
+        players.sort_by_key(|player| player.ws_uid);
+        players
+    }
+}
+
+///the messages from this tab. The server requests are answered here.
+fn on_local_message(shared: &Rc<Shared>, msg: &WsMessage) {
+    match msg {
+        WsMessage::RequestWsUid {
+            nickname, avatar, ..
+        } => {
+            let me = {
+                let mut me = shared.me.borrow_mut();
+                me.nickname = nickname.clone();
+                me.avatar = avatar.clone();
+                me.clone()
+            };
+            //the tabs have no server and no sessions
+            shared.deliver(WsMessage::ResponseWsUid {
+                your_ws_uid: me.ws_uid,
+                session_token: String::new(),
+                is_reconnect: false,
+            });
+            //the other tabs add or update this player
+            shared.post(&WsMessage::ResponsePlayerList { players: vec![me] });
+        }
+        WsMessage::RequestPlayerList { .. } => {
+            //the other tabs answer with their player
+            shared.post(msg);
+            shared.deliver(WsMessage::ResponsePlayerList {
+                players: shared.player_list(),
+            });
+        }
+        WsMessage::RequestSpelling { filename } => fetch_spelling(shared, filename),
+        //there is no leaderboard without the server
+        WsMessage::RequestLeaderboard { .. }
+        | WsMessage::RequestRatings { .. }
+        | WsMessage::StartSoloGame { .. }
+        | WsMessage::EndSoloGame { .. } => {}
+        _ => shared.post(msg),
+    }
+}
+
+///the messages from the other tabs
+fn on_channel_message(shared: &Rc<Shared>, json: &str) {
+    let msg: WsMessage = match serde_json::from_str(json) {
+        Ok(msg) => msg,
+        Err(err) => {
+            shared.deliver_error(ClientError::new("channel message", &err.to_string()));
+            return;
+        }
+    };
+    match msg {
+        WsMessage::RequestPlayerList { .. } => {
+            let me = shared.me.borrow().clone();
+            shared.post(&WsMessage::ResponsePlayerList { players: vec![me] });
+        }
+        WsMessage::ResponsePlayerList { players } => {
+            {
+                let mut known_players = shared.players.borrow_mut();
+                for player in players {
+                    known_players.retain(|known| known.ws_uid != player.ws_uid);
+                    known_players.push(player);
+                }
+            }
+            shared.deliver(WsMessage::ResponsePlayerList {
+                players: shared.player_list(),
+            });
+        }
+        _ => {
+            if is_for_me(json, shared.me.borrow().ws_uid) {
+                shared.deliver(msg);
+            }
+        }
+    }
+}
+
+///all tabs receive all messages. Like the server, use the other_ws_uid field of the message.
+///Zero means anybody.
+fn is_for_me(json: &str, my_ws_uid: usize) -> bool {
+    //the json of an enum variant is a map with one key: the name of the variant
+    let other_ws_uid = serde_json::from_str::<serde_json::Value>(json)
+        .ok()
+        .and_then(|value| {
+            value
+                .as_object()
+                .and_then(|map| map.values().next())
+                .and_then(|fields| fields.get("other_ws_uid"))
+                .and_then(serde_json::Value::as_u64)
+        });
+    match other_ws_uid {
+        Some(other_ws_uid) => other_ws_uid == 0 || other_ws_uid == my_ws_uid as u64,
+        None => true,
+    }
+}
+
+///the server reads the spelling file. Here the tab fetches it from the same http server.
+fn fetch_spelling(shared: &Rc<Shared>, filename: &str) {
+    let window = web_sys::window().expect("error: web_sys::window");
+    let shared_for_error = Rc::clone(shared);
+    let shared = Rc::clone(shared);
+    let retry = WsMessage::RequestSpelling {
+        filename: filename.to_string(),
+    };
+    let future = JsFuture::from(window.fetch_with_str(filename))
+        //a response that is not a Response has no text
+        .and_then(|response| {
+            response
+                .dyn_into::<Response>()
+                .and_then(|response| response.text())
+        })
+        .and_then(JsFuture::from)
+        .map(move |text| {
+            shared.deliver(WsMessage::ResponseSpellingJson {
+                json: text.as_string().unwrap_or_default(),
+            });
+        })
+        .map_err(move |err| {
+            console::log_2(&"fetch spelling failed".into(), &err);
+            shared_for_error.deliver_error(
+                ClientError::new("fetch spelling", &format!("{:?}", err)).with_retry(retry),
+            );
+        });
+    wasm_bindgen_futures::spawn_local(future);
+}
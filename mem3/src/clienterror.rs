@@ -0,0 +1,103 @@
+//! client error
+//! A bad message or a failed send must not kill the whole wasm app.
+//! The error is logged to the console with the context and shown in a banner.
+//! The banner has a retry action, when there is a message to send again, and a reset action.
+
+use crate::gamedata::GameTransition;
+use crate::RootRenderingComponent;
+use dodrio::builder::*;
+use dodrio::bumpalo::{self, Bump};
+use dodrio::Node;
+use mem3_common::WsMessage;
+use std::fmt;
+use web_sys::console;
+
+///a recoverable error in the message path
+pub struct ClientError {
+    ///where it happened, for example the name of the message
+    pub context: String,
+    ///what went wrong
+    pub detail: String,
+    ///the message to send again on retry
+    pub retry: Option<WsMessage>,
+}
+
+impl ClientError {
+    ///error without a retry action. Only reset helps.
+    pub fn new(context: &str, detail: &str) -> Self {
+        ClientError {
+            context: context.to_string(),
+            detail: detail.to_string(),
+            retry: None,
+        }
+    }
+    ///the retry sends this message again
+    pub fn with_retry(mut self, msg: WsMessage) -> Self {
+        self.retry = Some(msg);
+        self
+    }
+    ///write to the browser console
+    pub fn log(&self) {
+        console::log_1(&format!("error in {}: {}", self.context, self.detail).into());
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.detail)
+    }
+}
+
+///the red banner with the retry and reset actions. Empty if there is no error.
+pub fn div_client_error<'a, 'bump>(
+    root_rendering_component: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Node<'bump>
+where
+    'a: 'bump,
+{
+    let client_error = match &root_rendering_component.client_error {
+        Some(client_error) => client_error,
+        None => return div(bump).finish(),
+    };
+    let mut vec_of_nodes = vec![h4(bump)
+        .attr("style", "color:red;")
+        .children([text(
+            bumpalo::format!(in bump, "Something went wrong: {}", client_error).into_bump_str(),
+        )])
+        .finish()];
+    if client_error.retry.is_some() {
+        vec_of_nodes.push(
+            h4(bump)
+                .attr("style", "color:green;")
+                .children([text(
+                    bumpalo::format!(in bump, "Click here to try again{}", "").into_bump_str(),
+                )])
+                .on("click", move |root, vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    if let Some(client_error) = root_rendering_component.client_error.take() {
+                        if let Some(msg) = client_error.retry {
+                            root_rendering_component.transport.send(&msg);
+                        }
+                    }
+                    vdom.schedule_render();
+                })
+                .finish(),
+        );
+    }
+    vec_of_nodes.push(
+        h4(bump)
+            .attr("style", "color:green;")
+            .children([text(
+                bumpalo::format!(in bump, "Click here to start over{}", "").into_bump_str(),
+            )])
+            .on("click", move |root, vdom, _event| {
+                let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                root_rendering_component.client_error = None;
+                root_rendering_component.reset(GameTransition::StartOver);
+                vdom.schedule_render();
+            })
+            .finish(),
+    );
+    div(bump).children(vec_of_nodes).finish()
+}
@@ -1,11 +1,17 @@
 //! game data
 
+use mem3_common::{
+    GameEngine, GameRecord, GameResult, Leaderboard, LeaderboardRow, PlayerInfo, RecordedAction,
+};
 use rand::rngs::SmallRng;
 use rand::seq::SliceRandom;
 use rand::FromEntropy;
 use rand::Rng;
 use strum_macros::AsRefStr;
-use web_sys::WebSocket;
+use wasm_bindgen::prelude::*;
+
+///key in session storage for the game in progress
+const STORAGE_KEY_GAME_DATA: &str = "mem3_game_data";
 
 ///Aviation Spelling
 ///the zero element is card face down or empty, alphabet begins with 01 : A
@@ -17,7 +23,8 @@ pub struct Spelling {
 }
 
 ///the game can be in various states and that differentiate the UI and actions
-#[derive(AsRefStr)]
+///The state changes only with a `GameTransition` allowed in the `next()` transition table.
+#[derive(AsRefStr, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
 pub enum GameState {
     ///the start of the game
     Start,
@@ -25,70 +32,155 @@ pub enum GameState {
     Asking,
     ///Player2 is asked WantToPlay
     Asked,
-    ///play (the turn is in RootRenderingComponent.player_turn)
+    ///play (the turn is in GameEngine.player_turn)
     Play,
+    ///one player paused the game. Cards cannot be clicked.
+    Paused,
+    ///the other player is disconnected. The player may refresh the webpage and come back.
+    WaitingForReconnect,
+    ///the other player left the game and will not come back
+    Abandoned,
     ///end game
     EndGame,
 }
-///the 3 possible states of one card
-#[derive(Serialize, Deserialize)]
-pub enum CardStatusCardFace {
-    ///card face down
-    Down,
-    ///card face Up Temporary
-    UpTemporary,
-    ///card face up Permanently
-    UpPermanently,
+
+///every click and every received message that changes the game state
+#[derive(AsRefStr, Clone, Copy)]
+pub enum GameTransition {
+    ///click on Invite or Reinvite
+    Invite,
+    ///received WantToPlay
+    ReceiveWantToPlay,
+    ///click on Accept
+    Accept,
+    ///click on Decline
+    Decline,
+    ///received AcceptPlay
+    ReceiveAcceptPlay,
+    ///received DeclinePlay
+    ReceiveDeclinePlay,
+    ///received InvitationExpired
+    InvitationExpired,
+    ///click on a card or received PlayerClick
+    Click,
+    ///click on take your turn or received PlayerChange
+    TakeTurn,
+    ///the last pair is found or received EndGame
+    GameOver,
+    ///click on Pause or received PlayerPause
+    Pause,
+    ///click on continue or received PlayerResume
+    Resume,
+    ///the other player is not in the list of online players anymore
+    OpponentGone,
+    ///received RequestResync from the other player
+    OpponentReconnected,
+    ///received ResponseResync after a refresh or a desync
+    Resync,
+    ///click on Leave the game while waiting for the other player
+    Abandon,
+    ///click on Play again after the game
+    Reset,
+    ///click on Play alone
+    PlaySolo,
+    ///click on start over after an error. It is allowed in every state.
+    StartOver,
 }
 
-///all the data for one card
-#[derive(Serialize, Deserialize)]
-pub struct Card {
-    ///card status
-    pub status: CardStatusCardFace,
-    ///field for src attribute for HTML element imagea and filename of card image
-    pub card_number_and_img_src: usize,
-    ///field for id attribute for HTML element image contains the card index
-    pub card_index_and_id: usize,
+impl GameState {
+    ///the transition table. None if the transition is not allowed in this state.
+    pub fn next(self, transition: GameTransition) -> Option<GameState> {
+        use GameState::*;
+        use GameTransition::*;
+        match (self, transition) {
+            (Start, Invite) | (Asking, Invite) | (EndGame, Invite) | (Abandoned, Invite) => {
+                Some(Asking)
+            }
+            (Start, ReceiveWantToPlay)
+            | (Asked, ReceiveWantToPlay)
+            | (EndGame, ReceiveWantToPlay)
+            | (Abandoned, ReceiveWantToPlay) => Some(Asked),
+            (Asked, Accept) | (Asking, ReceiveAcceptPlay) => Some(Play),
+            (Start, PlaySolo) | (EndGame, PlaySolo) | (Abandoned, PlaySolo) => Some(Play),
+            (Asking, ReceiveDeclinePlay) | (Asked, Decline) => Some(Start),
+            //the accept came too late, the other player is not waiting anymore
+            (Asking, InvitationExpired)
+            | (Asked, InvitationExpired)
+            | (Play, InvitationExpired) => Some(Start),
+            (Play, Click) | (Play, TakeTurn) => Some(Play),
+            //the EndGame message comes after the last PlayerClick has already ended the game
+            (Play, GameOver) | (EndGame, GameOver) => Some(EndGame),
+            (Play, Pause) => Some(Paused),
+            (Paused, Resume) => Some(Play),
+            (Play, OpponentGone) | (Paused, OpponentGone) => Some(WaitingForReconnect),
+            (WaitingForReconnect, OpponentReconnected) | (Play, OpponentReconnected) => Some(Play),
+            (Paused, OpponentReconnected) => Some(Paused),
+            //the desync is found after the EndGame message
+            (EndGame, OpponentReconnected) => Some(EndGame),
+            (Play, Resync)
+            | (Paused, Resync)
+            | (WaitingForReconnect, Resync)
+            | (EndGame, Resync) => Some(Play),
+            (WaitingForReconnect, Abandon) => Some(Abandoned),
+            (EndGame, Reset) | (Abandoned, Reset) => Some(Start),
+            (_, StartOver) => Some(Start),
+            _ => None,
+        }
+    }
 }
 ///game data
+///It is stored in the session storage after every change, so a refresh does not lose the game.
+#[derive(Serialize, Deserialize)]
 pub struct GameData {
-    ///vector of cards
-    pub vec_cards: Vec<Card>,
-    //First turn: Player1 clicks 2 times and opens 2 cards.
-    //If cards match, Player1 receives one point and countinues: 2 click for 2 cards.
-    //If not match: Player2 clicks the Change button to close opened cards.
-    //Then starts the Player2 turn.
-    ///count click inside one turn
-    pub count_click_inside_one_turn: usize,
-    ///card index of first click
-    pub card_index_of_first_click: usize,
-    ///card index of second click
-    pub card_index_of_second_click: usize,
+    ///the cards, the turn and the points. All the rules are in the engine.
+    pub engine: GameEngine,
     ///counts only clicks that flip the card. The third click is not counted.
     pub count_all_clicks: usize,
-    ///web socket. used it to send message onclick.
-    pub ws: WebSocket,
+    ///start of the game in milliseconds from `js_sys::Date::now()`
+    pub game_start_ms: f64,
+    ///the leaderboard received from the WebSocket server. None when it is not shown.
+    pub leaderboard: Option<Leaderboard>,
+    ///ratings of all nicknames received from the WebSocket server
+    pub ratings: Vec<LeaderboardRow>,
     ///my ws client instance unique id. To not listen the echo to yourself.
     pub my_ws_uid: usize,
     ///other ws client instance unique id. To listen only to one accepted other player.
+    ///Before the invitation it is the chosen player to invite. Zero means anybody.
     pub other_ws_uid: usize,
+    ///all online players, received from the WebSocket server
+    pub online_players: Vec<PlayerInfo>,
+    ///my nickname from local storage
+    pub my_nickname: String,
+    ///my avatar from local storage
+    pub my_avatar: String,
+    ///my rating from the WebSocket server
+    pub my_rating: u32,
+    ///nickname of the other player from the invitation
+    pub other_nickname: String,
+    ///avatar of the other player from the invitation
+    pub other_avatar: String,
+    ///the form to choose nickname and avatar is open on the first launch
+    pub is_nickname_form_open: bool,
+    ///short message about the invitation: declined, expired,...
+    pub status_message: String,
+    ///the players don't agree about the game. Empty when everything is fine.
+    pub desync_message: String,
     ///game state: Start,Asking,Asked,Player1,Player2
     pub game_state: GameState,
     ///content folder name
     pub content_folder_name: String,
     ///What player am I
     pub this_machine_player_number: usize,
-    ///whose turn is now:  player 1 or 2
-    pub player_turn: usize,
-    ///player1 points
-    pub player1_points: usize,
-    ///player2 points
-    pub player2_points: usize,
     ///content folders vector
     pub content_folders: Vec<String>,
     ///spellings
     pub spelling: Option<Spelling>,
+    ///the deck and all the moves of the game for the replay
+    #[serde(default)]
+    pub game_record: GameRecord,
+    ///the player plays alone. There is no other player and no message about the moves.
+    #[serde(default)]
+    pub is_solo: bool,
 }
 impl GameData {
     ///prepare new random data
@@ -118,70 +210,218 @@ impl GameData {
         vrndslice.shuffle(&mut rng);
         //endregion
 
-        //region: create Cards from random numbers
-        let mut vec_cards = Vec::new();
-
-        //Index 0 is special and reserved for FaceDown. Cards start with base 1
-        let new_card = Card {
-            status: CardStatusCardFace::Down,
-            card_number_and_img_src: 0,
-            card_index_and_id: 0,
-        };
-        vec_cards.push(new_card);
-
-        //create the 16 card and push to the vector
-        for (index, random_number) in vec_of_random_numbers.iter().enumerate() {
-            let new_card = Card {
-                status: CardStatusCardFace::Down,
-                //dereference random number from iterator
-                card_number_and_img_src: *random_number,
-                //card base index will be 1. 0 is reserved for FaceDown.
-                card_index_and_id: index.checked_add(1).expect("usize overflow"),
-            };
-            vec_cards.push(new_card);
+        self.engine = GameEngine::from_card_numbers(&vec_of_random_numbers)
+            .expect("8 distinct numbers twice are a deck");
+    }
+    ///the player number of the other machine: 1 or 2
+    pub fn other_player_number(&self) -> usize {
+        if self.this_machine_player_number == 1 {
+            2
+        } else {
+            1
         }
-        //endregion
-        self.vec_cards = vec_cards;
-    }
-    ///asociated function: before Accept, there are not random numbers, just default cards.
-    pub fn prepare_for_empty() -> Vec<Card> {
-        //prepare 16 empty cards. The random is calculated only on AcceptPlay.
-        let mut vec_cards = Vec::new();
-        //I must prepare the 0 index, but then I don't use it ever.
-        for i in 0..=16 {
-            let new_card = Card {
-                status: CardStatusCardFace::Down,
-                card_number_and_img_src: 1,
-                card_index_and_id: i,
-            };
-            vec_cards.push(new_card);
+    }
+    ///nickname and avatar of player 1 or 2
+    pub fn player_nickname_and_avatar(&self, player_number: usize) -> (&str, &str) {
+        if player_number == self.this_machine_player_number {
+            (&self.my_nickname, &self.my_avatar)
+        } else {
+            (&self.other_nickname, &self.other_avatar)
         }
-        vec_cards
+    }
+    ///the result of the finished game for the leaderboard
+    pub fn game_result(&self) -> GameResult {
+        let (player1_nickname, _) = self.player_nickname_and_avatar(1);
+        let (player2_nickname, _) = self.player_nickname_and_avatar(2);
+        //the duration is always positive and much smaller than u64::MAX
+        let duration_ms = (js_sys::Date::now() - self.game_start_ms).max(0.0) as u64;
+        GameResult {
+            content_folder_name: self.content_folder_name.clone(),
+            board_size: self.engine.board_size(),
+            player1_nickname: player1_nickname.to_string(),
+            player2_nickname: player2_nickname.to_string(),
+            player1_points: self.engine.player1_points,
+            player2_points: self.engine.player2_points,
+            count_all_flips: self.engine.count_all_flips,
+            duration_ms,
+            //the server writes the times
+            started_at: 0,
+            ended_at: 0,
+            is_solo: self.is_solo,
+        }
+    }
+    ///the record starts with the deck, before the first move
+    pub fn start_game_record(&mut self) {
+        let (player1_nickname, _) = self.player_nickname_and_avatar(1);
+        let (player2_nickname, _) = self.player_nickname_and_avatar(2);
+        self.game_record = GameRecord::new(
+            &self.content_folder_name,
+            player1_nickname,
+            player2_nickname,
+            &self.engine,
+            self.game_start_ms as u64,
+        );
+    }
+    ///add the move with the time since the start of the game
+    pub fn record_move(&mut self, player_number: usize, action: RecordedAction) {
+        let at_ms = (js_sys::Date::now() - self.game_start_ms).max(0.0) as u64;
+        self.game_record.push(at_ms, player_number, action);
     }
     ///constructor of game data
-    pub fn new(ws: WebSocket, my_ws_uid: usize) -> Self {
+    pub fn new(my_ws_uid: usize, my_nickname: String, my_avatar: String) -> Self {
         //return from constructor
         GameData {
-            vec_cards: Self::prepare_for_empty(),
-            count_click_inside_one_turn: 0,
-            card_index_of_first_click: 0,
-            card_index_of_second_click: 0,
+            engine: GameEngine::empty(),
             count_all_clicks: 0,
-            ws,
+            game_start_ms: 0.0,
+            leaderboard: None,
+            ratings: Vec::new(),
             my_ws_uid,
             other_ws_uid: 0, //zero means not accepted yet
+            online_players: Vec::new(),
+            is_nickname_form_open: my_nickname.is_empty(),
+            my_nickname,
+            my_avatar,
+            my_rating: 0,
+            other_nickname: String::new(),
+            other_avatar: String::new(),
+            status_message: String::new(),
+            desync_message: String::new(),
             game_state: GameState::Start,
             content_folder_name: "alphabet".to_string(),
-            player1_points: 0,
-            player2_points: 0,
             this_machine_player_number: 0, //unknown until WantToPlay+Accept
-            player_turn: 0,
             content_folders: vec![
                 String::from("alphabet"),
                 String::from("animals"),
                 String::from("negative"),
             ],
             spelling: None,
+            game_record: GameRecord::default(),
+            is_solo: false,
         }
     }
+    ///the spelling of the card. Empty if the spelling is not yet received.
+    pub fn card_spelling(&self, card_index: usize) -> &str {
+        self.engine
+            .vec_cards
+            .get(card_index)
+            .and_then(|card| {
+                self.spelling
+                    .as_ref()
+                    .and_then(|spelling| spelling.name.get(card.card_number_and_img_src))
+            })
+            .map_or("", String::as_str)
+    }
+    ///write the game to the session storage. The storage can be full or disabled.
+    pub fn save_to_session_storage(&self) -> Result<(), JsValue> {
+        let json = self
+            .to_session_json()
+            .map_err(|err| JsValue::from_str(&err.to_string()))?;
+        crate::session_storage()
+            .ok_or_else(|| JsValue::from_str("no session storage"))?
+            .set_item(STORAGE_KEY_GAME_DATA, &json)
+    }
+    ///read the game from the session storage after a refresh. None if there is nothing to restore.
+    pub fn load_from_session_storage() -> Option<Self> {
+        let json = crate::session_storage()?
+            .get_item(STORAGE_KEY_GAME_DATA)
+            .ok()??;
+        Self::from_session_json(&json)
+    }
+    ///the json in the session storage
+    pub fn to_session_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+    ///the game from the json in the session storage. None if it is not a game.
+    pub fn from_session_json(json: &str) -> Option<Self> {
+        serde_json::from_str(json).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_STATES: [GameState; 8] = [
+        GameState::Start,
+        GameState::Asking,
+        GameState::Asked,
+        GameState::Play,
+        GameState::Paused,
+        GameState::WaitingForReconnect,
+        GameState::Abandoned,
+        GameState::EndGame,
+    ];
+
+    #[test]
+    fn a_whole_game_goes_through_the_table() {
+        let mut state = GameState::Start;
+        for transition in &[
+            GameTransition::Invite,
+            GameTransition::ReceiveAcceptPlay,
+            GameTransition::Click,
+            GameTransition::Click,
+            GameTransition::TakeTurn,
+            GameTransition::Pause,
+            GameTransition::Resume,
+            GameTransition::GameOver,
+            GameTransition::Reset,
+        ] {
+            state = state
+                .next(*transition)
+                .unwrap_or_else(|| panic!("{} in {}", transition.as_ref(), state.as_ref()));
+        }
+        assert_eq!(state, GameState::Start);
+    }
+
+    #[test]
+    fn decline_and_abandon_go_back_to_start() {
+        assert_eq!(
+            GameState::Asked.next(GameTransition::Decline),
+            Some(GameState::Start)
+        );
+        let abandoned = GameState::Play
+            .next(GameTransition::OpponentGone)
+            .and_then(|state| state.next(GameTransition::Abandon));
+        assert_eq!(abandoned, Some(GameState::Abandoned));
+        assert_eq!(
+            GameState::Abandoned.next(GameTransition::Reset),
+            Some(GameState::Start)
+        );
+    }
+
+    #[test]
+    fn start_over_is_allowed_in_every_state() {
+        for state in &ALL_STATES {
+            assert_eq!(
+                state.next(GameTransition::StartOver),
+                Some(GameState::Start)
+            );
+        }
+    }
+
+    #[test]
+    fn illegal_transitions_are_rejected() {
+        //a running game is not thrown away with Play again
+        assert_eq!(GameState::Play.next(GameTransition::Reset), None);
+        assert_eq!(GameState::Paused.next(GameTransition::Reset), None);
+        //only the invited player can decline
+        assert_eq!(GameState::Asking.next(GameTransition::Decline), None);
+        assert_eq!(GameState::Play.next(GameTransition::Decline), None);
+        //the other player must be gone to leave the game
+        assert_eq!(GameState::Play.next(GameTransition::Abandon), None);
+        //no cards before the game and no cards while paused
+        assert_eq!(GameState::Start.next(GameTransition::Click), None);
+        assert_eq!(GameState::Paused.next(GameTransition::Click), None);
+        assert_eq!(GameState::EndGame.next(GameTransition::TakeTurn), None);
+        assert_eq!(GameState::Start.next(GameTransition::Resume), None);
+        assert_eq!(GameState::Start.next(GameTransition::Accept), None);
+        //a solo game starts only without an invitation or a running game
+        assert_eq!(GameState::Asking.next(GameTransition::PlaySolo), None);
+        assert_eq!(GameState::Play.next(GameTransition::PlaySolo), None);
+        assert_eq!(
+            GameState::EndGame.next(GameTransition::PlaySolo),
+            Some(GameState::Play)
+        );
+    }
 }
@@ -0,0 +1,135 @@
+//! leaderboard
+//! The WebSocket server stores the finished games and sends the leaderboard on request.
+
+use crate::{RootRenderingComponent, BOARD_SIZE};
+use dodrio::builder::*;
+use dodrio::bumpalo::{self, Bump};
+use dodrio::Node;
+use mem3_common::{LeaderboardRow, WsMessage};
+
+///links to request the leaderboard for every deck
+pub fn div_leaderboard_links<'a, 'bump>(
+    root_rendering_component: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Node<'bump>
+where
+    'a: 'bump,
+{
+    let mut vec_of_nodes = Vec::new();
+    //I don't know how to solve the lifetime problems. So I just clone the small data.
+    let content_folders = root_rendering_component.game_data.content_folders.clone();
+    for folder_name in content_folders {
+        vec_of_nodes.push(
+            h5(bump)
+                .attr("style", "color:gray;")
+                .children([text(
+                    bumpalo::format!(in bump, "Leaderboard for {}", folder_name).into_bump_str(),
+                )])
+                .on("click", move |root, _vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    //the ratings are for all decks
+                    root_rendering_component
+                        .transport
+                        .send(&WsMessage::RequestRatings {
+                            my_ws_uid: root_rendering_component.game_data.my_ws_uid,
+                        });
+                    //the response will render the leaderboard
+                    root_rendering_component
+                        .transport
+                        .send(&WsMessage::RequestLeaderboard {
+                            content_folder_name: folder_name.clone(),
+                            board_size: BOARD_SIZE,
+                        });
+                })
+                .finish(),
+        );
+    }
+    div(bump).children(vec_of_nodes).finish()
+}
+
+///the leaderboard with 4 columns: most wins, best solo time, fewest flips and rating
+pub fn div_leaderboard<'a, 'bump>(
+    root_rendering_component: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Node<'bump>
+where
+    'a: 'bump,
+{
+    let leaderboard = match &root_rendering_component.game_data.leaderboard {
+        Some(leaderboard) => leaderboard,
+        None => return div(bump).finish(),
+    };
+
+    div(bump)
+        .children([
+            h3(bump)
+                .children([text(
+                    bumpalo::format!(in bump, "Leaderboard for {}", leaderboard.content_folder_name)
+                        .into_bump_str(),
+                )])
+                .finish(),
+            div(bump)
+                .attr("class", "grid_container_players")
+                .attr("style", "grid-template-columns: auto auto auto auto;")
+                .children([
+                    div_leaderboard_column("Most wins", &leaderboard.most_wins, false, bump),
+                    div_leaderboard_column(
+                        "Best solo time",
+                        &leaderboard.best_solo_time,
+                        true,
+                        bump,
+                    ),
+                    div_leaderboard_column("Fewest flips", &leaderboard.fewest_flips, false, bump),
+                    div_leaderboard_column(
+                        "Rating",
+                        &root_rendering_component.game_data.ratings,
+                        false,
+                        bump,
+                    ),
+                ])
+                .finish(),
+            h3(bump)
+                .attr("style", "color:green;")
+                .children([text(
+                    bumpalo::format!(in bump, "Close the leaderboard{}", "").into_bump_str(),
+                )])
+                .on("click", move |root, vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    root_rendering_component.game_data.leaderboard = None;
+                    vdom.schedule_render();
+                })
+                .finish(),
+        ])
+        .finish()
+}
+
+///one column of the leaderboard. The time is in milliseconds and shown in seconds.
+fn div_leaderboard_column<'bump>(
+    title: &str,
+    rows: &[LeaderboardRow],
+    is_time: bool,
+    bump: &'bump Bump,
+) -> Node<'bump> {
+    let mut vec_of_nodes = Vec::new();
+    vec_of_nodes.push(
+        h4(bump)
+            .children([text(bumpalo::format!(in bump, "{}", title).into_bump_str())])
+            .finish(),
+    );
+    for row in rows {
+        let value = if is_time {
+            bumpalo::format!(in bump, "{} s", row.value / 1000).into_bump_str()
+        } else {
+            bumpalo::format!(in bump, "{}", row.value).into_bump_str()
+        };
+        vec_of_nodes.push(
+            div(bump)
+                .attr("class", "grid_item")
+                .children([text(
+                    bumpalo::format!(in bump, "{}: {}", row.nickname, value).into_bump_str(),
+                )])
+                .finish(),
+        );
+    }
+    div(bump).children(vec_of_nodes).finish()
+}
@@ -41,15 +41,29 @@
 //endregion
 
 //region: extern and use statements
+mod broadcastchannel;
+mod clienterror;
 mod gamedata;
+mod leaderboard;
+mod nicknameandavatar;
 mod playersandscores;
+mod replay;
 mod rulesanddescription;
+mod transport;
 mod websocketcommunication;
-use crate::gamedata::{Card, CardStatusCardFace, GameData, GameState};
+use crate::broadcastchannel::BroadcastChannelTransport;
+use crate::clienterror::{div_client_error, ClientError};
+use crate::gamedata::{GameData, GameState, GameTransition};
+use crate::leaderboard::{div_leaderboard, div_leaderboard_links};
+use crate::nicknameandavatar::{
+    div_nickname_and_avatar, load_nickname_and_avatar, span_avatar_and_nickname,
+};
 use crate::playersandscores::PlayersAndScores;
+use crate::replay::{div_replay, div_replay_links, load_game_records, save_game_record, Replay};
 use crate::rulesanddescription::RulesAndDescription;
-use crate::websocketcommunication::setup_ws_connection;
+use crate::transport::{ConnectionState, LocalTransport, Transport};
 use crate::websocketcommunication::setup_ws_msg_recv;
+use crate::websocketcommunication::WebSocketTransport;
 
 //Strum is a set of macros and traits for working with enums and strings easier in Rust.
 extern crate console_error_panic_hook;
@@ -67,23 +81,39 @@ use dodrio::builder::*;
 use dodrio::bumpalo::{self, Bump};
 use dodrio::{Cached, Node, Render};
 
-use mem3_common::WsMessage;
+use mem3_common::{
+    Card, CardStatusCardFace, ErrorCode, GameEngine, GameEvent, GameRecord, Leaderboard,
+    LeaderboardRow, PlayerInfo, RecordedAction, WsMessage,
+};
 use rand::rngs::SmallRng;
 use rand::FromEntropy;
 use rand::Rng;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::{console, WebSocket};
+use web_sys::console;
 //endregion
 
 //region: enum, structs, const,...
 ///game title
 const GAME_TITLE: &str = "mem3";
+///opponents with a rating this close are a fair match
+const FAIR_RATING_DIFFERENCE: u32 = 100;
 ///fixed filename for card face down
 const SRC_FOR_CARD_FACE_DOWN: &str = "img/mem_image_00_cardfacedown.png";
+///all decks have the same board size: the css grid has 4x4 cards
+const BOARD_SIZE: usize = 16;
+///the grid shows this card if the board has no card in the place
+const CARD_NOT_ON_THE_BOARD: Card = Card {
+    status: CardStatusCardFace::Down,
+    card_number_and_img_src: 0,
+    card_index_and_id: 0,
+};
 
 ///Root Render Component: the card grid struct has all the needed data for play logic and rendering
 struct RootRenderingComponent {
+    ///send messages to the other player onclick
+    transport: Rc<dyn Transport>,
     ///game data will be inside of Root, but reference for all other RenderingComponents
     game_data: GameData,
     ///subComponent: score
@@ -91,6 +121,16 @@ struct RootRenderingComponent {
     ///subComponent: the static parts can be cached.
     /// I am not sure if a field in this struct is the best place to put it.
     cached_rules_and_description: Cached<RulesAndDescription>,
+    ///the last error in the message path. It is shown in a banner until retry or reset.
+    client_error: Option<ClientError>,
+    ///false when the heartbeats of the server stop
+    is_connection_alive: bool,
+    ///the connection to the WebSocket server
+    connection_state: ConnectionState,
+    ///the records of the last games from the local storage, the newest first
+    game_records: Vec<GameRecord>,
+    ///the grid shows the replay instead of the game. None when there is no replay.
+    replay: Option<Replay>,
 }
 //endregion
 
@@ -115,21 +155,44 @@ pub fn run() -> Result<(), JsValue> {
     //find out URL
     let location_href = window.location().href().expect("href not known");
 
-    //websocket connection
-    let ws = setup_ws_connection(location_href.as_str());
-    //I don't know why is needed to clone the websocket connection
-    let ws_c = ws.clone();
+    //nickname and avatar from the last launch. Empty on the first launch.
+    let (nickname, avatar) = load_nickname_and_avatar();
 
-    // Construct a new `RootRenderingComponent`.
-    //I added ws_c so that I can send messages on websocket
+    //with ?broadcastchannel in the url two tabs of this browser play without the WebSocket server
+    let search = window.location().search().unwrap_or_default();
+    let transport: Rc<dyn Transport> = if search.contains("broadcastchannel") {
+        match BroadcastChannelTransport::new(my_ws_uid, &nickname, &avatar) {
+            Ok(broadcast_channel_transport) => Rc::new(broadcast_channel_transport),
+            Err(err) => {
+                console::log_2(&"BroadcastChannel is not supported.".into(), &err);
+                Rc::new(LocalTransport)
+            }
+        }
+    } else {
+        //websocket connection. Without a http server there is no WebSocket server and no other player.
+        match WebSocketTransport::new(location_href.as_str(), &nickname, &avatar) {
+            Ok(websocket_transport) => Rc::new(websocket_transport),
+            Err(err) => {
+                console::log_2(&"WebSocket failed to connect.".into(), &err);
+                Rc::new(LocalTransport)
+            }
+        }
+    };
 
-    let root_rendering_component = RootRenderingComponent::new(ws_c, my_ws_uid);
+    // Construct a new `RootRenderingComponent`.
+    //The transport is shared so that I can send messages onclick
+    let mut root_rendering_component =
+        RootRenderingComponent::new(Rc::clone(&transport), my_ws_uid, nickname, avatar);
+    //after a refresh continue the game from the session storage
+    root_rendering_component.restore_from_session_storage();
+    //the records of the last games for the replay
+    root_rendering_component.game_records = load_game_records();
 
     // Mount the component to the `<div id="div_for_virtual_dom">`.
     let vdom = dodrio::Vdom::new(&div_for_virtual_dom, root_rendering_component);
 
     //websocket on receive message callback
-    setup_ws_msg_recv(&ws, &vdom);
+    setup_ws_msg_recv(transport.as_ref(), &vdom);
 
     // Run the component forever. Forget to drop the memory.
     vdom.forget();
@@ -149,10 +212,37 @@ fn text_with_br_newline<'a>(txt: &'a str, bump: &'a Bump) -> Vec<Node<'a>> {
     }
     vec_text_node
 }
-/// Get the top-level window's session storage.
-pub fn session_storage() -> web_sys::Storage {
+///difference between two ratings to find fair opponents
+fn rating_difference(rating: u32, my_rating: u32) -> u32 {
+    if rating > my_rating {
+        rating - my_rating
+    } else {
+        my_rating - rating
+    }
+}
+///the board of a running game from the other player, from the storage or from a record
+///must fit in the grid
+fn check_engine(engine: &GameEngine) -> Result<(), String> {
+    if engine.board_size() != BOARD_SIZE {
+        return Err(format!("the board has {} cards", engine.board_size()));
+    }
+    engine.check_board().map_err(|err| err.to_string())
+}
+///the saved game to continue after a refresh. None if no game was running.
+///Before Accept and after a reset the engine is only the empty placeholder and is not checked.
+fn restorable_game_data(game_data: GameData) -> Result<Option<GameData>, String> {
+    match game_data.game_state {
+        GameState::Play | GameState::Paused | GameState::EndGame => {
+            check_engine(&game_data.engine)?;
+            Ok(Some(game_data))
+        }
+        _ => Ok(None),
+    }
+}
+/// Get the top-level window's session storage. None if the browser disabled it.
+pub fn session_storage() -> Option<web_sys::Storage> {
     let window = web_sys::window().expect("error: web_sys::window");
-    window.session_storage().unwrap_throw().unwrap_throw()
+    window.session_storage().ok().flatten()
 }
 //endregion
 
@@ -163,17 +253,28 @@ pub fn session_storage() -> web_sys::Storage {
 //It knows nothing about HTML and Virtual dom.
 impl RootRenderingComponent {
     /// Construct a new `RootRenderingComponent` component. Only once at the begining.
-    pub fn new(ws: WebSocket, my_ws_uid: usize) -> Self {
-        let game_data = GameData::new(ws, my_ws_uid);
+    pub fn new(
+        transport: Rc<dyn Transport>,
+        my_ws_uid: usize,
+        nickname: String,
+        avatar: String,
+    ) -> Self {
+        let game_data = GameData::new(my_ws_uid, nickname, avatar);
 
         let game_rule_01 = RulesAndDescription {};
         let cached_rules_and_description = Cached::new(game_rule_01);
         let players_and_scores = Cached::new(PlayersAndScores::new());
 
         RootRenderingComponent {
+            transport,
             game_data,
             players_and_scores,
             cached_rules_and_description,
+            client_error: None,
+            is_connection_alive: true,
+            connection_state: ConnectionState::Connecting,
+            game_records: Vec::new(),
+            replay: None,
         }
     }
     ///check invalidate render cache for all sub components
@@ -182,201 +283,593 @@ impl RootRenderingComponent {
             Cached::invalidate(&mut self.players_and_scores);
         }
     }
-    ///The onclick event passed by javascript executes all the logic
-    ///and changes only the fields of the Card Grid struct.
-    ///That stuct is the only permanent data storage for later render the virtual dom.
-    fn card_on_click(&mut self) {
-        //get this_click_card_index from game_data
-        let this_click_card_index = if self.game_data.count_click_inside_one_turn == 1 {
-            self.game_data.card_index_of_first_click
-        } else {
-            self.game_data.card_index_of_second_click
-        };
-
-        if self.game_data.count_click_inside_one_turn == 1
-            || self.game_data.count_click_inside_one_turn == 2
-        {
-            //region: audio play
-            //prepare the audio element with src filename of mp3
-            let audio_element = web_sys::HtmlAudioElement::new_with_src(
-                format!(
-                    "content/{}/sound/mem_sound_{:02}.mp3",
-                    self.game_data.content_folder_name,
-                    self.game_data
-                        .vec_cards
-                        .get(this_click_card_index)
-                        .expect("error this_click_card_index")
-                        .card_number_and_img_src
-                )
-                .as_str(),
-            );
+    ///The engine executes all the rules and returns the events.
+    ///Here are only the consequences in the browser: sound and messages.
+    ///is_my_move is false for the moves of the other player received over WebSocket.
+    fn on_game_events(&mut self, events: &[GameEvent], is_my_move: bool) {
+        for event in events {
+            match event {
+                GameEvent::CardFlipped {
+                    card_index,
+                    card_number_and_img_src,
+                } => {
+                    //the card is flipped by the player on turn
+                    let player_number = self.game_data.engine.player_turn;
+                    self.game_data.record_move(
+                        player_number,
+                        RecordedAction::Click {
+                            card_index: *card_index,
+                        },
+                    );
+                    //region: audio play
+                    //prepare the audio element with src filename of mp3
+                    let audio_element = web_sys::HtmlAudioElement::new_with_src(
+                        format!(
+                            "content/{}/sound/mem_sound_{:02}.mp3",
+                            self.game_data.content_folder_name, card_number_and_img_src
+                        )
+                        .as_str(),
+                    );
 
-            //play() return a Promise in JSValue. That is too hard for me to deal with now.
-            audio_element
-                .expect("Error: HtmlAudioElement new.")
-                .play()
-                .expect("Error: HtmlAudioElement.play() ");
-            //endregion
-
-            //flip the card up
-            self.game_data
-                .vec_cards
-                .get_mut(this_click_card_index)
-                .expect("error this_click_card_index")
-                .status = CardStatusCardFace::UpTemporary;
-
-            if self.game_data.count_click_inside_one_turn == 2 {
-                //if is the second click, flip the card and then check for card match
-
-                //if the cards match, player get one point and continues another turn
-                if self
-                    .game_data
-                    .vec_cards
-                    .get(self.game_data.card_index_of_first_click)
-                    .expect("error game_data.card_index_of_first_click")
-                    .card_number_and_img_src
-                    == self
-                        .game_data
-                        .vec_cards
-                        .get(self.game_data.card_index_of_second_click)
-                        .expect("error game_data.card_index_of_second_click")
-                        .card_number_and_img_src
-                {
-                    //give points
-                    if self.game_data.player_turn == 1 {
-                        self.game_data.player1_points += 1;
-                    } else {
-                        self.game_data.player2_points += 1
+                    //play() return a Promise in JSValue. That is too hard for me to deal with now.
+                    //The game continues without the sound.
+                    if let Err(err) = audio_element.and_then(|audio_element| audio_element.play()) {
+                        console::log_2(&"audio play failed".into(), &err);
                     }
-
-                    // the two cards matches. make them permanent FaceUp
-                    let x1 = self.game_data.card_index_of_first_click;
-                    let x2 = self.game_data.card_index_of_second_click;
-                    self.game_data
-                        .vec_cards
-                        .get_mut(x1)
-                        .expect("error game_data.card_index_of_first_click")
-                        .status = CardStatusCardFace::UpPermanently;
-                    self.game_data
-                        .vec_cards
-                        .get_mut(x2)
-                        .expect("error game_data.card_index_of_second_click")
-                        .status = CardStatusCardFace::UpPermanently;
-                    self.game_data.count_click_inside_one_turn = 0;
-                    //the sum of points is 8, the game is over
-                    if self.game_data.player1_points + self.game_data.player2_points == 8 {
-                        self.game_data.game_state = GameState::EndGame;
-                        //send message
-                        self.game_data
-                            .ws
-                            .send_with_str(
-                                &serde_json::to_string(&WsMessage::EndGame {
-                                    my_ws_uid: self.game_data.my_ws_uid,
-                                    other_ws_uid: self.game_data.other_ws_uid,
-                                })
-                                .expect("error sending EndGame"),
-                            )
-                            .expect("Failed to send EndGame");
+                    //endregion
+                }
+                GameEvent::GameOver { .. } => {
+                    self.transition(GameTransition::GameOver);
+                    //both players keep the record for the replay
+                    save_game_record(&mut self.game_records, &self.game_data.game_record);
+                    //only the player that found the last pair sends the result to the server.
+                    //The solo game has its own message without the other player.
+                    if self.game_data.is_solo {
+                        self.transport.send(&WsMessage::EndSoloGame {
+                            my_ws_uid: self.game_data.my_ws_uid,
+                            game_result: self.game_data.game_result(),
+                        });
+                    } else if is_my_move {
+                        self.transport.send(&WsMessage::EndGame {
+                            my_ws_uid: self.game_data.my_ws_uid,
+                            other_ws_uid: self.game_data.other_ws_uid,
+                            game_result: self.game_data.game_result(),
+                            state_hash: self.game_data.engine.state_hash(),
+                        });
                     }
                 }
+                //the player that takes the turn closed the cards
+                GameEvent::TurnChanged { player_turn } => {
+                    let action = if self.game_data.is_solo {
+                        RecordedAction::Continue
+                    } else {
+                        RecordedAction::Change
+                    };
+                    self.game_data.record_move(*player_turn, action);
+                }
+                GameEvent::PairMatched { .. } | GameEvent::PairNotMatched => {}
             }
         }
+        self.save_game_data();
         self.check_invalidate_for_all_components();
     }
-    ///fn on change for both click and we msg.
-    fn take_turn(&mut self) {
-        self.game_data.player_turn = if self.game_data.player_turn == 1 {
-            2
-        } else {
-            1
-        };
-
-        //click on Change button closes first and second card
-        let x1 = self.game_data.card_index_of_first_click;
-        let x2 = self.game_data.card_index_of_second_click;
-        self.game_data
-            .vec_cards
-            .get_mut(x1)
-            .expect("error game_data.card_index_of_first_click ")
-            .status = CardStatusCardFace::Down;
-        self.game_data
-            .vec_cards
-            .get_mut(x2)
-            .expect("error game_data.card_index_of_second_click")
-            .status = CardStatusCardFace::Down;
-        self.game_data.card_index_of_first_click = 0;
-        self.game_data.card_index_of_second_click = 0;
-        self.game_data.count_click_inside_one_turn = 0;
+    ///store the game after every transition, so a refresh of the page continues it.
+    ///The storage can be full or disabled, then the refresh starts a new game.
+    fn save_game_data(&self) {
+        if let Err(err) = self.game_data.save_to_session_storage() {
+            console::log_1(&format!("save_to_session_storage: {:?}", err).into());
+        }
+    }
+    ///fn on change for both click and we msg. The player_number is the player that takes the turn.
+    ///Returns false if the turn cannot change now.
+    fn take_turn(&mut self, player_number: usize) -> bool {
+        if !self.transition(GameTransition::TakeTurn) {
+            return false;
+        }
+        match self.game_data.engine.take_turn(player_number) {
+            Ok(events) => {
+                self.on_game_events(&events, false);
+                true
+            }
+            Err(err) => {
+                console::log_1(&format!("take_turn: {}", err).into());
+                false
+            }
+        }
+    }
+    ///in a solo game the player closes the two cards that don't match and continues.
+    ///Returns false if the cards cannot be closed now.
+    fn continue_alone(&mut self) -> bool {
+        if !self.transition(GameTransition::TakeTurn) {
+            return false;
+        }
+        let player_number = self.game_data.this_machine_player_number;
+        match self.game_data.engine.continue_alone(player_number) {
+            Ok(events) => {
+                self.on_game_events(&events, true);
+                true
+            }
+            Err(err) => {
+                console::log_1(&format!("continue_alone: {}", err).into());
+                false
+            }
+        }
+    }
+    ///the next game state if the transition is allowed. Illegal transitions are logged.
+    fn next_game_state(&self, transition: GameTransition) -> Option<GameState> {
+        let next = self.game_data.game_state.next(transition);
+        if next.is_none() {
+            console::log_1(
+                &format!(
+                    "illegal transition {} in state {}",
+                    transition.as_ref(),
+                    self.game_data.game_state.as_ref()
+                )
+                .into(),
+            );
+        }
+        next
+    }
+    ///change the game state only if the transition is allowed. Returns false if it is ignored.
+    ///The status message belongs to the old state.
+    fn transition(&mut self, transition: GameTransition) -> bool {
+        match self.next_game_state(transition) {
+            Some(next) => {
+                if next != self.game_data.game_state {
+                    self.game_data.status_message = String::new();
+                }
+                self.game_data.game_state = next;
+                true
+            }
+            None => false,
+        }
+    }
+    ///reset the data to replay the game, if the transition is allowed
+    fn reset(&mut self, transition: GameTransition) -> bool {
+        if !self.transition(transition) {
+            return false;
+        }
+        self.clear_game_data();
+        self.save_game_data();
         self.check_invalidate_for_all_components();
+        true
     }
-    ///reset the data to replay the game
-    fn reset(&mut self) {
-        self.game_data.vec_cards = GameData::prepare_for_empty();
-        self.game_data.count_click_inside_one_turn = 0;
-        self.game_data.card_index_of_first_click = 0;
-        self.game_data.card_index_of_second_click = 0;
+    ///forget the last game, but not the state
+    fn clear_game_data(&mut self) {
+        self.game_data.engine = GameEngine::empty();
         self.game_data.count_all_clicks = 0;
+        self.game_data.game_start_ms = 0.0;
         self.game_data.other_ws_uid = 0;
-        self.game_data.game_state = GameState::Start;
+        self.game_data.other_nickname = String::new();
+        self.game_data.other_avatar = String::new();
+        self.game_data.status_message = String::new();
+        self.game_data.desync_message = String::new();
         self.game_data.content_folder_name = "alphabet".to_string();
-        self.game_data.player1_points = 0;
-        self.game_data.player2_points = 0;
         self.game_data.this_machine_player_number = 0;
-        self.game_data.player_turn = 0;
         self.game_data.spelling = None;
-
+        self.game_data.game_record = GameRecord::default();
+        self.game_data.is_solo = false;
+    }
+    ///the player plays alone with this deck. The server notes the start for the best solo time.
+    fn start_solo_game(&mut self, content_folder_name: &str) -> bool {
+        if !self.transition(GameTransition::PlaySolo) {
+            return false;
+        }
+        self.clear_game_data();
+        self.game_data.is_solo = true;
+        self.game_data.content_folder_name = content_folder_name.to_string();
+        self.game_data.this_machine_player_number = 1;
+        self.game_data.prepare_random_data();
+        self.game_data.game_start_ms = js_sys::Date::now();
+        self.game_data.start_game_record();
+        self.transport.send(&WsMessage::RequestSpelling {
+            filename: format!("content/{}/text.json", content_folder_name),
+        });
+        self.transport.send(&WsMessage::StartSoloGame {
+            my_ws_uid: self.game_data.my_ws_uid,
+            content_folder_name: content_folder_name.to_string(),
+        });
+        self.save_game_data();
         self.check_invalidate_for_all_components();
+        true
+    }
+    ///after a refresh continue the game saved in the session storage.
+    ///An invitation is not restored, because the other player knows only the old ws_uid.
+    fn restore_from_session_storage(&mut self) {
+        if let Some(game_data) = GameData::load_from_session_storage() {
+            match restorable_game_data(game_data) {
+                Ok(Some(mut game_data)) => {
+                    //the old ws_uid stays until ResponseWsUid, to send it with RequestResync
+                    game_data.leaderboard = None;
+                    game_data.is_nickname_form_open = self.game_data.is_nickname_form_open;
+                    self.game_data = game_data;
+                    self.check_invalidate_for_all_components();
+                }
+                Ok(None) => {}
+                Err(err) => self.on_client_error(ClientError::new("session storage", &err)),
+            }
+        }
+    }
+    ///show the replay of the record instead of the game
+    fn start_replay(&mut self, game_record: GameRecord) {
+        if game_record.deck.len() != BOARD_SIZE {
+            let detail = format!("the board has {} cards", game_record.deck.len());
+            self.on_client_error(ClientError::new("replay", &detail));
+            return;
+        }
+        match Replay::new(game_record) {
+            Ok(replay) => self.replay = Some(replay),
+            Err(err) => self.on_client_error(ClientError::new("replay", &err.to_string())),
+        }
+    }
+    ///a bad message or a failed send. The app continues and shows the error.
+    fn on_client_error(&mut self, client_error: ClientError) {
+        client_error.log();
+        self.client_error = Some(client_error);
     }
     //region: all functions for receive message (like events)
     // I separate the code into functions to avoid looking at all that boilerplate in the big match around futures and components.
     // All the data changing must be encapsulated inside these functions.
     ///msg response we uid
-    fn on_response_ws_uid(&mut self, your_ws_uid: usize) {
+    fn on_response_ws_uid(&mut self, your_ws_uid: usize, is_reconnect: bool) {
+        let old_ws_uid = self.game_data.my_ws_uid;
         self.game_data.my_ws_uid = your_ws_uid;
+        //after a refresh without a session the WebSocket server gives a new ws_uid.
+        //After a restart of the server the session has the same ws_uid, but the game is behind.
+        //The other player must know it and sends back the state of the game.
+        //A solo game has no other player and all the state is here.
+        if let GameState::Play | GameState::Paused = self.game_data.game_state {
+            if (old_ws_uid != your_ws_uid || is_reconnect) && !self.game_data.is_solo {
+                self.transport.send(&WsMessage::RequestResync {
+                    my_ws_uid: your_ws_uid,
+                    old_ws_uid,
+                    other_ws_uid: self.game_data.other_ws_uid,
+                });
+            }
+        }
+        //the list of online players for the invitation
+        self.transport.send(&WsMessage::RequestPlayerList {
+            my_ws_uid: self.game_data.my_ws_uid,
+        });
+    }
+    ///msg response player list
+    fn on_response_player_list(&mut self, players: Vec<PlayerInfo>) {
+        let my_ws_uid = self.game_data.my_ws_uid;
+        if let Some(me) = players.iter().find(|player| player.ws_uid == my_ws_uid) {
+            self.game_data.my_rating = me.rating;
+        }
+        let mut online_players: Vec<PlayerInfo> = players
+            .into_iter()
+            .filter(|player| player.ws_uid != my_ws_uid)
+            .collect();
+        //the most fair opponents are on the top of the list
+        let my_rating = self.game_data.my_rating;
+        online_players.sort_by_key(|player| rating_difference(player.rating, my_rating));
+        self.game_data.online_players = online_players;
+        //the other player is gone. The opponent may come back with RequestResync.
+        if let GameState::Play | GameState::Paused = self.game_data.game_state {
+            let other_ws_uid = self.game_data.other_ws_uid;
+            if !self.game_data.is_solo
+                && !self
+                    .game_data
+                    .online_players
+                    .iter()
+                    .any(|player| player.ws_uid == other_ws_uid)
+            {
+                self.transition(GameTransition::OpponentGone);
+            }
+        }
+        //the chosen player is gone
+        if let GameState::Start = self.game_data.game_state {
+            let other_ws_uid = self.game_data.other_ws_uid;
+            if !self
+                .game_data
+                .online_players
+                .iter()
+                .any(|player| player.ws_uid == other_ws_uid)
+            {
+                self.game_data.other_ws_uid = 0;
+            }
+        }
     }
     ///msg want to play
-    fn on_want_to_play(&mut self, my_ws_uid: usize, content_folder_name: String) {
+    fn on_want_to_play(
+        &mut self,
+        my_ws_uid: usize,
+        my_nickname: String,
+        my_avatar: String,
+        content_folder_name: String,
+    ) {
         console::log_1(&"rcv wanttoplay".into());
-        self.reset();
-        self.game_data.game_state = GameState::Asked;
+        let next = match self.next_game_state(GameTransition::ReceiveWantToPlay) {
+            Some(next) => next,
+            None => return,
+        };
+        self.clear_game_data();
+        self.game_data.game_state = next;
         self.game_data.other_ws_uid = my_ws_uid;
+        self.game_data.other_nickname = my_nickname;
+        self.game_data.other_avatar = my_avatar;
         self.game_data.content_folder_name = content_folder_name;
     }
     ///msg accept play
-    fn on_accept_play(&mut self, my_ws_uid: usize, card_grid_data: &str) {
-        self.game_data.player_turn = 1;
-        self.game_data.game_state = GameState::Play;
-        let v: Vec<Card> =
-            serde_json::from_str(card_grid_data).expect("Field 'text' is not Vec<Card>");
-        self.game_data.vec_cards = v;
+    fn on_accept_play(
+        &mut self,
+        my_ws_uid: usize,
+        my_nickname: String,
+        my_avatar: String,
+        card_grid_data: &str,
+    ) {
+        let next = match self.next_game_state(GameTransition::ReceiveAcceptPlay) {
+            Some(next) => next,
+            None => return,
+        };
+        let engine = match serde_json::from_str::<Vec<Card>>(card_grid_data) {
+            Ok(vec_cards) => match GameEngine::from_cards(vec_cards) {
+                Ok(engine) => engine,
+                Err(err) => {
+                    self.on_client_error(ClientError::new("AcceptPlay", &err.to_string()));
+                    return;
+                }
+            },
+            Err(err) => {
+                self.on_client_error(ClientError::new("AcceptPlay", &err.to_string()));
+                return;
+            }
+        };
+        if let Err(err) = check_engine(&engine) {
+            self.on_client_error(ClientError::new("AcceptPlay", &err));
+            return;
+        }
+        self.game_data.game_state = next;
+        self.game_data.game_start_ms = js_sys::Date::now();
+        self.game_data.engine = engine;
         self.game_data.other_ws_uid = my_ws_uid;
+        self.game_data.other_nickname = my_nickname;
+        self.game_data.other_avatar = my_avatar;
+        self.game_data.start_game_record();
+        self.save_game_data();
         self.check_invalidate_for_all_components();
     }
+    ///msg decline play
+    fn on_decline_play(&mut self, my_ws_uid: usize) {
+        if let GameState::Asking = self.game_data.game_state {
+            if my_ws_uid == self.game_data.other_ws_uid {
+                //the invited player said no. Invite somebody else.
+                self.transition(GameTransition::ReceiveDeclinePlay);
+                self.game_data.other_ws_uid = 0;
+                self.game_data.status_message = String::from("invitation declined");
+            } else if self.game_data.other_ws_uid == 0 {
+                //the invitation for anybody is still valid for other players
+                self.game_data.status_message =
+                    format!("invitation declined by player {}", my_ws_uid);
+            }
+        }
+    }
+    ///msg invitation expired
+    fn on_invitation_expired(&mut self, inviter_ws_uid: usize) {
+        let is_my_invitation = inviter_ws_uid == self.game_data.my_ws_uid;
+        let is_invited = inviter_ws_uid == self.game_data.other_ws_uid;
+        match self.game_data.game_state {
+            GameState::Asking if is_my_invitation => {
+                self.transition(GameTransition::InvitationExpired);
+                self.game_data.other_ws_uid = 0;
+                self.game_data.status_message = String::from("invitation expired");
+            }
+            GameState::Asked | GameState::Play if is_invited => {
+                //the accept came too late, the other player is not waiting anymore
+                self.reset(GameTransition::InvitationExpired);
+                self.game_data.status_message = String::from("invitation expired");
+            }
+            _ => {}
+        }
+    }
     ///msg end game
-    fn on_end_game(&mut self) {
-        self.game_data.game_state = GameState::EndGame;
+    fn on_end_game(&mut self, state_hash: u64) {
+        if self.transition(GameTransition::GameOver) {
+            self.check_state_hash("EndGame", state_hash);
+        }
+    }
+    ///msg error: the last message could not be handled. It is shown in the status area.
+    fn on_error(&mut self, code: ErrorCode, message: String, related_msg: &str) {
+        console::log_1(&format!("error {:?} for {}: {}", code, related_msg, message).into());
+        match code {
+            ErrorCode::OpponentGone | ErrorCode::UnknownDeck if related_msg == "WantToPlay" => {
+                //nobody got the invitation. Invite somebody else.
+                if self.transition(GameTransition::InvitationExpired) {
+                    self.game_data.other_ws_uid = 0;
+                }
+            }
+            ErrorCode::OpponentGone => {
+                if let GameState::Play | GameState::Paused = self.game_data.game_state {
+                    self.transition(GameTransition::OpponentGone);
+                }
+            }
+            ErrorCode::BadMessage
+            | ErrorCode::UnknownDeck
+            | ErrorCode::NotYourTurn
+            | ErrorCode::VersionMismatch
+            | ErrorCode::RateLimited => {}
+        }
+        self.game_data.status_message = message;
+    }
+    ///msg notice: information from the server or the other player
+    fn on_notice(&mut self, message: String) {
+        self.game_data.status_message = message;
+    }
+    ///msg server shutting down: the connection closes and the reconnect starts
+    fn on_server_shutting_down(&mut self, restart_eta_secs: Option<u64>) {
+        self.game_data.status_message = match restart_eta_secs {
+            Some(restart_eta_secs) => format!(
+                "The server restarts. It is back in about {} seconds.",
+                restart_eta_secs
+            ),
+            None => "The server is shutting down.".to_string(),
+        };
     }
     ///msg response spelling json
     fn on_response_spelling_json(&mut self, json: &str) {
-        self.game_data.spelling = serde_json::from_str(json).expect(
-            "error root_rendering_component.game_data.spelling = serde_json::from_str(&json)",
-        );
+        match serde_json::from_str(json) {
+            Ok(spelling) => self.game_data.spelling = Some(spelling),
+            Err(err) => {
+                let filename = format!("content/{}/text.json", self.game_data.content_folder_name);
+                self.on_client_error(
+                    ClientError::new("ResponseSpellingJson", &err.to_string())
+                        .with_retry(WsMessage::RequestSpelling { filename }),
+                );
+            }
+        }
+    }
+    ///msg response leaderboard
+    fn on_response_leaderboard(&mut self, leaderboard: Leaderboard) {
+        self.game_data.leaderboard = Some(leaderboard);
+    }
+    ///msg response ratings
+    fn on_response_ratings(&mut self, ratings: Vec<LeaderboardRow>) {
+        self.game_data.ratings = ratings;
     }
     ///msg player change
-    fn on_player_change(&mut self) {
-        self.take_turn();
+    fn on_player_change(&mut self, state_hash: u64) {
+        if self.take_turn(self.game_data.other_player_number()) {
+            self.check_state_hash("PlayerChange", state_hash);
+        }
+        self.save_game_data();
+    }
+    ///compare the hash of the other player with the local state after the same move
+    fn check_state_hash(&mut self, msg_name: &str, state_hash: u64) {
+        let my_state_hash = self.game_data.engine.state_hash();
+        if my_state_hash == state_hash {
+            return;
+        }
+        self.report_desync(format!(
+            "{} state hash {:x}, expected {:x}",
+            msg_name, state_hash, my_state_hash
+        ));
+    }
+    ///msg player pause
+    fn on_player_pause(&mut self) {
+        if self.transition(GameTransition::Pause) {
+            self.save_game_data();
+        }
+    }
+    ///msg player resume
+    fn on_player_resume(&mut self) {
+        if self.transition(GameTransition::Resume) {
+            self.save_game_data();
+        }
     }
     ///msg player click
-    fn on_player_click(&mut self, count_click_inside_one_turn: usize, card_index: usize) {
-        self.game_data.count_click_inside_one_turn = count_click_inside_one_turn;
-        if count_click_inside_one_turn == 1 {
-            self.game_data.card_index_of_first_click = card_index;
-        } else if count_click_inside_one_turn == 2 {
-            self.game_data.card_index_of_second_click = card_index;
-        } else {
-            //nothing
+    ///The move of the other player is validated against the local state.
+    fn on_player_click(&mut self, card_index: usize, count_click_inside_one_turn: usize) {
+        if !self.transition(GameTransition::Click) {
+            return;
+        }
+        let expected_count = self.game_data.engine.count_click_inside_one_turn + 1;
+        if count_click_inside_one_turn != expected_count {
+            self.report_desync(format!(
+                "PlayerClick is the click {} in the turn, expected {}",
+                count_click_inside_one_turn, expected_count
+            ));
+            return;
+        }
+        //the other player is on turn
+        let player_turn = self.game_data.engine.player_turn;
+        match self.game_data.engine.click(player_turn, card_index) {
+            Ok(events) => self.on_game_events(&events, false),
+            Err(err) => self.report_desync(format!("PlayerClick card {}: {}", card_index, err)),
+        }
+    }
+    ///the local state does not match the move of the other player.
+    ///Show the warning with a resync link and tell the other player.
+    fn report_desync(&mut self, reason: String) {
+        console::log_1(&format!("desync: {}", reason).into());
+        self.transport.send(&WsMessage::Desync {
+            my_ws_uid: self.game_data.my_ws_uid,
+            other_ws_uid: self.game_data.other_ws_uid,
+            reason: reason.clone(),
+        });
+        self.game_data.desync_message = reason;
+        //the host, that is player1, has the right state
+        if self.game_data.this_machine_player_number != 1 {
+            self.request_resync();
+        }
+    }
+    ///msg desync: the other player does not agree with my move
+    fn on_desync(&mut self, reason: String) {
+        console::log_1(&format!("desync reported by the other player: {}", reason).into());
+        self.game_data.desync_message = format!("the other player reports: {}", reason);
+        //the host, that is player1, has the right state
+        if self.game_data.this_machine_player_number != 1 {
+            self.request_resync();
+        }
+    }
+    ///ask the other player for the whole game state
+    fn request_resync(&mut self) {
+        //the same ws_uid as before, because there was no refresh
+        self.transport.send(&WsMessage::RequestResync {
+            my_ws_uid: self.game_data.my_ws_uid,
+            old_ws_uid: self.game_data.my_ws_uid,
+            other_ws_uid: self.game_data.other_ws_uid,
+        });
+    }
+    ///msg request resync: the other player refreshed the webpage and has a new ws_uid
+    ///or the game is out of sync and the ws_uid is the same
+    fn on_request_resync(&mut self, my_ws_uid: usize, old_ws_uid: usize) {
+        if old_ws_uid != self.game_data.other_ws_uid {
+            return;
+        }
+        if !self.transition(GameTransition::OpponentReconnected) {
+            return;
+        }
+        self.game_data.other_ws_uid = my_ws_uid;
+        self.game_data.desync_message = String::new();
+        match serde_json::to_string(&self.game_data) {
+            Ok(game_data) => self.transport.send(&WsMessage::ResponseResync {
+                my_ws_uid: self.game_data.my_ws_uid,
+                other_ws_uid: self.game_data.other_ws_uid,
+                game_data,
+            }),
+            Err(err) => self.on_client_error(ClientError::new("RequestResync", &err.to_string())),
+        }
+    }
+    ///msg response resync: the state of the game shared by both players
+    fn on_response_resync(&mut self, game_data: &str) {
+        let next = match self.next_game_state(GameTransition::Resync) {
+            Some(next) => next,
+            None => return,
+        };
+        let other = serde_json::from_str::<GameData>(game_data)
+            .map_err(|err| err.to_string())
+            .and_then(|other| check_engine(&other.engine).map(|()| other));
+        let other = match other {
+            Ok(other) => other,
+            Err(err) => {
+                //ask again. The same ws_uid, because the other player knows it already.
+                self.on_client_error(ClientError::new("ResponseResync", &err).with_retry(
+                    WsMessage::RequestResync {
+                        my_ws_uid: self.game_data.my_ws_uid,
+                        old_ws_uid: self.game_data.my_ws_uid,
+                        other_ws_uid: self.game_data.other_ws_uid,
+                    },
+                ));
+                return;
+            }
+        };
+        //debug log of what was different
+        for diff in self.game_data.engine.diff(&other.engine) {
+            console::log_1(&format!("resync: {}", diff).into());
         }
-        self.card_on_click();
+        self.game_data.engine = other.engine;
+        //the record of the other player has all the moves
+        self.game_data.game_record = other.game_record;
+        self.game_data.desync_message = String::new();
+        self.game_data.game_state = if self.game_data.engine.is_game_over() {
+            GameState::EndGame
+        } else {
+            next
+        };
+        self.save_game_data();
+        self.check_invalidate_for_all_components();
     }
     //endregion
 }
@@ -414,38 +907,40 @@ impl Render for RootRenderingComponent {
             use dodrio::builder::*;
             //this game_data mutable reference is dropped on the end of the function
             let game_data = &root_rendering_component.game_data;
+            //the replay shows its own engine and deck on the same grid
+            let (engine, content_folder_name) = match &root_rendering_component.replay {
+                Some(replay) => (&replay.engine, &replay.record.content_folder_name),
+                None => (&game_data.engine, &game_data.content_folder_name),
+            };
 
             let mut vec_grid_item_bump = Vec::new();
-            for x in 1..=16 {
+            for x in 1..=BOARD_SIZE {
                 let index: usize = x;
                 //region: prepare variables and closures for inserting into vdom
-                let img_src = match game_data.vec_cards.get(index).expect("error index").status {
+                let card = engine
+                    .vec_cards
+                    .get(index)
+                    .unwrap_or(&CARD_NOT_ON_THE_BOARD);
+                let img_src = match card.status {
                     CardStatusCardFace::Down => bumpalo::format!(in bump, "content/{}/{}",
-                                                game_data.content_folder_name,
+                                                content_folder_name,
                                                 SRC_FOR_CARD_FACE_DOWN)
                     .into_bump_str(),
                     CardStatusCardFace::UpTemporary | CardStatusCardFace::UpPermanently => {
                         bumpalo::format!(in bump, "content/{}/img/mem_image_{:02}.png",
-                        game_data.content_folder_name,
-                                game_data
-                                    .vec_cards
-                                    .get(index)
-                                    .expect("error index")
-                                    .card_number_and_img_src
+                        content_folder_name,
+                                card.card_number_and_img_src
                         )
                         .into_bump_str()
                     }
                 };
 
                 let img_id =
-                    bumpalo::format!(in bump, "img{:02}",game_data.vec_cards.get(index).expect("error index").card_index_and_id)
-                        .into_bump_str();
+                    bumpalo::format!(in bump, "img{:02}",card.card_index_and_id).into_bump_str();
 
                 let opacity = if img_src
-                    == format!(
-                        "content/{}/{}",
-                        game_data.content_folder_name, SRC_FOR_CARD_FACE_DOWN
-                    ) {
+                    == format!("content/{}/{}", content_folder_name, SRC_FOR_CARD_FACE_DOWN)
+                {
                     bumpalo::format!(in bump, "opacity:{}", 0.2).into_bump_str()
                 } else {
                     bumpalo::format!(in bump, "opacity:{}", 1).into_bump_str()
@@ -469,14 +964,13 @@ impl Render for RootRenderingComponent {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
                             //this game_data mutable reference is dropped on the end of the function
-                            let mut game_data = &mut root_rendering_component.game_data;
+                            let game_data = &mut root_rendering_component.game_data;
                             //the click on grid is allowed only when is the turn of this player
-                            if (game_data.game_state.as_ref() == GameState::Play.as_ref()
-                                && game_data.player_turn == 1
-                                && game_data.this_machine_player_number == 1)
-                                || (game_data.game_state.as_ref() == GameState::Play.as_ref()
-                                    && game_data.player_turn == 2
-                                    && game_data.this_machine_player_number == 2)
+                            //and never in the replay
+                            if root_rendering_component.replay.is_none()
+                                && game_data.game_state.next(GameTransition::Click).is_some()
+                                && game_data.engine.player_turn
+                                    == game_data.this_machine_player_number
                             {
                                 // If the event's target is our image...
                                 let img = match event
@@ -490,50 +984,36 @@ impl Render for RootRenderingComponent {
 
                                 //id attribute of image html element is prefixed with img ex. "img12"
                                 let this_click_card_index =
-                                    (img.id().get(3..).expect("error slicing"))
-                                        .parse::<usize>()
-                                        .expect("error parse img id to usize");
-
-                                //click is usefull only od facedown cards
-                                if let CardStatusCardFace::Down = game_data
-                                    .vec_cards
-                                    .get(this_click_card_index)
-                                    .expect("error this_click_card_index")
-                                    .status
+                                    match img.id().get(3..).and_then(|id| id.parse::<usize>().ok())
+                                    {
+                                        Some(card_index) => card_index,
+                                        None => return,
+                                    };
+
+                                //the engine accepts only clicks on facedown cards
+                                //and only the first and the second click in one turn
+                                let player_number = game_data.this_machine_player_number;
+                                if let Ok(events) =
+                                    game_data.engine.click(player_number, this_click_card_index)
                                 {
-                                    //the begining of the turn is count_click_inside_one_turn=0
-                                    //on click imediately increase that. So first click is 1 and second click is 2.
-                                    //all other clicks on the grid are not usable.
-                                    game_data.count_click_inside_one_turn += 1;
-
-                                    if game_data.count_click_inside_one_turn == 1 {
-                                        game_data.card_index_of_first_click = this_click_card_index;
-                                        game_data.card_index_of_second_click = 0;
-                                        game_data.count_all_clicks += 1;
-                                    } else if game_data.count_click_inside_one_turn == 2 {
-                                        game_data.card_index_of_second_click =
-                                            this_click_card_index;
-                                        game_data.count_all_clicks += 1;
-                                    } else {
-                                        //nothing
-                                    }
+                                    game_data.count_all_clicks += 1;
 
                                     //region: send WsMessage over websocket
-                                    game_data
-                                        .ws
-                                        .send_with_str(
-                                            &serde_json::to_string(&WsMessage::PlayerClick {
+                                    //nobody else plays the solo game
+                                    if !game_data.is_solo {
+                                        root_rendering_component.transport.send(
+                                            &WsMessage::PlayerClick {
                                                 my_ws_uid: game_data.my_ws_uid,
                                                 other_ws_uid: game_data.other_ws_uid,
                                                 card_index: this_click_card_index,
                                                 count_click_inside_one_turn: game_data
+                                                    .engine
                                                     .count_click_inside_one_turn,
-                                            })
-                                            .expect("error sending PlayerClick"),
-                                        )
-                                        .expect("Failed to send PlayerClick");
+                                            },
+                                        );
+                                    }
                                     //endregion
-                                    root_rendering_component.card_on_click();
+                                    root_rendering_component.on_game_events(&events, true);
                                 }
                                 // Finally, re-render the component on the next animation frame.
                                 vdom.schedule_render();
@@ -556,25 +1036,25 @@ impl Render for RootRenderingComponent {
             let game_data = &root_rendering_component.game_data;
             //if the Spellings are visible, than don't show GameTitle, because there is not
             //enought space on smartphones
-            if game_data.card_index_of_first_click != 0 || game_data.card_index_of_second_click != 0
+            if game_data.engine.card_index_of_first_click != 0
+                || game_data.engine.card_index_of_second_click != 0
             {
                 //if the two opened card match use green else use red color
                 let color; //haha variable does not need to be mutable. Great !
 
-                if game_data
-                    .vec_cards
-                    .get(game_data.card_index_of_first_click)
-                    .expect("error index")
-                    .card_number_and_img_src
-                    == game_data
+                let card_number_of = |card_index: usize| {
+                    game_data
+                        .engine
                         .vec_cards
-                        .get(game_data.card_index_of_second_click)
-                        .expect("error index")
-                        .card_number_and_img_src
+                        .get(card_index)
+                        .map(|card| card.card_number_and_img_src)
+                };
+                if card_number_of(game_data.engine.card_index_of_first_click)
+                    == card_number_of(game_data.engine.card_index_of_second_click)
                 {
                     color = "green";
-                } else if game_data.card_index_of_first_click == 0
-                    || game_data.card_index_of_second_click == 0
+                } else if game_data.engine.card_index_of_first_click == 0
+                    || game_data.engine.card_index_of_second_click == 0
                 {
                     color = "yellow";
                 } else {
@@ -596,9 +1076,7 @@ impl Render for RootRenderingComponent {
                         .attr("style", "text-align: left;")
                         .children([text(
 bumpalo::format!(in bump, "{}",
- root_rendering_component.game_data.spelling.clone().expect("root_rendering_component.game_data.spelling.clone()")
- .name.get(game_data.vec_cards.get(game_data.card_index_of_first_click).expect("error index")
-                                .card_number_and_img_src).expect("error index")
+ game_data.card_spelling(game_data.engine.card_index_of_first_click)
 )
                         .into_bump_str(),
                         )])
@@ -608,10 +1086,7 @@ bumpalo::format!(in bump, "{}",
                         .attr("style", "text-align: right;")
                         .children([text(
                             bumpalo::format!(in bump, "{}",
-                            root_rendering_component.game_data.spelling.clone().expect("root_rendering_component.game_data.spelling.clone()")
-                            .name.get(game_data.vec_cards.get(game_data.card_index_of_second_click)
-                            .expect("error index")
-                                .card_number_and_img_src).expect("error index")
+                            game_data.card_spelling(game_data.engine.card_index_of_second_click)
                                 )
                         .into_bump_str(),
                         )])
@@ -658,40 +1133,39 @@ bumpalo::format!(in bump, "{}",
                         .on("click", move |root, vdom, _event| {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
+                            if !root_rendering_component.transition(GameTransition::Invite) {
+                                return;
+                            }
                             //region: send WsMessage over websocket
                             root_rendering_component
                                 .game_data
                                 .this_machine_player_number = 1;
-                            root_rendering_component.game_data.game_state = GameState::Asking;
+                            root_rendering_component.game_data.status_message = String::new();
                             root_rendering_component.game_data.content_folder_name =
                                 folder_name.clone();
 
                             //send request to Websocket server for spellings
                             root_rendering_component
-                                .game_data
-                                .ws
-                                .send_with_str(
-                                    &serde_json::to_string(&WsMessage::RequestSpelling {
-                                        filename: format!(
-                                            "content/{}/text.json",
-                                            root_rendering_component.game_data.content_folder_name
-                                        ),
-                                    })
-                                    .expect("error sending RequestSpelling"),
-                                )
-                                .expect("Failed to send RequestSpelling");
+                                .transport
+                                .send(&WsMessage::RequestSpelling {
+                                    filename: format!(
+                                        "content/{}/text.json",
+                                        root_rendering_component.game_data.content_folder_name
+                                    ),
+                                });
 
                             root_rendering_component
-                                .game_data
-                                .ws
-                                .send_with_str(
-                                    &serde_json::to_string(&WsMessage::WantToPlay {
-                                        my_ws_uid: root_rendering_component.game_data.my_ws_uid,
-                                        content_folder_name: folder_name.clone(),
-                                    })
-                                    .expect("error sending WantToPlay"),
-                                )
-                                .expect("Failed to send WantToPlay");
+                                .transport
+                                .send(&WsMessage::WantToPlay {
+                                    my_ws_uid: root_rendering_component.game_data.my_ws_uid,
+                                    other_ws_uid: root_rendering_component.game_data.other_ws_uid,
+                                    my_nickname: root_rendering_component
+                                        .game_data
+                                        .my_nickname
+                                        .clone(),
+                                    my_avatar: root_rendering_component.game_data.my_avatar.clone(),
+                                    content_folder_name: folder_name.clone(),
+                                });
 
                             //endregion
                             vdom.schedule_render();
@@ -702,6 +1176,119 @@ bumpalo::format!(in bump, "{}",
             div(bump).children(vec_of_nodes).finish()
         }
 
+        ///links to play alone with every deck, for the best solo time
+        fn div_play_alone<'a, 'bump>(
+            root_rendering_component: &'a RootRenderingComponent,
+            bump: &'bump Bump,
+        ) -> Node<'bump>
+        where
+            'a: 'bump,
+        {
+            let mut vec_of_nodes = Vec::new();
+            //I don't know how to solve the lifetime problems. So I just clone the small data.
+            let content_folders = root_rendering_component.game_data.content_folders.clone();
+            for folder_name in content_folders {
+                vec_of_nodes.push(
+                    h5(bump)
+                        .attr("style", "color:gray;")
+                        .children([text(
+                            bumpalo::format!(in bump, "Play alone for {}", folder_name)
+                                .into_bump_str(),
+                        )])
+                        .on("click", move |root, vdom, _event| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            if root_rendering_component.start_solo_game(&folder_name) {
+                                vdom.schedule_render();
+                            }
+                        })
+                        .finish(),
+                );
+            }
+            div(bump).children(vec_of_nodes).finish()
+        }
+
+        ///render the online players. Click on a player to invite only this player.
+        fn div_online_players<'a, 'bump>(
+            root_rendering_component: &'a RootRenderingComponent,
+            bump: &'bump Bump,
+        ) -> Node<'bump>
+        where
+            'a: 'bump,
+        {
+            let game_data = &root_rendering_component.game_data;
+            let mut vec_of_nodes = Vec::new();
+            vec_of_nodes.push(
+                h4(bump)
+                    .children([text(
+                        bumpalo::format!(in bump, "Online players: {}", game_data.online_players.len())
+                            .into_bump_str(),
+                    )])
+                    .finish(),
+            );
+            //zero is not a player, it means the invitation is for anybody
+            let mut vec_players = vec![PlayerInfo {
+                ws_uid: 0,
+                nickname: String::from("anybody"),
+                avatar: String::new(),
+                rating: 0,
+            }];
+            vec_players.extend(game_data.online_players.iter().cloned());
+            for player in vec_players {
+                let ws_uid = player.ws_uid;
+                let mut vec_player_nodes =
+                    span_avatar_and_nickname(&player.nickname, &player.avatar, bump);
+                if ws_uid != 0 {
+                    let is_fair = rating_difference(player.rating, game_data.my_rating)
+                        <= FAIR_RATING_DIFFERENCE;
+                    vec_player_nodes.push(text(
+                        bumpalo::format!(in bump, " ({}){}",
+                            player.rating,
+                            if is_fair {" fair match"} else {""}
+                        )
+                        .into_bump_str(),
+                    ));
+                }
+                vec_of_nodes.push(
+                    h4(bump)
+                        .attr(
+                            "style",
+                            bumpalo::format!(in bump, "color:{};text-decoration:{}",
+                                if game_data.other_ws_uid == ws_uid {"green"} else {"gray"},
+                                if game_data.other_ws_uid == ws_uid {"underline"} else {"none"}
+                            )
+                            .into_bump_str(),
+                        )
+                        .children(vec_player_nodes)
+                        .on("click", move |root, vdom, _event| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            root_rendering_component.game_data.other_ws_uid = ws_uid;
+                            vdom.schedule_render();
+                        })
+                        .finish(),
+                );
+            }
+            div(bump).children(vec_of_nodes).finish()
+        }
+
+        ///short message about the invitation, errors and notices
+        fn div_status_message<'a, 'bump>(
+            root_rendering_component: &'a RootRenderingComponent,
+            bump: &'bump Bump,
+        ) -> Node<'bump>
+        where
+            'a: 'bump,
+        {
+            h4(bump)
+                .attr("style", "color:red;")
+                .children([text(
+                    bumpalo::format!(in bump, "{}", root_rendering_component.game_data.status_message)
+                        .into_bump_str(),
+                )])
+                .finish()
+        }
+
         ///html element to inform player what to do and get a click action from user
         fn div_game_status_and_player_actions<'a, 'bump>(
             root_rendering_component: &'a RootRenderingComponent,
@@ -710,25 +1297,74 @@ bumpalo::format!(in bump, "{}",
         where
             'a: 'bump,
         {
-            if let GameState::Start = root_rendering_component.game_data.game_state {
+            if root_rendering_component.replay.is_some() {
+                //the controls of the replay instead of the game
+                div_replay(root_rendering_component, bump)
+            } else if root_rendering_component.game_data.is_nickname_form_open {
+                //first launch or change nickname
+                div_nickname_and_avatar(root_rendering_component, bump)
+            } else if root_rendering_component.game_data.leaderboard.is_some() {
+                //the leaderboard is shown instead of the invitation
+                div_leaderboard(root_rendering_component, bump)
+            } else if let GameState::Start = root_rendering_component.game_data.game_state {
                 // 1S Ask Player2 to play!
                 console::log_1(&"GameState::Start".into());
                 //return Ask Player2 to play!
-                ask_to_play(root_rendering_component, bump, "Invite")
+                div(bump)
+                    .children([
+                        div_online_players(root_rendering_component, bump),
+                        ask_to_play(root_rendering_component, bump, "Invite"),
+                        div_play_alone(root_rendering_component, bump),
+                        div_leaderboard_links(root_rendering_component, bump),
+                        div_replay_links(root_rendering_component, bump),
+                        h5(bump)
+                            .attr("style", "color:gray;")
+                            .children([text(
+                                bumpalo::format!(in bump, "Change nickname{}", "").into_bump_str(),
+                            )])
+                            .on("click", move |root, vdom, _event| {
+                                let root_rendering_component =
+                                    root.unwrap_mut::<RootRenderingComponent>();
+                                root_rendering_component.game_data.is_nickname_form_open = true;
+                                vdom.schedule_render();
+                            })
+                            .finish(),
+                    ])
+                    .finish()
             } else if let GameState::EndGame = root_rendering_component.game_data.game_state {
                 //end game ,Play again?
-                h3(bump)
-                    .attr("id", "ws_elem")
-                    .attr("style", "color:green;")
-                    .children([text(
-                        //Play again?
-                        bumpalo::format!(in bump, "Play again{}?", "").into_bump_str(),
-                    )])
-                    .on("click", move |root, vdom, _event| {
-                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
-                        root_rendering_component.reset();
-                        vdom.schedule_render();
-                    })
+                div(bump)
+                    .children([
+                        h3(bump)
+                            .attr("id", "ws_elem")
+                            .attr("style", "color:green;")
+                            .children([text(
+                                //Play again?
+                                bumpalo::format!(in bump, "Play again{}?", "").into_bump_str(),
+                            )])
+                            .on("click", move |root, vdom, _event| {
+                                let root_rendering_component =
+                                    root.unwrap_mut::<RootRenderingComponent>();
+                                if root_rendering_component.reset(GameTransition::Reset) {
+                                    vdom.schedule_render();
+                                }
+                            })
+                            .finish(),
+                        h5(bump)
+                            .attr("style", "color:gray;")
+                            .children([text(
+                                bumpalo::format!(in bump, "Replay this game{}", "").into_bump_str(),
+                            )])
+                            .on("click", move |root, vdom, _event| {
+                                let root_rendering_component =
+                                    root.unwrap_mut::<RootRenderingComponent>();
+                                let game_record =
+                                    root_rendering_component.game_data.game_record.clone();
+                                root_rendering_component.start_replay(game_record);
+                                vdom.schedule_render();
+                            })
+                            .finish(),
+                    ])
                     .finish()
             } else if let GameState::Asking = root_rendering_component.game_data.game_state {
                 //return wait for the other player
@@ -741,70 +1377,182 @@ bumpalo::format!(in bump, "{}",
             } else if let GameState::Asked = root_rendering_component.game_data.game_state {
                 // 2S Click here to Accept play!
                 console::log_1(&"GameState::Asked".into());
-                //return Click here to Accept play
+                //return Click here to Accept play or Decline
+                div(bump)
+                    .children([
+                        h3(bump)
+                            .children(span_avatar_and_nickname(
+                                &root_rendering_component.game_data.other_nickname,
+                                &root_rendering_component.game_data.other_avatar,
+                                bump,
+                            ))
+                            .finish(),
+                        h3(bump)
+                            .attr("id", "ws_elem")
+                            .attr("style", "color:green;")
+                            .children([text(
+                                //show Ask Player2 to Play!
+                                bumpalo::format!(in bump, "Click here to Accept {}!", root_rendering_component.game_data.content_folder_name)
+                                    .into_bump_str(),
+                            )])
+                            .on("click", move |root, vdom, _event| {
+                                let root_rendering_component =
+                                    root.unwrap_mut::<RootRenderingComponent>();
+                                if !root_rendering_component.transition(GameTransition::Accept) {
+                                    return;
+                                }
+                                //region: send WsMessage over websocket
+                                root_rendering_component.game_data.prepare_random_data();
+                                root_rendering_component
+                                    .game_data
+                                    .this_machine_player_number = 2;
+                                root_rendering_component.game_data.game_start_ms = js_sys::Date::now();
+                                root_rendering_component.game_data.start_game_record();
+                                root_rendering_component.save_game_data();
+
+                                //send request to Websocket server for spellings
+                                root_rendering_component
+                                    .transport
+                                    .send(&WsMessage::RequestSpelling {
+                                        filename: format!(
+                                            "content/{}/text.json",
+                                            root_rendering_component.game_data.content_folder_name
+                                        ),
+                                    });
+
+                                root_rendering_component
+                                    .transport
+                                    .send(&WsMessage::AcceptPlay {
+                                        my_ws_uid: root_rendering_component.game_data.my_ws_uid,
+                                        other_ws_uid: root_rendering_component.game_data.other_ws_uid,
+                                        my_nickname: root_rendering_component.game_data.my_nickname.clone(),
+                                        my_avatar: root_rendering_component.game_data.my_avatar.clone(),
+                                        //send the vector of cards because both players need cards in the same location.
+                                        card_grid_data: serde_json::to_string(
+                                            &root_rendering_component.game_data.engine.vec_cards,
+                                        )
+                                        .expect("error serde_json"),
+                                    });
+                                //endregion
+                                vdom.schedule_render();
+                            })
+                            .finish(),
+                        h3(bump)
+                            .attr("style", "color:red;")
+                            .children([text(
+                                bumpalo::format!(in bump, "Click here to Decline {}!", root_rendering_component.game_data.other_nickname)
+                                    .into_bump_str(),
+                            )])
+                            .on("click", move |root, vdom, _event| {
+                                let root_rendering_component =
+                                    root.unwrap_mut::<RootRenderingComponent>();
+                                //region: send WsMessage over websocket
+                                root_rendering_component
+                                    .transport
+                                    .send(&WsMessage::DeclinePlay {
+                                        my_ws_uid: root_rendering_component.game_data.my_ws_uid,
+                                        other_ws_uid: root_rendering_component.game_data.other_ws_uid,
+                                    });
+                                //endregion
+                                if root_rendering_component.reset(GameTransition::Decline) {
+                                    vdom.schedule_render();
+                                }
+                            })
+                            .finish(),
+                    ])
+                    .finish()
+            } else if let GameState::Paused = root_rendering_component.game_data.game_state {
                 h3(bump)
                     .attr("id", "ws_elem")
                     .attr("style", "color:green;")
                     .children([text(
-                        //show Ask Player2 to Play!
-                        bumpalo::format!(in bump, "Click here to Accept {}!", root_rendering_component.game_data.content_folder_name)
+                        bumpalo::format!(in bump, "The game is paused. Click here to continue !{}", "")
                             .into_bump_str(),
                     )])
                     .on("click", move |root, vdom, _event| {
-                        let mut root_rendering_component =
-                            root.unwrap_mut::<RootRenderingComponent>();
-                        //region: send WsMessage over websocket
-                        root_rendering_component.game_data.prepare_random_data();
-                        root_rendering_component
-                            .game_data
-                            .this_machine_player_number = 2;
-                        root_rendering_component.game_data.player_turn=1;
-                        root_rendering_component.game_data.game_state = GameState::Play;
-
-                        //send request to Websocket server for spellings
-                        root_rendering_component
-                            .game_data
-                            .ws
-                            .send_with_str(
-                                &serde_json::to_string(&WsMessage::RequestSpelling {
-                                    filename: format!(
-                                        "content/{}/text.json",
-                                        root_rendering_component.game_data.content_folder_name
-                                    ),
-                                })
-                                .expect("error sending RequestSpelling"),
-                            )
-                            .expect("Failed to send RequestSpelling");
-
-                        root_rendering_component
-                            .game_data
-                            .ws
-                            .send_with_str(
-                                &serde_json::to_string(&WsMessage::AcceptPlay {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        if root_rendering_component.transition(GameTransition::Resume) {
+                            root_rendering_component
+                                .transport
+                                .send(&WsMessage::PlayerResume {
                                     my_ws_uid: root_rendering_component.game_data.my_ws_uid,
                                     other_ws_uid: root_rendering_component.game_data.other_ws_uid,
-                                    //send the vector of cards because both players need cards in the same location.
-                                    card_grid_data: serde_json::to_string(
-                                        &root_rendering_component.game_data.vec_cards,
-                                    )
-                                    .expect("error serde_json"),
-                                })
-                                .expect("error sending test"),
-                            )
-                            .expect("Failed to send");
-                        //endregion
-                        vdom.schedule_render();
+                                });
+                            vdom.schedule_render();
+                        }
+                    })
+                    .finish()
+            } else if let GameState::WaitingForReconnect =
+                root_rendering_component.game_data.game_state
+            {
+                div(bump)
+                    .children([
+                        h3(bump)
+                            .attr("id", "ws_elem")
+                            .attr("style", "color:red;")
+                            .children([text(
+                                bumpalo::format!(in bump, "The other player is disconnected. Wait until the other player comes back.{}", "")
+                                    .into_bump_str(),
+                            )])
+                            .finish(),
+                        h5(bump)
+                            .attr("style", "color:gray;")
+                            .children([text(
+                                bumpalo::format!(in bump, "Leave the game{}", "").into_bump_str(),
+                            )])
+                            .on("click", move |root, vdom, _event| {
+                                let root_rendering_component =
+                                    root.unwrap_mut::<RootRenderingComponent>();
+                                if root_rendering_component.transition(GameTransition::Abandon) {
+                                    root_rendering_component.save_game_data();
+                                    vdom.schedule_render();
+                                }
+                            })
+                            .finish(),
+                    ])
+                    .finish()
+            } else if let GameState::Abandoned = root_rendering_component.game_data.game_state {
+                h3(bump)
+                    .attr("id", "ws_elem")
+                    .attr("style", "color:green;")
+                    .children([text(
+                        bumpalo::format!(in bump, "The other player left the game. Play again{}?", "")
+                            .into_bump_str(),
+                    )])
+                    .on("click", move |root, vdom, _event| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        if root_rendering_component.reset(GameTransition::Reset) {
+                            vdom.schedule_render();
+                        }
                     })
                     .finish()
             } else if root_rendering_component
                 .game_data
+                .engine
                 .count_click_inside_one_turn
                 >= 2
             {
-                if root_rendering_component
+                if root_rendering_component.game_data.is_solo {
+                    //return Click here to continue
+                    h3(bump)
+                        .attr("id", "ws_elem")
+                        .attr("style", "color:green;")
+                        .children([text(
+                            bumpalo::format!(in bump, "Click here to continue !{}", "")
+                                .into_bump_str(),
+                        )])
+                        .on("click", move |root, vdom, _event| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            if root_rendering_component.continue_alone() {
+                                vdom.schedule_render();
+                            }
+                        })
+                        .finish()
+                } else if root_rendering_component
                     .game_data
                     .this_machine_player_number
-                    == root_rendering_component.game_data.player_turn
+                    == root_rendering_component.game_data.engine.player_turn
                 {
                     //return wait for the other player
                     div_wait_for_other_player(bump)
@@ -820,22 +1568,22 @@ bumpalo::format!(in bump, "{}",
                         .on("click", move |root, vdom, _event| {
                             let root_rendering_component =
                                 root.unwrap_mut::<RootRenderingComponent>();
-                            //this game_data mutable reference is dropped on the end of the function
-                            //clippy is wrong about dropping the mut. I need it.
-                            let game_data = &root_rendering_component.game_data;
-                            //region: send WsMessage over websocket
-                            game_data
-                                .ws
-                                .send_with_str(
-                                    &serde_json::to_string(&WsMessage::PlayerChange {
+                            let player_number = root_rendering_component
+                                .game_data
+                                .this_machine_player_number;
+                            if root_rendering_component.take_turn(player_number) {
+                                //this game_data reference is dropped on the end of the block
+                                let game_data = &root_rendering_component.game_data;
+                                //region: send WsMessage over websocket
+                                root_rendering_component
+                                    .transport
+                                    .send(&WsMessage::PlayerChange {
                                         my_ws_uid: game_data.my_ws_uid,
                                         other_ws_uid: game_data.other_ws_uid,
-                                    })
-                                    .expect("error sending PlayerChange"),
-                                )
-                                .expect("Failed to send PlayerChange");
-                            //endregion
-                            root_rendering_component.take_turn();
+                                        state_hash: game_data.engine.state_hash(),
+                                    });
+                                //endregion
+                            }
                             // Finally, re-render the component on the next animation frame.
                             vdom.schedule_render();
                         })
@@ -843,13 +1591,14 @@ bumpalo::format!(in bump, "{}",
                 }
             } else if root_rendering_component
                 .game_data
+                .engine
                 .count_click_inside_one_turn
                 < 2
             {
                 if root_rendering_component
                     .game_data
                     .this_machine_player_number
-                    == root_rendering_component.game_data.player_turn
+                    == root_rendering_component.game_data.engine.player_turn
                 {
                     h3(bump)
                         .attr("id", "ws_elem")
@@ -874,6 +1623,136 @@ bumpalo::format!(in bump, "{}",
                     .finish()
             }
         }
+        ///during the play any player can pause the game for both players
+        fn div_pause<'a, 'bump>(
+            root_rendering_component: &'a RootRenderingComponent,
+            bump: &'bump Bump,
+        ) -> Node<'bump>
+        where
+            'a: 'bump,
+        {
+            //the solo game has nobody to wait for
+            if root_rendering_component.game_data.is_solo {
+                div(bump).finish()
+            } else if let GameState::Play = root_rendering_component.game_data.game_state {
+                h5(bump)
+                    .attr("style", "color:gray;")
+                    .children([text(
+                        bumpalo::format!(in bump, "Pause the game{}", "").into_bump_str(),
+                    )])
+                    .on("click", move |root, vdom, _event| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        if root_rendering_component.transition(GameTransition::Pause) {
+                            root_rendering_component
+                                .transport
+                                .send(&WsMessage::PlayerPause {
+                                    my_ws_uid: root_rendering_component.game_data.my_ws_uid,
+                                    other_ws_uid: root_rendering_component.game_data.other_ws_uid,
+                                });
+                            vdom.schedule_render();
+                        }
+                    })
+                    .finish()
+            } else {
+                div(bump).finish()
+            }
+        }
+        ///warning when the two players don't agree about the game
+        fn div_desync<'a, 'bump>(
+            root_rendering_component: &'a RootRenderingComponent,
+            bump: &'bump Bump,
+        ) -> Node<'bump>
+        where
+            'a: 'bump,
+        {
+            if root_rendering_component.game_data.desync_message.is_empty() {
+                return div(bump).finish();
+            }
+            div(bump)
+                .children([
+                    h4(bump)
+                        .attr("style", "color:red;")
+                        .children([text(
+                            bumpalo::format!(in bump, "The game is out of sync: {}",
+                            root_rendering_component.game_data.desync_message)
+                            .into_bump_str(),
+                        )])
+                        .finish(),
+                    h4(bump)
+                        .attr("style", "color:green;")
+                        .children([text(
+                            bumpalo::format!(in bump, "Click here to resync from the other player{}", "")
+                                .into_bump_str(),
+                        )])
+                        .on("click", move |root, vdom, _event| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            root_rendering_component.request_resync();
+                            vdom.schedule_render();
+                        })
+                        .finish(),
+                ])
+                .finish()
+        }
+        ///the badge with the connection state to the WebSocket server.
+        ///When the server is gone, the banner says it instead of "Wait for the other player".
+        fn div_connection_indicator<'a, 'bump>(
+            root_rendering_component: &'a RootRenderingComponent,
+            bump: &'bump Bump,
+        ) -> Node<'bump>
+        where
+            'a: 'bump,
+        {
+            if !root_rendering_component.transport.has_heartbeat() {
+                return div(bump).finish();
+            }
+            let (color, description) = match root_rendering_component.connection_state {
+                ConnectionState::Connecting => ("orange", "connecting..."),
+                ConnectionState::Open if root_rendering_component.is_connection_alive => {
+                    ("green", "online")
+                }
+                ConnectionState::Open => ("red", "connection lost"),
+                ConnectionState::Reconnecting => ("orange", "reconnecting..."),
+                ConnectionState::Closed => ("red", "offline"),
+            };
+            let badge = h5(bump)
+                .attr(
+                    "style",
+                    bumpalo::format!(in bump, "color:{};", color).into_bump_str(),
+                )
+                .children([text(
+                    bumpalo::format!(in bump, "\u{25cf} {}", description).into_bump_str(),
+                )])
+                .finish();
+            if root_rendering_component.connection_state != ConnectionState::Closed {
+                return badge;
+            }
+            div(bump)
+                .children([
+                    h3(bump)
+                        .attr("style", "color:red;")
+                        .children([text(
+                            bumpalo::format!(in bump, "The game server is not reachable.{}", "")
+                                .into_bump_str(),
+                        )])
+                        .finish(),
+                    h3(bump)
+                        .attr("style", "color:green;")
+                        .children([text(
+                            bumpalo::format!(in bump, "Click here to connect again{}", "")
+                                .into_bump_str(),
+                        )])
+                        .on("click", move |root, vdom, _event| {
+                            let root_rendering_component =
+                                root.unwrap_mut::<RootRenderingComponent>();
+                            root_rendering_component.transport.reconnect();
+                            vdom.schedule_render();
+                        })
+                        .finish(),
+                    badge,
+                ])
+                .finish()
+        }
         ///the text 'wait for other player' is used multiple times
         fn div_wait_for_other_player(bump: &Bump) -> Node {
             h3(bump)
@@ -891,6 +1770,7 @@ bumpalo::format!(in bump, "{}",
         div(bump)
             .attr("class", "m_container")
             .children([
+                div_connection_indicator(self, bump),
                 div_grid_header(self, bump),
                 //div for the css grid object defined in css with <img> inside
                 div(bump)
@@ -900,6 +1780,10 @@ bumpalo::format!(in bump, "{}",
                     .finish(),
                 self.players_and_scores.render(bump),
                 div_game_status_and_player_actions(self, bump),
+                div_status_message(self, bump),
+                div_client_error(self, bump),
+                div_desync(self, bump),
+                div_pause(self, bump),
                 h5(bump)
                     .children([text(
                         bumpalo::format!(in bump, "Count of Clicks: {}", self.game_data.count_all_clicks)
@@ -913,3 +1797,109 @@ bumpalo::format!(in bump, "{}",
     }
 }
 //endregion
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::RecordingTransport;
+
+    ///the component with the mock transport, without the browser
+    fn component_with_recording_transport() -> (RootRenderingComponent, Rc<RecordingTransport>) {
+        let transport = Rc::new(RecordingTransport::default());
+        let root_rendering_component = RootRenderingComponent::new(
+            Rc::clone(&transport) as Rc<dyn Transport>,
+            11,
+            "Ana".to_string(),
+            "1".to_string(),
+        );
+        (root_rendering_component, transport)
+    }
+
+    #[test]
+    fn response_ws_uid_requests_the_player_list() {
+        let (mut root_rendering_component, transport) = component_with_recording_transport();
+        root_rendering_component.on_response_ws_uid(12, false);
+        assert_eq!(root_rendering_component.game_data.my_ws_uid, 12);
+        assert_eq!(
+            transport.sent_messages(),
+            vec![r#"{"RequestPlayerList":{"my_ws_uid":12}}"#]
+        );
+    }
+
+    #[test]
+    fn reconnect_during_the_game_requests_a_resync() {
+        let (mut root_rendering_component, transport) = component_with_recording_transport();
+        root_rendering_component.game_data.game_state = GameState::Play;
+        root_rendering_component.game_data.other_ws_uid = 22;
+        root_rendering_component.on_response_ws_uid(11, true);
+        assert_eq!(
+            transport.sent_messages(),
+            vec![
+                r#"{"RequestResync":{"my_ws_uid":11,"old_ws_uid":11,"other_ws_uid":22}}"#,
+                r#"{"RequestPlayerList":{"my_ws_uid":11}}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn check_engine_accepts_only_a_full_board_with_pairs() {
+        let card_numbers: Vec<usize> = (1..=8).chain(1..=8).collect();
+        let mut engine = GameEngine::from_card_numbers(&card_numbers).expect("a valid deck");
+        assert_eq!(check_engine(&engine), Ok(()));
+        engine.click(1, 1).expect("a legal click");
+        assert_eq!(check_engine(&engine), Ok(()));
+
+        let small = GameEngine::from_card_numbers(&[1, 1, 2, 2]).expect("a valid deck");
+        assert_eq!(
+            check_engine(&small),
+            Err("the board has 4 cards".to_string())
+        );
+        engine.vec_cards[16].card_number_and_img_src = 1;
+        assert!(check_engine(&engine).is_err());
+    }
+
+    ///reset() without the session storage of the browser
+    fn saved_json_after_reset(
+        root_rendering_component: &mut RootRenderingComponent,
+        transition: GameTransition,
+    ) -> String {
+        assert!(root_rendering_component.transition(transition));
+        root_rendering_component.clear_game_data();
+        root_rendering_component
+            .game_data
+            .to_session_json()
+            .expect("the game data as json")
+    }
+
+    #[test]
+    fn game_after_a_reset_is_restored_without_an_error() {
+        let (mut root_rendering_component, _transport) = component_with_recording_transport();
+        root_rendering_component.game_data.game_state = GameState::EndGame;
+        let json = saved_json_after_reset(&mut root_rendering_component, GameTransition::Reset);
+        let game_data = GameData::from_session_json(&json).expect("the saved game data");
+        assert_eq!(
+            restorable_game_data(game_data).map(|game_data| game_data.is_some()),
+            Ok(false)
+        );
+
+        root_rendering_component.game_data.game_state = GameState::Asked;
+        let json = saved_json_after_reset(&mut root_rendering_component, GameTransition::Decline);
+        let game_data = GameData::from_session_json(&json).expect("the saved game data");
+        assert_eq!(
+            restorable_game_data(game_data).map(|game_data| game_data.is_some()),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn running_game_with_a_broken_board_is_not_restored() {
+        let (mut root_rendering_component, _transport) = component_with_recording_transport();
+        root_rendering_component.game_data.game_state = GameState::Play;
+        let json = root_rendering_component
+            .game_data
+            .to_session_json()
+            .expect("the game data as json");
+        let game_data = GameData::from_session_json(&json).expect("the saved game data");
+        assert!(restorable_game_data(game_data).is_err());
+    }
+}
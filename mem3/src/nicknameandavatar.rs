@@ -0,0 +1,181 @@
+//! nickname and avatar
+//! The player chooses them on the first launch. They are stored in the local storage.
+
+use crate::gamedata::GameData;
+use crate::transport::Transport;
+use crate::RootRenderingComponent;
+use dodrio::builder::*;
+use dodrio::bumpalo::{self, Bump};
+use dodrio::Node;
+use mem3_common::{WsMessage, PROTOCOL_VERSION};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+///key in local storage for nickname
+const STORAGE_KEY_NICKNAME: &str = "mem3_nickname";
+///key in local storage for avatar
+const STORAGE_KEY_AVATAR: &str = "mem3_avatar";
+///the deck images from 1 to 26 can be avatars
+const COUNT_OF_AVATAR_IMAGES: usize = 26;
+
+/// Get the top-level window's local storage.
+pub fn local_storage() -> web_sys::Storage {
+    let window = web_sys::window().expect("error: web_sys::window");
+    window.local_storage().unwrap_throw().unwrap_throw()
+}
+
+///read nickname and avatar from local storage. Empty strings on the first launch.
+pub fn load_nickname_and_avatar() -> (String, String) {
+    let ls = local_storage();
+    let nickname = ls
+        .get_item(STORAGE_KEY_NICKNAME)
+        .unwrap_throw()
+        .unwrap_or_default();
+    let avatar = ls
+        .get_item(STORAGE_KEY_AVATAR)
+        .unwrap_throw()
+        .unwrap_or_default();
+    (nickname, avatar)
+}
+
+///write nickname and avatar to local storage for the next launch
+pub fn save_nickname_and_avatar(nickname: &str, avatar: &str) {
+    let ls = local_storage();
+    ls.set_item(STORAGE_KEY_NICKNAME, nickname).unwrap_throw();
+    ls.set_item(STORAGE_KEY_AVATAR, avatar).unwrap_throw();
+}
+
+///src of the avatar image from the current deck
+pub fn avatar_src(content_folder_name: &str, image_number: usize) -> String {
+    format!(
+        "content/{}/img/mem_image_{:02}.png",
+        content_folder_name, image_number
+    )
+}
+
+///small avatar image with nickname for the score bar, the lobby and the invitations
+pub fn span_avatar_and_nickname<'bump>(
+    nickname: &str,
+    avatar: &str,
+    bump: &'bump Bump,
+) -> Vec<Node<'bump>> {
+    let mut vec_of_nodes = Vec::new();
+    if !avatar.is_empty() {
+        vec_of_nodes.push(
+            img(bump)
+                .attr("class", "avatar")
+                .attr(
+                    "src",
+                    bumpalo::format!(in bump, "{}", avatar).into_bump_str(),
+                )
+                .finish(),
+        );
+    }
+    vec_of_nodes.push(text(
+        bumpalo::format!(in bump, " {}", nickname).into_bump_str(),
+    ));
+    vec_of_nodes
+}
+
+///the form to choose the nickname and the avatar
+pub fn div_nickname_and_avatar<'a, 'bump>(
+    root_rendering_component: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Node<'bump>
+where
+    'a: 'bump,
+{
+    let game_data = &root_rendering_component.game_data;
+
+    let mut vec_avatar_nodes = Vec::new();
+    for image_number in 1..=COUNT_OF_AVATAR_IMAGES {
+        let src = avatar_src(&game_data.content_folder_name, image_number);
+        let border = if src == game_data.my_avatar {
+            "border: 3px solid green;"
+        } else {
+            "border: 3px solid transparent;"
+        };
+        vec_avatar_nodes.push(
+            img(bump)
+                .attr("class", "avatar")
+                .attr("style", border)
+                .attr("src", bumpalo::format!(in bump, "{}", src).into_bump_str())
+                .on("click", move |root, vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    root_rendering_component.game_data.my_avatar = src.clone();
+                    vdom.schedule_render();
+                })
+                .finish(),
+        );
+    }
+
+    div(bump)
+        .children([
+            h3(bump)
+                .children([text(
+                    bumpalo::format!(in bump, "Choose your nickname and avatar{}", "")
+                        .into_bump_str(),
+                )])
+                .finish(),
+            input(bump)
+                .attr("id", "input_nickname")
+                .attr("maxlength", "20")
+                .attr(
+                    "value",
+                    bumpalo::format!(in bump, "{}", game_data.my_nickname).into_bump_str(),
+                )
+                .on("input", move |root, _vdom, event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    if let Some(input) = event
+                        .target()
+                        .and_then(|t| t.dyn_into::<web_sys::HtmlInputElement>().ok())
+                    {
+                        root_rendering_component.game_data.my_nickname =
+                            input.value().trim().to_string();
+                    }
+                })
+                .finish(),
+            div(bump)
+                .attr("class", "grid_container_avatars")
+                .children(vec_avatar_nodes)
+                .finish(),
+            h3(bump)
+                .attr("style", "color:green;")
+                .children([text(
+                    bumpalo::format!(in bump, "Click here to Save{}", "").into_bump_str(),
+                )])
+                .on("click", move |root, vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    if on_save_nickname_and_avatar(
+                        &mut root_rendering_component.game_data,
+                        root_rendering_component.transport.as_ref(),
+                    ) {
+                        vdom.schedule_render();
+                    }
+                })
+                .finish(),
+        ])
+        .finish()
+}
+
+///save to local storage and send the new nickname to the WebSocket server.
+///Returns false if the nickname is still empty.
+fn on_save_nickname_and_avatar(game_data: &mut GameData, transport: &dyn Transport) -> bool {
+    if game_data.my_nickname.is_empty() {
+        return false;
+    }
+    if game_data.my_avatar.is_empty() {
+        game_data.my_avatar = avatar_src(&game_data.content_folder_name, 1);
+    }
+    save_nickname_and_avatar(&game_data.my_nickname, &game_data.my_avatar);
+    game_data.is_nickname_form_open = false;
+    //the handshake again with the new nickname
+    transport.send(&WsMessage::RequestWsUid {
+        nickname: game_data.my_nickname.clone(),
+        avatar: game_data.my_avatar.clone(),
+        version: PROTOCOL_VERSION,
+        //the transport adds the token
+        session_token: String::new(),
+    });
+    true
+}
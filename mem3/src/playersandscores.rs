@@ -1,5 +1,6 @@
 //! file and module for playersandscores
 use crate::gamedata::GameData;
+use crate::nicknameandavatar::span_avatar_and_nickname;
 
 use dodrio::builder::*;
 use dodrio::bumpalo::{self, Bump};
@@ -18,16 +19,28 @@ pub struct PlayersAndScores {
     player2_points: usize,
     ///What player am I
     this_machine_player_number: usize,
+    ///player1 nickname
+    player1_nickname: String,
+    ///player1 avatar
+    player1_avatar: String,
+    ///player2 nickname
+    player2_nickname: String,
+    ///player2 avatar
+    player2_avatar: String,
 }
 
 impl PlayersAndScores {
     ///constructor
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
         PlayersAndScores {
             player1_points: 0,
             player2_points: 0,
             this_machine_player_number: 0, //unknown until WantToPlay+Accept
             player_turn: 0,
+            player1_nickname: String::new(),
+            player1_avatar: String::new(),
+            player2_nickname: String::new(),
+            player2_avatar: String::new(),
         }
     }
     ///copies the data from game data to internal cache
@@ -35,12 +48,12 @@ impl PlayersAndScores {
     pub fn update_intern_cache(&mut self, game_data: &GameData) -> bool {
         let mut is_invalidated;
         is_invalidated = false;
-        if self.player1_points != game_data.player1_points {
-            self.player1_points = game_data.player1_points;
+        if self.player1_points != game_data.engine.player1_points {
+            self.player1_points = game_data.engine.player1_points;
             is_invalidated = true;
         }
-        if self.player2_points != game_data.player2_points {
-            self.player2_points = game_data.player2_points;
+        if self.player2_points != game_data.engine.player2_points {
+            self.player2_points = game_data.engine.player2_points;
             is_invalidated = true;
         }
 
@@ -48,8 +61,20 @@ impl PlayersAndScores {
             self.this_machine_player_number = game_data.this_machine_player_number;
             is_invalidated = true;
         }
-        if self.player_turn != game_data.player_turn {
-            self.player_turn = game_data.player_turn;
+        if self.player_turn != game_data.engine.player_turn {
+            self.player_turn = game_data.engine.player_turn;
+            is_invalidated = true;
+        }
+        let (player1_nickname, player1_avatar) = game_data.player_nickname_and_avatar(1);
+        if self.player1_nickname != player1_nickname || self.player1_avatar != player1_avatar {
+            self.player1_nickname = player1_nickname.to_string();
+            self.player1_avatar = player1_avatar.to_string();
+            is_invalidated = true;
+        }
+        let (player2_nickname, player2_avatar) = game_data.player_nickname_and_avatar(2);
+        if self.player2_nickname != player2_nickname || self.player2_avatar != player2_avatar {
+            self.player2_nickname = player2_nickname.to_string();
+            self.player2_avatar = player2_avatar.to_string();
             is_invalidated = true;
         }
         is_invalidated
@@ -83,10 +108,17 @@ impl Render for PlayersAndScores {
                         )
                         .into_bump_str(),
                     )
-                    .children([text(
-                        bumpalo::format!(in bump, "player1: {}",self.player1_points)
-                            .into_bump_str(),
-                    )])
+                    .children({
+                        let mut vec_of_nodes = span_avatar_and_nickname(
+                            &self.player1_nickname,
+                            &self.player1_avatar,
+                            bump,
+                        );
+                        vec_of_nodes.push(text(
+                            bumpalo::format!(in bump, ": {}",self.player1_points).into_bump_str(),
+                        ));
+                        vec_of_nodes
+                    })
                     .finish(),
                 div(bump)
                     .attr("class", "grid_item")
@@ -103,10 +135,17 @@ impl Render for PlayersAndScores {
                         )
                         .into_bump_str(),
                     )
-                    .children([text(
-                        bumpalo::format!(in bump, "player2: {}",self.player2_points)
-                            .into_bump_str(),
-                    )])
+                    .children({
+                        let mut vec_of_nodes = span_avatar_and_nickname(
+                            &self.player2_nickname,
+                            &self.player2_avatar,
+                            bump,
+                        );
+                        vec_of_nodes.push(text(
+                            bumpalo::format!(in bump, ": {}",self.player2_points).into_bump_str(),
+                        ));
+                        vec_of_nodes
+                    })
                     .finish(),
             ])
             .finish()
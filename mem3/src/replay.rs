@@ -0,0 +1,335 @@
+//! replay
+//! The finished games are kept in the local storage. A teacher can watch how the kids played.
+//! The replay makes the recorded moves again on a new engine and the grid shows this engine.
+
+use crate::nicknameandavatar::local_storage;
+use crate::RootRenderingComponent;
+use dodrio::builder::*;
+use dodrio::bumpalo::{self, Bump};
+use dodrio::Node;
+use futures::Future;
+use mem3_common::{GameEngine, GameRecord, RecordError};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::console;
+
+///prefix of the keys in local storage for the records of the last games.
+///Every record has its own key, so two tabs don't overwrite the records of each other.
+const STORAGE_KEY_PREFIX_GAME_RECORD: &str = "mem3_game_record_";
+///the older records are removed
+const MAX_GAME_RECORDS: usize = 10;
+///the speed changes in this order with every click
+const REPLAY_SPEEDS: [f64; 4] = [1.0, 2.0, 4.0, 0.5];
+///the shortest wait between two moves, so the eye can follow
+const MIN_DELAY_MS: f64 = 300.0;
+///the longest wait between two moves. The kids sometimes think for a long time.
+const MAX_DELAY_MS: f64 = 3_000.0;
+
+///the replay of one game
+pub struct Replay {
+    ///the recorded game
+    pub record: GameRecord,
+    ///the state after the moves that are already shown
+    pub engine: GameEngine,
+    ///the state before the first move, for a new play from the start
+    initial_engine: GameEngine,
+    ///count of moves that are already shown
+    pub count_of_moves: usize,
+    ///the moves are shown one after the other with the recorded delays
+    pub is_playing: bool,
+    ///index in `REPLAY_SPEEDS`
+    speed_index: usize,
+    ///changes with every play and pause, so the timer of an old play stops
+    generation: u32,
+    ///the record has a move that breaks the rules. The replay stops there.
+    pub error: Option<String>,
+}
+
+impl Replay {
+    ///the replay before the first move.
+    ///A record with a bad deck or with a move that breaks the rules cannot be replayed.
+    pub fn new(record: GameRecord) -> Result<Self, RecordError> {
+        let initial_engine = record.initial_engine().map_err(RecordError::BadDeck)?;
+        record.engine_after(record.moves.len())?;
+        Ok(Replay {
+            engine: initial_engine.clone(),
+            initial_engine,
+            record,
+            count_of_moves: 0,
+            is_playing: false,
+            speed_index: 0,
+            generation: 0,
+            error: None,
+        })
+    }
+    ///all the moves are shown
+    pub fn is_at_end(&self) -> bool {
+        self.count_of_moves >= self.record.moves.len() || self.error.is_some()
+    }
+    ///the speed as a factor of the real time
+    pub fn speed(&self) -> f64 {
+        REPLAY_SPEEDS[self.speed_index]
+    }
+    ///the next speed
+    pub fn change_speed(&mut self) {
+        self.speed_index = (self.speed_index + 1) % REPLAY_SPEEDS.len();
+    }
+    ///show the next move. Returns false at the end.
+    pub fn step(&mut self) -> bool {
+        if self.is_at_end() {
+            return false;
+        }
+        let recorded_move = &self.record.moves[self.count_of_moves];
+        if let Err(err) = recorded_move.apply(&mut self.engine) {
+            console::log_1(&format!("replay move {}: {}", self.count_of_moves + 1, err).into());
+            self.error = Some(format!("move {}: {}", self.count_of_moves + 1, err));
+            return false;
+        }
+        self.count_of_moves += 1;
+        true
+    }
+    ///start to play from here or from the start, if it is at the end. Returns the generation.
+    pub fn play(&mut self) -> u32 {
+        if self.is_at_end() && self.error.is_none() {
+            self.engine = self.initial_engine.clone();
+            self.count_of_moves = 0;
+        }
+        self.is_playing = true;
+        self.generation += 1;
+        self.generation
+    }
+    ///stop playing. The timer of the play stops on the next move.
+    pub fn pause(&mut self) {
+        self.is_playing = false;
+        self.generation += 1;
+    }
+    ///the timer of the play shows the next move.
+    ///Returns the delay to the move after it. None if the play ended.
+    pub fn play_next(&mut self, generation: u32) -> Option<i32> {
+        if !self.is_playing || generation != self.generation {
+            return None;
+        }
+        if !self.step() || self.is_at_end() {
+            self.is_playing = false;
+            return None;
+        }
+        Some(self.delay_ms())
+    }
+    ///the recorded time between the last move and the next one, changed by the speed
+    pub fn delay_ms(&self) -> i32 {
+        let last_at_ms = match self.count_of_moves {
+            0 => 0,
+            count_of_moves => self.record.moves[count_of_moves - 1].at_ms,
+        };
+        let next_at_ms = self
+            .record
+            .moves
+            .get(self.count_of_moves)
+            .map_or(last_at_ms, |recorded_move| recorded_move.at_ms);
+        let delay_ms = next_at_ms.saturating_sub(last_at_ms) as f64 / self.speed();
+        delay_ms.max(MIN_DELAY_MS).min(MAX_DELAY_MS) as i32
+    }
+}
+
+///the records of the last games, the newest first. Empty if there are none.
+pub fn load_game_records() -> Vec<GameRecord> {
+    let storage = local_storage();
+    let mut game_records: Vec<GameRecord> = game_record_keys(&storage)
+        .iter()
+        .filter_map(|key| storage.get_item(key).ok().and_then(|json| json))
+        .filter_map(|json| serde_json::from_str(&json).ok())
+        .collect();
+    game_records.sort_by(|a, b| b.started_at_ms.cmp(&a.started_at_ms));
+    game_records
+}
+
+///add the record of the finished game. Both players save it, but only once per game.
+///The records of the other tabs are read again, so the list has all of them.
+pub fn save_game_record(game_records: &mut Vec<GameRecord>, game_record: &GameRecord) {
+    let storage = local_storage();
+    let result = serde_json::to_string(game_record)
+        .map_err(|err| JsValue::from_str(&err.to_string()))
+        .and_then(|json| storage.set_item(&storage_key_of(game_record), &json));
+    if let Err(err) = result {
+        console::log_2(&"the game record is not saved".into(), &err);
+    }
+    *game_records = load_game_records();
+    if game_records.len() > MAX_GAME_RECORDS {
+        for old in game_records.split_off(MAX_GAME_RECORDS) {
+            let _removed = storage.remove_item(&storage_key_of(&old));
+        }
+    }
+}
+
+///the key of one record. A record saved again replaces the old one.
+fn storage_key_of(game_record: &GameRecord) -> String {
+    format!(
+        "{}{}",
+        STORAGE_KEY_PREFIX_GAME_RECORD, game_record.started_at_ms
+    )
+}
+
+///the keys of all the records in local storage
+fn game_record_keys(storage: &web_sys::Storage) -> Vec<String> {
+    let length = storage.length().unwrap_or(0);
+    (0..length)
+        .filter_map(|index| storage.key(index).ok().and_then(|key| key))
+        .filter(|key| key.starts_with(STORAGE_KEY_PREFIX_GAME_RECORD))
+        .collect()
+}
+
+///the timer shows the next move after the delay
+fn schedule_next_move(weak: &dodrio::VdomWeak, delay_ms: i32, generation: u32) {
+    let weak = weak.clone();
+    //one timer for one move. `once_into_js` frees the closure after the call.
+    let next_move_handler = Closure::once_into_js(move || {
+        wasm_bindgen_futures::spawn_local(
+            weak.with_component({
+                let v2 = weak.clone();
+                move |root| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    if let Some(replay) = &mut root_rendering_component.replay {
+                        if let Some(delay_ms) = replay.play_next(generation) {
+                            schedule_next_move(&v2, delay_ms, generation);
+                        }
+                        v2.schedule_render();
+                    }
+                }
+            })
+            .map_err(|_| ()),
+        );
+    });
+    let window = web_sys::window().expect("error: web_sys::window");
+    if let Err(err) = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+        next_move_handler.unchecked_ref(),
+        delay_ms,
+    ) {
+        console::log_2(&"replay timer not started".into(), &err);
+    }
+}
+
+///links to replay the last games
+pub fn div_replay_links<'a, 'bump>(
+    root_rendering_component: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Node<'bump>
+where
+    'a: 'bump,
+{
+    let mut vec_of_nodes = Vec::new();
+    for (index, game_record) in root_rendering_component.game_records.iter().enumerate() {
+        vec_of_nodes.push(
+            h5(bump)
+                .attr("style", "color:gray;")
+                .children([text(
+                    bumpalo::format!(in bump, "Replay {} and {} with {}",
+                        game_record.player1_nickname,
+                        game_record.player2_nickname,
+                        game_record.content_folder_name
+                    )
+                    .into_bump_str(),
+                )])
+                .on("click", move |root, vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    if let Some(game_record) = root_rendering_component.game_records.get(index) {
+                        let game_record = game_record.clone();
+                        root_rendering_component.start_replay(game_record);
+                        vdom.schedule_render();
+                    }
+                })
+                .finish(),
+        );
+    }
+    div(bump).children(vec_of_nodes).finish()
+}
+
+///the players and the controls of the replay: play, pause, step, speed and close
+pub fn div_replay<'a, 'bump>(
+    root_rendering_component: &'a RootRenderingComponent,
+    bump: &'bump Bump,
+) -> Node<'bump>
+where
+    'a: 'bump,
+{
+    let replay = match &root_rendering_component.replay {
+        Some(replay) => replay,
+        None => return div(bump).finish(),
+    };
+    let play_or_pause = if replay.is_playing { "Pause" } else { "Play" };
+    div(bump)
+        .children([
+            h3(bump)
+                .children([text(
+                    bumpalo::format!(in bump, "Replay: {} {} : {} {}",
+                        replay.record.player1_nickname,
+                        replay.engine.player1_points,
+                        replay.engine.player2_points,
+                        replay.record.player2_nickname
+                    )
+                    .into_bump_str(),
+                )])
+                .finish(),
+            h4(bump)
+                .children([text(
+                    bumpalo::format!(in bump, "Move {} of {}{}",
+                        replay.count_of_moves,
+                        replay.record.moves.len(),
+                        replay.error.as_ref().map_or(String::new(), |err| format!(". The record is broken at {}", err))
+                    )
+                    .into_bump_str(),
+                )])
+                .finish(),
+            h3(bump)
+                .attr("style", "color:green;")
+                .children([text(play_or_pause)])
+                .on("click", move |root, vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    if let Some(replay) = &mut root_rendering_component.replay {
+                        if replay.is_playing {
+                            replay.pause();
+                        } else {
+                            let generation = replay.play();
+                            schedule_next_move(&vdom, replay.delay_ms(), generation);
+                        }
+                        vdom.schedule_render();
+                    }
+                })
+                .finish(),
+            h4(bump)
+                .attr("style", "color:green;")
+                .children([text("Step")])
+                .on("click", move |root, vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    if let Some(replay) = &mut root_rendering_component.replay {
+                        replay.pause();
+                        replay.step();
+                        vdom.schedule_render();
+                    }
+                })
+                .finish(),
+            h4(bump)
+                .attr("style", "color:green;")
+                .children([text(
+                    bumpalo::format!(in bump, "Speed {}x", replay.speed()).into_bump_str(),
+                )])
+                .on("click", move |root, vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    if let Some(replay) = &mut root_rendering_component.replay {
+                        replay.change_speed();
+                        vdom.schedule_render();
+                    }
+                })
+                .finish(),
+            h5(bump)
+                .attr("style", "color:gray;")
+                .children([text("Close the replay")])
+                .on("click", move |root, vdom, _event| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    //the timer stops, because there is no replay anymore
+                    root_rendering_component.replay = None;
+                    vdom.schedule_render();
+                })
+                .finish(),
+        ])
+        .finish()
+}
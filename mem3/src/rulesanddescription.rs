@@ -11,18 +11,21 @@ use dodrio::{Node, Render};
 ///End of line in the code is simply and intuitively end of line in the string.
 ///The special character \ at the end of the line in code means that it is NOT the end of the line for the string.
 ///The escape sequence \n means end of line also. For doublequote simply \" .
-const GAME_RULES:& str = "This game is for exactly 2 players.  
+const GAME_RULES:& str = "This game is for 2 players. One player can also play alone for the best solo time.  
 Both players must have the webpage simultaneously opened in the browser to allow communication.  
-To start over just refresh the webpage.  
-The first player clicks on 'Invite for play?' and broadcasts the message over WebSocket.  
+A refresh of the webpage continues the game. To start over open the game in a new tab.  
+The first player chooses an online player or anybody and clicks on 'Invite for play?'. The invitation is sent over WebSocket.  
 He can choose different types of play: alphabet, animal,...  
-Player2 then sees on the screen 'Click here to Accept play!', clicks it and sends the message back to Player1.  
+Player2 then sees on the screen 'Click here to Accept play!', clicks it and sends the message back to Player1. Player2 can also decline the invitation.  
+An invitation without answer expires after one minute.  
 The game starts with a grid of 8 randomly shuffled card pairs face down - 16 cards in all.  
 On the screen under the grid are clear signals which player plays and which waits.  
 Player1 flips over two cards with two clicks. The cards are accompanied by sounds and text on the screen.  
 If the cards do not match, the other player clicks on 'Click here to Take your turn' and both cards are flipped back face down. Then it is his turn and he clicks to flip over his two cards.  
 If the cards match, they are left face up permanently and the player receives a point. He continues to play, he opens the next two cards.  
 The game is over when all the cards are permanently face up. It means that the sum of points is exactly 8.  
+Any player can pause the game for both players. If the other player disconnects, wait until the other player comes back or leave the game.  
+Click on 'Play alone for ...' to play without an opponent. After a mismatch click on 'Click here to continue' to flip the cards back.  
 Click on \"Play again?\" to start the game over.  ";
 
 ///game description
@@ -0,0 +1,126 @@
+//! transport
+//! The game sends and receives `WsMessage` through a transport.
+//! The rendering component does not know if it is a WebSocket or something else.
+
+use crate::clienterror::ClientError;
+use mem3_common::WsMessage;
+#[cfg(test)]
+use std::cell::RefCell;
+use strum_macros::AsRefStr;
+
+///the callback for received messages. A message that cannot be received or sent comes as an error.
+pub type OnMessage = Box<dyn Fn(Result<WsMessage, ClientError>)>;
+
+///the callback for the changes of the connection state
+pub type OnState = Box<dyn Fn(ConnectionState)>;
+
+///the state of the connection to the server
+#[derive(AsRefStr, Clone, Copy, PartialEq)]
+pub enum ConnectionState {
+    ///the first connection is opening
+    Connecting,
+    ///the messages can go
+    Open,
+    ///the connection is lost and it is opening again
+    Reconnecting,
+    ///no more attempts. The player can try again with a click.
+    Closed,
+}
+
+///send messages to the other player and receive them
+pub trait Transport {
+    ///send one message. A failure goes to the on_message callback as an error.
+    fn send(&self, msg: &WsMessage);
+    ///the callback for every received message. It is set once after the vdom is mounted.
+    fn set_on_message(&self, on_message: OnMessage);
+    ///the callback for the changes of the connection state.
+    ///A transport without a connection never calls it.
+    fn set_on_state(&self, _on_state: OnState) {}
+    ///true if the other side sends a Ping in every heartbeat interval
+    fn has_heartbeat(&self) -> bool {
+        false
+    }
+    ///open the closed connection again
+    fn reconnect(&self) {}
+}
+
+///no-op transport for offline modes. Nothing is sent and nothing is received.
+pub struct LocalTransport;
+
+impl Transport for LocalTransport {
+    fn send(&self, _msg: &WsMessage) {}
+    fn set_on_message(&self, _on_message: OnMessage) {}
+}
+
+///mock transport for tests. It records the sent messages and injects received messages.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingTransport {
+    ///json of every sent message in order
+    sent: RefCell<Vec<String>>,
+    ///the callback set by `set_on_message`
+    on_message: RefCell<Option<OnMessage>>,
+}
+
+#[cfg(test)]
+impl RecordingTransport {
+    ///json of all the sent messages
+    pub fn sent_messages(&self) -> Vec<String> {
+        self.sent.borrow().clone()
+    }
+    ///the message is received like it came from the other player
+    pub fn receive(&self, msg: WsMessage) {
+        if let Some(on_message) = self.on_message.borrow().as_ref() {
+            on_message(Ok(msg));
+        }
+    }
+}
+
+#[cfg(test)]
+impl Transport for RecordingTransport {
+    fn send(&self, msg: &WsMessage) {
+        self.sent
+            .borrow_mut()
+            .push(serde_json::to_string(msg).expect("error serde_json::to_string(msg)"));
+    }
+    fn set_on_message(&self, on_message: OnMessage) {
+        *self.on_message.borrow_mut() = Some(on_message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn recording_transport_keeps_the_sent_json_in_order() {
+        let transport = RecordingTransport::default();
+        transport.send(&WsMessage::RequestPlayerList { my_ws_uid: 1 });
+        transport.send(&WsMessage::RequestRatings { my_ws_uid: 1 });
+        assert_eq!(
+            transport.sent_messages(),
+            vec![
+                r#"{"RequestPlayerList":{"my_ws_uid":1}}"#,
+                r#"{"RequestRatings":{"my_ws_uid":1}}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn recording_transport_delivers_the_received_message() {
+        let transport = RecordingTransport::default();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let received2 = Rc::clone(&received);
+        transport.set_on_message(Box::new(move |msg| {
+            received2.borrow_mut().push(msg.ok());
+        }));
+        transport.receive(WsMessage::RequestPlayerList { my_ws_uid: 7 });
+        let received = received.borrow();
+        assert_eq!(received.len(), 1);
+        match &received[0] {
+            Some(WsMessage::RequestPlayerList { my_ws_uid }) => assert_eq!(*my_ws_uid, 7),
+            _ => panic!("expected RequestPlayerList"),
+        }
+    }
+}
@@ -1,54 +1,383 @@
 //! websocket communication
 
-use crate::gamedata::GameState;
+use crate::clienterror::ClientError;
+use crate::transport::{ConnectionState, OnMessage, OnState, Transport};
 use crate::RootRenderingComponent;
 use futures::Future;
 use js_sys::Reflect;
-use mem3_common::WsMessage;
+use mem3_common::{WsMessage, PROTOCOL_VERSION};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{console, WebSocket};
 
-///setup websocket connection
-pub fn setup_ws_connection(location_href: &str) -> WebSocket {
-    //web-sys has websocket for Rust exactly like javascript has¸
-    console::log_1(&"location_href".into());
-    console::log_1(&wasm_bindgen::JsValue::from_str(location_href));
-    //location_href comes in this format  http://localhost:4000/
-    let mut loc_href = location_href
-        .replace("http://", "ws://")
-        .replace("https://", "wss://");
-    //Only for debugging in the development environment
-    //let mut loc_href = String::from("ws://192.168.1.57:80/");
-    loc_href.push_str("mem3ws/");
-    console::log_1(&wasm_bindgen::JsValue::from_str(&loc_href));
-    //same server address and port as http server
-    let ws = WebSocket::new(&loc_href).expect("WebSocket failed to connect.");
-
-    //I don't know why is clone needed
-    let ws_c = ws.clone();
+///without any message for this long the connection indicator turns red.
+///The server sends a Ping every 10 seconds.
+const HEARTBEAT_TIMEOUT_MS: f64 = 25_000.0;
+///how often the client looks at the time of the last message
+const HEARTBEAT_CHECK_MS: i32 = 5_000;
+
+///wait before the first reconnect. Every next attempt waits longer.
+const RECONNECT_DELAY_MS: i32 = 2_000;
+///after so many failed attempts the connection is closed until the player clicks reconnect
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+
+///the session token in the session storage. A refresh of the tab keeps the session.
+const STORAGE_KEY_SESSION_TOKEN: &str = "mem3_session_token";
+
+///the transport over the WebSocket server
+pub struct WebSocketTransport {
+    ///shared with the event handlers of the socket
+    shared: Rc<Shared>,
+}
+
+///the socket and what is needed to open it again
+struct Shared {
+    ///the url of the WebSocket server
+    url: String,
+    ///web socket. used it to send message onclick. It is replaced on reconnect.
+    ws: RefCell<Option<WebSocket>>,
+    ///nickname and avatar for the handshake. They change with RequestWsUid.
+    handshake: RefCell<(String, String)>,
+    ///the server gives the same ws_uid to the same session token after a reconnect
+    session_token: RefCell<String>,
+    ///the callback set by `set_on_message`
+    on_message: RefCell<Option<OnMessage>>,
+    ///the callback set by `set_on_state`
+    on_state: RefCell<Option<OnState>>,
+    ///failed attempts since the last open connection
+    reconnect_attempts: Cell<u32>,
+    ///counts the sockets. Only the close of the newest socket schedules a reconnect.
+    connection_number: Cell<u32>,
+}
+
+impl WebSocketTransport {
+    ///setup websocket connection
+    pub fn new(location_href: &str, nickname: &str, avatar: &str) -> Result<Self, JsValue> {
+        //web-sys has websocket for Rust exactly like javascript has¸
+        console::log_1(&"location_href".into());
+        console::log_1(&wasm_bindgen::JsValue::from_str(location_href));
+        //location_href comes in this format  http://localhost:4000/
+        let mut loc_href = location_href
+            .replace("http://", "ws://")
+            .replace("https://", "wss://");
+        //Only for debugging in the development environment
+        //let mut loc_href = String::from("ws://192.168.1.57:80/");
+        loc_href.push_str("mem3ws/");
+        console::log_1(&wasm_bindgen::JsValue::from_str(&loc_href));
+        let shared = Rc::new(Shared {
+            url: loc_href,
+            ws: RefCell::new(None),
+            handshake: RefCell::new((nickname.to_string(), avatar.to_string())),
+            session_token: RefCell::new(
+                crate::session_storage()
+                    .and_then(|storage| storage.get_item(STORAGE_KEY_SESSION_TOKEN).ok())
+                    .flatten()
+                    .unwrap_or_default(),
+            ),
+            on_message: RefCell::new(None),
+            on_state: RefCell::new(None),
+            reconnect_attempts: Cell::new(0),
+            connection_number: Cell::new(0),
+        });
+        //same server address and port as http server
+        //the url is invalid if the page is not from a http server, for example file://
+        connect(&shared)?;
+        Ok(WebSocketTransport { shared })
+    }
+}
+
+impl Shared {
+    ///deliver a message or an error to the game
+    fn deliver(&self, msg: Result<WsMessage, ClientError>) {
+        if let Some(on_message) = self.on_message.borrow().as_ref() {
+            on_message(msg);
+        }
+    }
+    ///remember the session token from the server, also for a refresh of the tab
+    fn set_session_token(&self, session_token: &str) {
+        if !session_token.is_empty() && *self.session_token.borrow() != session_token {
+            *self.session_token.borrow_mut() = session_token.to_string();
+            //without the storage a refresh of the tab starts a new session
+            if let Some(storage) = crate::session_storage() {
+                if let Err(err) = storage.set_item(STORAGE_KEY_SESSION_TOKEN, session_token) {
+                    console::log_1(&err);
+                }
+            }
+        }
+    }
+    ///inform the game about the new connection state
+    fn set_state(&self, connection_state: ConnectionState) {
+        console::log_1(&format!("connection: {}", connection_state.as_ref()).into());
+        if let Some(on_state) = self.on_state.borrow().as_ref() {
+            on_state(connection_state);
+        }
+    }
+}
+
+///open a new socket with all the event handlers
+fn connect(shared: &Rc<Shared>) -> Result<(), JsValue> {
+    let ws = WebSocket::new(&shared.url)?;
+    let connection_number = shared.connection_number.get().wrapping_add(1);
+    shared.connection_number.set(connection_number);
+    shared.set_state(if shared.reconnect_attempts.get() == 0 {
+        ConnectionState::Connecting
+    } else {
+        ConnectionState::Reconnecting
+    });
+
     //It looks that the first send is in some way a handshake and is part of the connection
     //it will be execute onopen as a closure
+    let shared_c = Rc::clone(shared);
     let open_handler = Box::new(move || {
-        console::log_1(&"Connection opened, sending 'test' to server".into());
-        ws_c.send_with_str(
-            &serde_json::to_string(&WsMessage::RequestWsUid {
-                test: String::from("test"),
-            })
-            .expect("error sending test"),
-        )
-        .expect("Failed to send 'test' to server");
+        console::log_1(&"Connection opened, sending nickname to server".into());
+        shared_c.reconnect_attempts.set(0);
+        shared_c.set_state(ConnectionState::Open);
+        let (nickname, avatar) = shared_c.handshake.borrow().clone();
+        send_json(
+            &shared_c,
+            &WsMessage::RequestWsUid {
+                nickname,
+                avatar,
+                version: PROTOCOL_VERSION,
+                session_token: shared_c.session_token.borrow().clone(),
+            },
+        );
     });
-
     let cb_oh: Closure<dyn Fn()> = Closure::wrap(open_handler);
     ws.set_onopen(Some(cb_oh.as_ref().unchecked_ref()));
     //don't drop the open_handler memory
     cb_oh.forget();
-    ws
+
+    let shared_c = Rc::clone(shared);
+    let msg_recv_handler = Box::new(move |msg: JsValue| {
+        //serde_json can find out the variant of WsMessage
+        //parse json and put data in the enum
+        let msg = match Reflect::get(&msg, &"data".into())
+            .ok()
+            .and_then(|data| data.as_string())
+        {
+            Some(data) => serde_json::from_str::<WsMessage>(&data)
+                .map_err(|err| ClientError::new("websocket message", &err.to_string())),
+            None => Err(ClientError::new(
+                "websocket message",
+                "the field 'data' is missing or is not a string",
+            )),
+        };
+        if let Ok(WsMessage::ResponseWsUid { session_token, .. }) = &msg {
+            shared_c.set_session_token(session_token);
+        }
+        shared_c.deliver(msg);
+    });
+    //magic ??
+    let cb_mrh: Closure<dyn Fn(JsValue)> = Closure::wrap(msg_recv_handler);
+    ws.set_onmessage(Some(cb_mrh.as_ref().unchecked_ref()));
+    //don't drop the eventlistener from memory
+    cb_mrh.forget();
+
+    //the error is always followed by close. Only log it.
+    let error_handler = Box::new(move |err: JsValue| {
+        console::log_2(&"WebSocket error".into(), &err);
+    });
+    let cb_eh: Closure<dyn Fn(JsValue)> = Closure::wrap(error_handler);
+    ws.set_onerror(Some(cb_eh.as_ref().unchecked_ref()));
+    cb_eh.forget();
+
+    let shared_c = Rc::clone(shared);
+    let close_handler = Box::new(move |_event: JsValue| {
+        //a replaced socket closes later. The newest socket is already connecting.
+        if shared_c.connection_number.get() != connection_number {
+            return;
+        }
+        let attempts = shared_c.reconnect_attempts.get();
+        if attempts < MAX_RECONNECT_ATTEMPTS {
+            shared_c.reconnect_attempts.set(attempts + 1);
+            shared_c.set_state(ConnectionState::Reconnecting);
+            schedule_reconnect(&shared_c, RECONNECT_DELAY_MS * (attempts as i32 + 1));
+        } else {
+            shared_c.set_state(ConnectionState::Closed);
+        }
+    });
+    let cb_ch: Closure<dyn Fn(JsValue)> = Closure::wrap(close_handler);
+    ws.set_onclose(Some(cb_ch.as_ref().unchecked_ref()));
+    cb_ch.forget();
+
+    //the old socket of a manual reconnect can be still open
+    if let Some(old_ws) = shared.ws.replace(Some(ws)) {
+        if let Err(err) = old_ws.close() {
+            console::log_2(&"close of the old WebSocket failed".into(), &err);
+        }
+    }
+    Ok(())
+}
+
+///open the socket again after a delay
+fn schedule_reconnect(shared: &Rc<Shared>, delay_ms: i32) {
+    let shared_c = Rc::clone(shared);
+    let reconnect_handler = Box::new(move || {
+        if let Err(err) = connect(&shared_c) {
+            console::log_2(&"WebSocket reconnect failed".into(), &err);
+            shared_c.set_state(ConnectionState::Closed);
+        }
+    });
+    let cb_rh: Closure<dyn Fn()> = Closure::wrap(reconnect_handler);
+    let window = web_sys::window().expect("error: web_sys::window");
+    if let Err(err) = window.set_timeout_with_callback_and_timeout_and_arguments_0(
+        cb_rh.as_ref().unchecked_ref(),
+        delay_ms,
+    ) {
+        console::log_2(&"reconnect not scheduled".into(), &err);
+    }
+    cb_rh.forget();
+}
+
+///send over the current socket. A failure goes to the game as an error.
+fn send_json(shared: &Shared, msg: &WsMessage) {
+    let json = serde_json::to_string(msg).expect("error serde_json::to_string(msg)");
+    let result = match shared.ws.borrow().as_ref() {
+        Some(ws) => ws.send_with_str(&json),
+        None => Err(JsValue::from_str("no socket")),
+    };
+    if let Err(err) = result {
+        //the socket is closed or not yet open
+        console::log_2(&"Failed to send WsMessage".into(), &err);
+        shared.deliver(Err(ClientError::new(
+            "send to WebSocket server",
+            "the connection is not open",
+        )
+        .with_retry(msg.clone())));
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn send(&self, msg: &WsMessage) {
+        //the reconnect sends the same handshake
+        if let WsMessage::RequestWsUid {
+            nickname,
+            avatar,
+            version,
+            ..
+        } = msg
+        {
+            *self.shared.handshake.borrow_mut() = (nickname.clone(), avatar.clone());
+            //the game does not know the session token, the transport adds it
+            send_json(
+                &self.shared,
+                &WsMessage::RequestWsUid {
+                    nickname: nickname.clone(),
+                    avatar: avatar.clone(),
+                    version: *version,
+                    session_token: self.shared.session_token.borrow().clone(),
+                },
+            );
+            return;
+        }
+        send_json(&self.shared, msg);
+    }
+    fn set_on_message(&self, on_message: OnMessage) {
+        *self.shared.on_message.borrow_mut() = Some(on_message);
+    }
+    fn set_on_state(&self, on_state: OnState) {
+        *self.shared.on_state.borrow_mut() = Some(on_state);
+    }
+    fn has_heartbeat(&self) -> bool {
+        true
+    }
+    fn reconnect(&self) {
+        self.shared.reconnect_attempts.set(0);
+        if let Err(err) = connect(&self.shared) {
+            console::log_2(&"WebSocket reconnect failed".into(), &err);
+            self.shared.set_state(ConnectionState::Closed);
+        }
+    }
 }
 
-/// receive websocket msg callback. I don't understand this much. Too much future and promises.
-pub fn setup_ws_msg_recv(ws: &WebSocket, vdom: &dodrio::Vdom) {
+/// receive msg callback. I don't understand this much. Too much future and promises.
+pub fn setup_ws_msg_recv(transport: &dyn Transport, vdom: &dodrio::Vdom) {
+    let weak = vdom.weak();
+    //the time of the last received message
+    let last_message_ms = Rc::new(Cell::new(js_sys::Date::now()));
+    let last_message_ms2 = Rc::clone(&last_message_ms);
+    let weak2 = vdom.weak();
+    transport.set_on_state(Box::new(move |connection_state| {
+        receive_state(&weak2, connection_state)
+    }));
+    transport.set_on_message(Box::new(move |msg| match msg {
+        Ok(msg) => {
+            last_message_ms2.set(js_sys::Date::now());
+            receive_msg(&weak, msg)
+        }
+        Err(client_error) => receive_error(&weak, client_error),
+    }));
+    if transport.has_heartbeat() {
+        setup_heartbeat_check(vdom.weak(), last_message_ms);
+    }
+}
+
+///the connection indicator turns red when the heartbeats stop
+fn setup_heartbeat_check(weak: dodrio::VdomWeak, last_message_ms: Rc<Cell<f64>>) {
+    let check_handler = Box::new(move || {
+        let is_alive = js_sys::Date::now() - last_message_ms.get() < HEARTBEAT_TIMEOUT_MS;
+        wasm_bindgen_futures::spawn_local(
+            weak.with_component({
+                let v2 = weak.clone();
+                move |root| {
+                    let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                    if root_rendering_component.is_connection_alive != is_alive {
+                        console::log_1(&format!("connection alive: {}", is_alive).into());
+                        root_rendering_component.is_connection_alive = is_alive;
+                        v2.schedule_render();
+                    }
+                }
+            })
+            .map_err(|_| ()),
+        );
+    });
+    let cb_ch: Closure<dyn Fn()> = Closure::wrap(check_handler);
+    let window = web_sys::window().expect("error: web_sys::window");
+    if let Err(err) = window.set_interval_with_callback_and_timeout_and_arguments_0(
+        cb_ch.as_ref().unchecked_ref(),
+        HEARTBEAT_CHECK_MS,
+    ) {
+        console::log_2(&"heartbeat check not started".into(), &err);
+    }
+    //don't drop the interval handler from memory
+    cb_ch.forget();
+}
+
+///the connection state is shown on the next vdom tick
+fn receive_state(weak: &dodrio::VdomWeak, connection_state: ConnectionState) {
+    wasm_bindgen_futures::spawn_local(
+        weak.with_component({
+            let v2 = weak.clone();
+            move |root| {
+                let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                root_rendering_component.connection_state = connection_state;
+                v2.schedule_render();
+            }
+        })
+        .map_err(|_| ()),
+    );
+}
+
+///the error is shown on the next vdom tick
+fn receive_error(weak: &dodrio::VdomWeak, client_error: ClientError) {
+    wasm_bindgen_futures::spawn_local(
+        weak.with_component({
+            let v2 = weak.clone();
+            move |root| {
+                let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                root_rendering_component.on_client_error(client_error);
+                v2.schedule_render();
+            }
+        })
+        .map_err(|_| ()),
+    );
+}
+
+///one received message changes the RootRenderingComponent on the next vdom tick
+fn receive_msg(weak: &dodrio::VdomWeak, msg: WsMessage) {
     //Player1 on machine1 have a button Ask player to play! before he starts to play.
     //Click and it sends the WsMessage want_to_play. Player1 waits for the reply and cannot play.
     //Player2 on machine2 see the WsMessage and Accepts it.
@@ -58,159 +387,361 @@ pub fn setup_ws_msg_recv(ws: &WebSocket, vdom: &dodrio::Vdom) {
     //Machine2 receives the WsMessage and runs the same code as the player would click. The RootRenderingComponent is blocked.
     //The method with_component() needs a future (promise) It will be executed on the next vdom tick.
     //This is the only way I found to write to RootRenderingComponent fields.
-    let weak = vdom.weak();
-    let msg_recv_handler = Box::new(move |msg: JsValue| {
-        let data: JsValue =
-            Reflect::get(&msg, &"data".into()).expect("No 'data' field in websocket message!");
+    //match enum by variant and prepares the future that will be executed on the next tick
+    //in this big enum I put only boilerplate code that don't change any data.
+    //for changing data I put code in separate functions for easy reading.
+    match msg {
+        //I don't know why I need a dummy, but is entertaining to have one.
+        WsMessage::Dummy { .. } => {}
+        //the heartbeat of the server
+        WsMessage::Ping {} => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.transport.send(&WsMessage::Pong {});
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        //this Pong is only for the WebSocket server
+        WsMessage::Pong {} => {}
+        //this RequestWsUid is only for the WebSocket server
+        WsMessage::RequestWsUid { .. } => {}
+        WsMessage::ResponseWsUid {
+            your_ws_uid,
+            is_reconnect,
+            ..
+        } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_response_ws_uid(your_ws_uid, is_reconnect);
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
 
-        //serde_json can find out the variant of WsMessage
-        //parse json and put data in the enum
-        let msg: WsMessage =
-            serde_json::from_str(&data.as_string().expect("Field 'data' is not string"))
-                .unwrap_or_else(|_x| WsMessage::Dummy {
-                    dummy: String::from("error"),
-                });
-
-        //match enum by variant and prepares the future that will be executed on the next tick
-        //in this big enum I put only boilerplate code that don't change any data.
-        //for changing data I put code in separate functions for easy reading.
-        match msg {
-            //I don't know why I need a dummy, but is entertaining to have one.
-            WsMessage::Dummy { dummy } => console::log_1(&dummy.into()),
-            //this RequestWsUid is only for the WebSocket server
-            WsMessage::RequestWsUid { test } => console::log_1(&test.into()),
-            WsMessage::ResponseWsUid { your_ws_uid } => {
-                wasm_bindgen_futures::spawn_local(
-                    weak.with_component({
-                        move |root| {
-                            console::log_1(&"ResponseWsUid".into());
-                            let root_rendering_component =
-                                root.unwrap_mut::<RootRenderingComponent>();
-                            root_rendering_component.on_response_ws_uid(your_ws_uid);
-                        }
-                    })
-                    .map_err(|_| ()),
-                );
-            }
+        //this RequestPlayerList is only for the WebSocket server
+        WsMessage::RequestPlayerList { .. } => {}
+        WsMessage::ResponsePlayerList { players } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_response_player_list(players);
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::WantToPlay {
+            my_ws_uid,
+            other_ws_uid,
+            my_nickname,
+            my_avatar,
+            content_folder_name,
+        } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
 
-            WsMessage::WantToPlay {
-                my_ws_uid,
-                content_folder_name,
-            } => {
-                wasm_bindgen_futures::spawn_local(
-                    weak.with_component({
-                        let v2 = weak.clone();
-                        move |root| {
-                            let root_rendering_component =
-                                root.unwrap_mut::<RootRenderingComponent>();
-
-                            if let GameState::EndGame | GameState::Start | GameState::Asked =
-                                root_rendering_component.game_data.game_state
-                            {
-                                root_rendering_component
-                                    .on_want_to_play(my_ws_uid, content_folder_name);
-                                v2.schedule_render();
-                            }
+                        //the invitation is for anybody or exactly for me
+                        let is_for_me = other_ws_uid == 0
+                            || other_ws_uid == root_rendering_component.game_data.my_ws_uid;
+                        //the game state is checked in on_want_to_play
+                        if is_for_me {
+                            root_rendering_component.on_want_to_play(
+                                my_ws_uid,
+                                my_nickname,
+                                my_avatar,
+                                content_folder_name,
+                            );
+                            v2.schedule_render();
                         }
-                    })
-                    .map_err(|_| ()),
-                );
-            }
-            WsMessage::AcceptPlay {
-                my_ws_uid,
-                card_grid_data,
-                ..
-            } => {
-                wasm_bindgen_futures::spawn_local(
-                    weak.with_component({
-                        let v2 = weak.clone();
-                        move |root| {
-                            console::log_1(&"rcv AcceptPlay".into());
-                            let root_rendering_component =
-                                root.unwrap_mut::<RootRenderingComponent>();
-                            root_rendering_component.on_accept_play(my_ws_uid, &card_grid_data);
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::AcceptPlay {
+            my_ws_uid,
+            my_nickname,
+            my_avatar,
+            card_grid_data,
+            ..
+        } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_accept_play(
+                            my_ws_uid,
+                            my_nickname,
+                            my_avatar,
+                            &card_grid_data,
+                        );
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::DeclinePlay { my_ws_uid, .. } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_decline_play(my_ws_uid);
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::InvitationExpired { inviter_ws_uid } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_invitation_expired(inviter_ws_uid);
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::PlayerClick {
+            my_ws_uid,
+            card_index,
+            count_click_inside_one_turn,
+            ..
+        } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        if my_ws_uid == root_rendering_component.game_data.other_ws_uid {
+                            root_rendering_component
+                                .on_player_click(card_index, count_click_inside_one_turn);
                             v2.schedule_render();
                         }
-                    })
-                    .map_err(|_| ()),
-                );
-            }
-            WsMessage::PlayerClick {
-                my_ws_uid,
-                card_index,
-                count_click_inside_one_turn,
-                ..
-            } => {
-                wasm_bindgen_futures::spawn_local(
-                    weak.with_component({
-                        let v2 = weak.clone();
-                        console::log_1(&"player_click".into());
-                        move |root| {
-                            let root_rendering_component =
-                                root.unwrap_mut::<RootRenderingComponent>();
-                            console::log_1(&"other_ws_uid".into());
-                            if my_ws_uid == root_rendering_component.game_data.other_ws_uid {
-                                root_rendering_component
-                                    .on_player_click(count_click_inside_one_turn, card_index);
-                                v2.schedule_render();
-                            }
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::PlayerChange {
+            my_ws_uid,
+            state_hash,
+            ..
+        } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        if my_ws_uid == root_rendering_component.game_data.other_ws_uid {
+                            root_rendering_component.on_player_change(state_hash);
+                            v2.schedule_render();
                         }
-                    })
-                    .map_err(|_| ()),
-                );
-            }
-            WsMessage::PlayerChange { my_ws_uid, .. } => {
-                wasm_bindgen_futures::spawn_local(
-                    weak.with_component({
-                        let v2 = weak.clone();
-                        move |root| {
-                            let root_rendering_component =
-                                root.unwrap_mut::<RootRenderingComponent>();
-                            console::log_1(&"PlayerChange".into());
-                            if my_ws_uid == root_rendering_component.game_data.other_ws_uid {
-                                root_rendering_component.on_player_change();
-                                v2.schedule_render();
-                            }
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::Desync {
+            my_ws_uid, reason, ..
+        } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        if my_ws_uid == root_rendering_component.game_data.other_ws_uid {
+                            root_rendering_component.on_desync(reason);
+                            v2.schedule_render();
                         }
-                    })
-                    .map_err(|_| ()),
-                );
-            }
-            //this message is for the WebSocket server
-            WsMessage::RequestSpelling { filename } => console::log_1(&filename.into()),
-            WsMessage::ResponseSpellingJson { json } => {
-                wasm_bindgen_futures::spawn_local(
-                    weak.with_component({
-                        move |root| {
-                            console::log_1(&"ResponseSpellingJson".into());
-                            let root_rendering_component =
-                                root.unwrap_mut::<RootRenderingComponent>();
-                            root_rendering_component.on_response_spelling_json(&json)
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::PlayerPause { my_ws_uid, .. } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        if my_ws_uid == root_rendering_component.game_data.other_ws_uid {
+                            root_rendering_component.on_player_pause();
+                            v2.schedule_render();
                         }
-                    })
-                    .map_err(|_| ()),
-                );
-            }
-            WsMessage::EndGame { .. } => {
-                wasm_bindgen_futures::spawn_local(
-                    weak.with_component({
-                        move |root| {
-                            console::log_1(&"EndGame".into());
-                            let root_rendering_component =
-                                root.unwrap_mut::<RootRenderingComponent>();
-                            root_rendering_component.on_end_game();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::PlayerResume { my_ws_uid, .. } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        if my_ws_uid == root_rendering_component.game_data.other_ws_uid {
+                            root_rendering_component.on_player_resume();
+                            v2.schedule_render();
                         }
-                    })
-                    .map_err(|_| ()),
-                );
-            }
+                    }
+                })
+                .map_err(|_| ()),
+            );
         }
-    });
-
-    //magic ??
-    let cb_mrh: Closure<dyn Fn(JsValue)> = Closure::wrap(msg_recv_handler);
-    ws.set_onmessage(Some(cb_mrh.as_ref().unchecked_ref()));
-
-    //don't drop the eventlistener from memory
-    cb_mrh.forget();
+        WsMessage::RequestResync {
+            my_ws_uid,
+            old_ws_uid,
+            ..
+        } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_request_resync(my_ws_uid, old_ws_uid);
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::ResponseResync {
+            my_ws_uid,
+            game_data,
+            ..
+        } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        if my_ws_uid == root_rendering_component.game_data.other_ws_uid {
+                            root_rendering_component.on_response_resync(&game_data);
+                            v2.schedule_render();
+                        }
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        //this message is for the WebSocket server
+        WsMessage::RequestSpelling { .. } => {}
+        WsMessage::ResponseSpellingJson { json } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_response_spelling_json(&json);
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::Error {
+            code,
+            message,
+            related_msg,
+        } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_error(code, message, &related_msg);
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::Notice { message } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_notice(message);
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::ServerShuttingDown { restart_eta_secs } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_server_shutting_down(restart_eta_secs);
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        //this RequestLeaderboard is only for the WebSocket server
+        WsMessage::RequestLeaderboard { .. } => {}
+        WsMessage::ResponseLeaderboard { leaderboard } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_response_leaderboard(leaderboard);
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        //this RequestRatings is only for the WebSocket server
+        WsMessage::RequestRatings { .. } => {}
+        //the solo game is only for the WebSocket server
+        WsMessage::StartSoloGame { .. } | WsMessage::EndSoloGame { .. } => {}
+        WsMessage::ResponseRatings { ratings } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    let v2 = weak.clone();
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_response_ratings(ratings);
+                        v2.schedule_render();
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+        WsMessage::EndGame { state_hash, .. } => {
+            wasm_bindgen_futures::spawn_local(
+                weak.with_component({
+                    move |root| {
+                        let root_rendering_component = root.unwrap_mut::<RootRenderingComponent>();
+                        root_rendering_component.on_end_game(state_hash);
+                    }
+                })
+                .map_err(|_| ()),
+            );
+        }
+    }
 }
@@ -0,0 +1,745 @@
+//! game engine
+//! All the rules of the game without any dependency on the browser.
+//! The client, the server and bots use it the same way:
+//! a move changes the state and returns the events to react to (sound, messages, render).
+
+use std::collections::HashMap;
+use std::fmt;
+
+///the 3 possible states of one card
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum CardStatusCardFace {
+    ///card face down
+    Down,
+    ///card face Up Temporary
+    UpTemporary,
+    ///card face up Permanently
+    UpPermanently,
+}
+
+///all the data for one card
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Card {
+    ///card status
+    pub status: CardStatusCardFace,
+    ///field for src attribute for HTML element imagea and filename of card image
+    pub card_number_and_img_src: usize,
+    ///field for id attribute for HTML element image contains the card index
+    pub card_index_and_id: usize,
+}
+
+///what happened after a move
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    ///one card is flipped face up
+    CardFlipped {
+        ///index of the card
+        card_index: usize,
+        ///the image and the sound of the card
+        card_number_and_img_src: usize,
+    },
+    ///the two flipped cards match and the player gets one point
+    PairMatched {
+        ///the player that gets the point
+        player_number: usize,
+    },
+    ///the two flipped cards don't match. The other player takes the turn.
+    PairNotMatched,
+    ///the cards are face down again and the other player continues
+    TurnChanged {
+        ///whose turn is now: player 1 or 2
+        player_turn: usize,
+    },
+    ///all the pairs are found
+    GameOver {
+        ///player1 points
+        player1_points: usize,
+        ///player2 points
+        player2_points: usize,
+    },
+}
+
+///a move that breaks the rules. The state is not changed.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum MoveError {
+    ///all the pairs are already found
+    GameOver,
+    ///the move is not from the player on turn
+    NotYourTurn,
+    ///there is no card with this index on the board
+    CardIndexOutOfRange,
+    ///the card is already face up
+    CardNotFaceDown,
+    ///two cards are already flipped in this turn
+    TwoCardsFlipped,
+    ///the turn can change only after two cards that don't match
+    TurnNotFinished,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            MoveError::GameOver => "the game is over",
+            MoveError::NotYourTurn => "it is not your turn",
+            MoveError::CardIndexOutOfRange => "there is no card with this index",
+            MoveError::CardNotFaceDown => "the card is already face up",
+            MoveError::TwoCardsFlipped => "two cards are already flipped",
+            MoveError::TurnNotFinished => "the turn is not finished",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+///a deck that cannot be played. It comes from the other player or from a saved record.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum DeckError {
+    ///the board needs at least one pair and every card has a pair
+    BadBoardSize(usize),
+    ///two cards have the same index
+    DuplicateCardIndex(usize),
+    ///the index of the card is not its place on the board
+    WrongCardIndex(usize),
+    ///a new game starts with all the cards face down
+    CardNotFaceDown(usize),
+    ///every card number must be on the board exactly twice
+    NotAPair(usize),
+}
+
+impl fmt::Display for DeckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeckError::BadBoardSize(board_size) => {
+                write!(f, "a board of {} cards cannot be played", board_size)
+            }
+            DeckError::DuplicateCardIndex(card_index) => {
+                write!(f, "the card index {} is there twice", card_index)
+            }
+            DeckError::WrongCardIndex(card_index) => {
+                write!(f, "the card index {} is in the wrong place", card_index)
+            }
+            DeckError::CardNotFaceDown(card_index) => {
+                write!(f, "the card {} is not face down", card_index)
+            }
+            DeckError::NotAPair(card_number) => {
+                write!(f, "the card number {} is not a pair", card_number)
+            }
+        }
+    }
+}
+
+///the state of the board and of the players
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GameEngine {
+    ///vector of cards. The card with index 0 is not on the board.
+    pub vec_cards: Vec<Card>,
+    //First turn: Player1 clicks 2 times and opens 2 cards.
+    //If cards match, Player1 receives one point and countinues: 2 click for 2 cards.
+    //If not match: Player2 clicks the Change button to close opened cards.
+    //Then starts the Player2 turn.
+    ///count click inside one turn
+    pub count_click_inside_one_turn: usize,
+    ///card index of first click
+    pub card_index_of_first_click: usize,
+    ///card index of second click
+    pub card_index_of_second_click: usize,
+    ///counts the flipped cards of both players. Stored in the leaderboard.
+    pub count_all_flips: usize,
+    ///whose turn is now:  player 1 or 2
+    pub player_turn: usize,
+    ///player1 points
+    pub player1_points: usize,
+    ///player2 points
+    pub player2_points: usize,
+}
+
+impl GameEngine {
+    ///new game with the cards in this order. Every number must be there exactly twice.
+    ///Player1 starts.
+    pub fn from_card_numbers(card_numbers: &[usize]) -> Result<Self, DeckError> {
+        Self::from_cards(cards_of(card_numbers))
+    }
+
+    ///new game with the cards received from the other player. Player1 starts.
+    pub fn from_cards(vec_cards: Vec<Card>) -> Result<Self, DeckError> {
+        check_deck(&vec_cards)?;
+        Ok(Self::with_cards(vec_cards))
+    }
+
+    ///before Accept there are no random numbers, just 16 empty cards and nobody on turn
+    pub fn empty() -> Self {
+        let mut engine = Self::with_cards(cards_of(&[1; 16]));
+        engine.player_turn = 0;
+        engine
+    }
+
+    ///the game with the cards before the first move
+    fn with_cards(vec_cards: Vec<Card>) -> Self {
+        GameEngine {
+            vec_cards,
+            count_click_inside_one_turn: 0,
+            card_index_of_first_click: 0,
+            card_index_of_second_click: 0,
+            count_all_flips: 0,
+            player_turn: 1,
+            player1_points: 0,
+            player2_points: 0,
+        }
+    }
+
+    ///the board of a running game from the other player or from the storage:
+    ///every card in its place and with its pair. The cards can be face up.
+    pub fn check_board(&self) -> Result<(), DeckError> {
+        check_cards(&self.vec_cards, false)?;
+        for card_index in &[
+            self.card_index_of_first_click,
+            self.card_index_of_second_click,
+        ] {
+            if *card_index >= self.vec_cards.len() {
+                return Err(DeckError::WrongCardIndex(*card_index));
+            }
+        }
+        Ok(())
+    }
+
+    ///count of cards on the board
+    pub fn board_size(&self) -> usize {
+        //the card with index 0 is not on the board
+        self.vec_cards.len().saturating_sub(1)
+    }
+
+    ///all the pairs are found when the points sum to half of the board
+    pub fn is_game_over(&self) -> bool {
+        self.player1_points + self.player2_points >= self.board_size() / 2
+    }
+
+    ///compact hash of the board, the turn and the scores.
+    ///Both players compare it after every turn to find out if they still agree.
+    ///FNV-1a, because it must be the same on every machine and every build.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut add = |value: usize| {
+            for byte in (value as u64).to_le_bytes().iter() {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        for card in &self.vec_cards {
+            add(card.card_number_and_img_src);
+            add(card.status as usize);
+        }
+        add(self.count_click_inside_one_turn);
+        add(self.card_index_of_first_click);
+        add(self.card_index_of_second_click);
+        add(self.count_all_flips);
+        add(self.player_turn);
+        add(self.player1_points);
+        add(self.player2_points);
+        hash
+    }
+
+    ///the fields that differ from the other state. For the debug log after a desync.
+    pub fn diff(&self, other: &GameEngine) -> Vec<String> {
+        let mut diffs = Vec::new();
+        if self.vec_cards.len() != other.vec_cards.len() {
+            diffs.push(format!(
+                "board size: {} != {}",
+                self.board_size(),
+                other.board_size()
+            ));
+        }
+        for (mine, theirs) in self.vec_cards.iter().zip(other.vec_cards.iter()) {
+            if mine.card_number_and_img_src != theirs.card_number_and_img_src
+                || mine.status != theirs.status
+            {
+                diffs.push(format!(
+                    "card {}: {} {:?} != {} {:?}",
+                    mine.card_index_and_id,
+                    mine.card_number_and_img_src,
+                    mine.status,
+                    theirs.card_number_and_img_src,
+                    theirs.status
+                ));
+            }
+        }
+        let fields = [
+            (
+                "count_click_inside_one_turn",
+                self.count_click_inside_one_turn,
+                other.count_click_inside_one_turn,
+            ),
+            (
+                "card_index_of_first_click",
+                self.card_index_of_first_click,
+                other.card_index_of_first_click,
+            ),
+            (
+                "card_index_of_second_click",
+                self.card_index_of_second_click,
+                other.card_index_of_second_click,
+            ),
+            (
+                "count_all_flips",
+                self.count_all_flips,
+                other.count_all_flips,
+            ),
+            ("player_turn", self.player_turn, other.player_turn),
+            ("player1_points", self.player1_points, other.player1_points),
+            ("player2_points", self.player2_points, other.player2_points),
+        ];
+        for (name, mine, theirs) in fields.iter() {
+            if mine != theirs {
+                diffs.push(format!("{}: {} != {}", name, mine, theirs));
+            }
+        }
+        diffs
+    }
+
+    ///the player flips one card
+    pub fn click(
+        &mut self,
+        player_number: usize,
+        card_index: usize,
+    ) -> Result<Vec<GameEvent>, MoveError> {
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+        if player_number != self.player_turn {
+            return Err(MoveError::NotYourTurn);
+        }
+        if card_index == 0 || card_index > self.board_size() {
+            return Err(MoveError::CardIndexOutOfRange);
+        }
+        if self.count_click_inside_one_turn >= 2 {
+            return Err(MoveError::TwoCardsFlipped);
+        }
+        if self.vec_cards[card_index].status != CardStatusCardFace::Down {
+            return Err(MoveError::CardNotFaceDown);
+        }
+
+        //the begining of the turn is count_click_inside_one_turn=0
+        //So first click is 1 and second click is 2.
+        self.count_click_inside_one_turn += 1;
+        if self.count_click_inside_one_turn == 1 {
+            self.card_index_of_first_click = card_index;
+            self.card_index_of_second_click = 0;
+        } else {
+            self.card_index_of_second_click = card_index;
+        }
+        self.count_all_flips += 1;
+        self.vec_cards[card_index].status = CardStatusCardFace::UpTemporary;
+
+        let mut events = vec![GameEvent::CardFlipped {
+            card_index,
+            card_number_and_img_src: self.vec_cards[card_index].card_number_and_img_src,
+        }];
+        if self.count_click_inside_one_turn == 2 {
+            events.push(self.check_pair());
+            if self.is_game_over() {
+                events.push(GameEvent::GameOver {
+                    player1_points: self.player1_points,
+                    player2_points: self.player2_points,
+                });
+            }
+        }
+        Ok(events)
+    }
+
+    ///the other player closes the two cards that don't match and takes the turn
+    pub fn take_turn(&mut self, player_number: usize) -> Result<Vec<GameEvent>, MoveError> {
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+        if (player_number != 1 && player_number != 2) || player_number == self.player_turn {
+            return Err(MoveError::NotYourTurn);
+        }
+        if self.count_click_inside_one_turn != 2 {
+            return Err(MoveError::TurnNotFinished);
+        }
+        self.player_turn = if self.player_turn == 1 { 2 } else { 1 };
+        Ok(self.close_cards())
+    }
+
+    ///in a solo game the same player closes the two cards that don't match and continues
+    pub fn continue_alone(&mut self, player_number: usize) -> Result<Vec<GameEvent>, MoveError> {
+        if self.is_game_over() {
+            return Err(MoveError::GameOver);
+        }
+        if player_number != self.player_turn {
+            return Err(MoveError::NotYourTurn);
+        }
+        if self.count_click_inside_one_turn != 2 {
+            return Err(MoveError::TurnNotFinished);
+        }
+        Ok(self.close_cards())
+    }
+
+    ///click on Change button closes first and second card
+    fn close_cards(&mut self) -> Vec<GameEvent> {
+        self.vec_cards[self.card_index_of_first_click].status = CardStatusCardFace::Down;
+        self.vec_cards[self.card_index_of_second_click].status = CardStatusCardFace::Down;
+        self.card_index_of_first_click = 0;
+        self.card_index_of_second_click = 0;
+        self.count_click_inside_one_turn = 0;
+        vec![GameEvent::TurnChanged {
+            player_turn: self.player_turn,
+        }]
+    }
+
+    ///after the second click: if the cards match, player get one point and continues another turn
+    fn check_pair(&mut self) -> GameEvent {
+        let x1 = self.card_index_of_first_click;
+        let x2 = self.card_index_of_second_click;
+        if self.vec_cards[x1].card_number_and_img_src != self.vec_cards[x2].card_number_and_img_src
+        {
+            return GameEvent::PairNotMatched;
+        }
+        //give points
+        if self.player_turn == 1 {
+            self.player1_points += 1;
+        } else {
+            self.player2_points += 1;
+        }
+        // the two cards matches. make them permanent FaceUp
+        self.vec_cards[x1].status = CardStatusCardFace::UpPermanently;
+        self.vec_cards[x2].status = CardStatusCardFace::UpPermanently;
+        self.count_click_inside_one_turn = 0;
+        GameEvent::PairMatched {
+            player_number: self.player_turn,
+        }
+    }
+}
+
+///the cards for the card numbers. Index 0 is special and reserved for FaceDown.
+///Cards start with base 1.
+fn cards_of(card_numbers: &[usize]) -> Vec<Card> {
+    let mut vec_cards = vec![Card {
+        status: CardStatusCardFace::Down,
+        card_number_and_img_src: 0,
+        card_index_and_id: 0,
+    }];
+    for (index, card_number) in card_numbers.iter().enumerate() {
+        vec_cards.push(Card {
+            status: CardStatusCardFace::Down,
+            card_number_and_img_src: *card_number,
+            card_index_and_id: index + 1,
+        });
+    }
+    vec_cards
+}
+
+///the deck is a new game: every card in its place, face down and with its pair
+fn check_deck(vec_cards: &[Card]) -> Result<(), DeckError> {
+    check_cards(vec_cards, true)
+}
+
+///every card in its place and with its pair
+fn check_cards(vec_cards: &[Card], must_be_face_down: bool) -> Result<(), DeckError> {
+    //the card with index 0 is not on the board
+    let board_size = vec_cards.len().saturating_sub(1);
+    if board_size == 0 || board_size % 2 != 0 {
+        return Err(DeckError::BadBoardSize(board_size));
+    }
+    let mut is_index_used = vec![false; vec_cards.len()];
+    let mut count_of_card_numbers: HashMap<usize, usize> = HashMap::new();
+    for (index, card) in vec_cards.iter().enumerate() {
+        if let Some(is_used) = is_index_used.get_mut(card.card_index_and_id) {
+            if *is_used {
+                return Err(DeckError::DuplicateCardIndex(card.card_index_and_id));
+            }
+            *is_used = true;
+        }
+        if card.card_index_and_id != index {
+            return Err(DeckError::WrongCardIndex(card.card_index_and_id));
+        }
+        if must_be_face_down && card.status != CardStatusCardFace::Down {
+            return Err(DeckError::CardNotFaceDown(index));
+        }
+        if index > 0 {
+            *count_of_card_numbers
+                .entry(card.card_number_and_img_src)
+                .or_insert(0) += 1;
+        }
+    }
+    //the number 0 is the image of the card face down.
+    //The first wrong card on the board is reported, so the error is always the same.
+    match vec_cards
+        .iter()
+        .skip(1)
+        .map(|card| card.card_number_and_img_src)
+        .find(|card_number| *card_number == 0 || count_of_card_numbers.get(card_number) != Some(&2))
+    {
+        Some(card_number) => Err(DeckError::NotAPair(card_number)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///the pairs are next to each other: 1 1 2 2 3 3 4 4
+    fn sorted_engine() -> GameEngine {
+        GameEngine::from_card_numbers(&[1, 1, 2, 2, 3, 3, 4, 4]).expect("a valid deck")
+    }
+
+    ///count of matched pairs on the board
+    fn matched_pairs(engine: &GameEngine) -> usize {
+        engine
+            .vec_cards
+            .iter()
+            .filter(|card| card.status == CardStatusCardFace::UpPermanently)
+            .count()
+            / 2
+    }
+
+    ///xorshift, so the random sequences are the same on every run
+    struct Xorshift(u64);
+
+    impl Xorshift {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    #[test]
+    fn click_flips_the_card() {
+        let mut engine = sorted_engine();
+        let events = engine.click(1, 3).expect("a legal click");
+        assert_eq!(
+            events,
+            vec![GameEvent::CardFlipped {
+                card_index: 3,
+                card_number_and_img_src: 2
+            }]
+        );
+        assert_eq!(engine.vec_cards[3].status, CardStatusCardFace::UpTemporary);
+        assert_eq!(engine.count_click_inside_one_turn, 1);
+        assert_eq!(engine.count_all_flips, 1);
+    }
+
+    #[test]
+    fn click_breaking_the_rules_does_not_change_the_state() {
+        let mut engine = sorted_engine();
+        let state_hash = engine.state_hash();
+        assert_eq!(engine.click(2, 1), Err(MoveError::NotYourTurn));
+        assert_eq!(engine.click(1, 0), Err(MoveError::CardIndexOutOfRange));
+        assert_eq!(engine.click(1, 9), Err(MoveError::CardIndexOutOfRange));
+        assert_eq!(engine.state_hash(), state_hash);
+
+        engine.click(1, 1).expect("a legal click");
+        assert_eq!(engine.click(1, 1), Err(MoveError::CardNotFaceDown));
+        engine.click(1, 3).expect("a legal click");
+        assert_eq!(engine.click(1, 5), Err(MoveError::TwoCardsFlipped));
+    }
+
+    #[test]
+    fn matching_pair_gives_a_point_and_the_player_continues() {
+        let mut engine = sorted_engine();
+        engine.click(1, 1).expect("a legal click");
+        let events = engine.click(1, 2).expect("a legal click");
+        assert_eq!(events[1], GameEvent::PairMatched { player_number: 1 });
+        assert_eq!(engine.player1_points, 1);
+        assert_eq!(engine.player_turn, 1);
+        assert_eq!(engine.count_click_inside_one_turn, 0);
+        assert_eq!(
+            engine.vec_cards[1].status,
+            CardStatusCardFace::UpPermanently
+        );
+    }
+
+    #[test]
+    fn take_turn_only_by_the_other_player_after_a_mismatch() {
+        let mut engine = sorted_engine();
+        assert_eq!(engine.take_turn(2), Err(MoveError::TurnNotFinished));
+        engine.click(1, 1).expect("a legal click");
+        let events = engine.click(1, 3).expect("a legal click");
+        assert_eq!(events[1], GameEvent::PairNotMatched);
+        assert_eq!(engine.take_turn(1), Err(MoveError::NotYourTurn));
+        assert_eq!(engine.take_turn(3), Err(MoveError::NotYourTurn));
+        assert_eq!(
+            engine.take_turn(2),
+            Ok(vec![GameEvent::TurnChanged { player_turn: 2 }])
+        );
+        assert_eq!(engine.vec_cards[1].status, CardStatusCardFace::Down);
+        assert_eq!(engine.vec_cards[3].status, CardStatusCardFace::Down);
+        assert_eq!(engine.click(1, 1), Err(MoveError::NotYourTurn));
+    }
+
+    #[test]
+    fn solo_player_closes_the_cards_and_continues() {
+        let mut engine = sorted_engine();
+        assert_eq!(engine.continue_alone(1), Err(MoveError::TurnNotFinished));
+        engine.click(1, 1).expect("a legal click");
+        engine.click(1, 3).expect("a legal click");
+        assert_eq!(engine.continue_alone(2), Err(MoveError::NotYourTurn));
+        assert_eq!(
+            engine.continue_alone(1),
+            Ok(vec![GameEvent::TurnChanged { player_turn: 1 }])
+        );
+        assert_eq!(engine.vec_cards[1].status, CardStatusCardFace::Down);
+        assert_eq!(engine.vec_cards[3].status, CardStatusCardFace::Down);
+        assert!(engine.click(1, 1).is_ok());
+    }
+
+    #[test]
+    fn last_pair_ends_the_game() {
+        let mut engine = sorted_engine();
+        let mut events = Vec::new();
+        for card_index in 1..=8 {
+            events = engine.click(1, card_index).expect("a legal click");
+        }
+        assert_eq!(
+            events.last(),
+            Some(&GameEvent::GameOver {
+                player1_points: 4,
+                player2_points: 0
+            })
+        );
+        assert!(engine.is_game_over());
+        assert_eq!(engine.click(1, 1), Err(MoveError::GameOver));
+        assert_eq!(engine.take_turn(2), Err(MoveError::GameOver));
+    }
+
+    #[test]
+    fn state_hash_and_diff_follow_the_moves() {
+        let mut engine = sorted_engine();
+        let other = sorted_engine();
+        assert_eq!(engine.state_hash(), other.state_hash());
+        assert!(engine.diff(&other).is_empty());
+
+        engine.click(1, 2).expect("a legal click");
+        assert_ne!(engine.state_hash(), other.state_hash());
+        assert_eq!(
+            engine.diff(&other),
+            vec![
+                "card 2: 1 UpTemporary != 1 Down".to_string(),
+                "count_click_inside_one_turn: 1 != 0".to_string(),
+                "card_index_of_first_click: 2 != 0".to_string(),
+                "count_all_flips: 1 != 0".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_cards_accepts_the_json_of_the_other_player() {
+        let json = serde_json::to_string(&sorted_engine().vec_cards).expect("json of the cards");
+        let vec_cards: Vec<Card> = serde_json::from_str(&json).expect("cards from json");
+        let engine = GameEngine::from_cards(vec_cards).expect("a valid deck");
+        assert_eq!(engine.board_size(), 8);
+        assert_eq!(engine.player_turn, 1);
+    }
+
+    #[test]
+    fn from_cards_rejects_a_bad_deck() {
+        assert_eq!(
+            GameEngine::from_card_numbers(&[]).err(),
+            Some(DeckError::BadBoardSize(0))
+        );
+        assert_eq!(
+            GameEngine::from_card_numbers(&[1, 1, 2]).err(),
+            Some(DeckError::BadBoardSize(3))
+        );
+        assert_eq!(
+            GameEngine::from_card_numbers(&[1, 1, 1, 1]).err(),
+            Some(DeckError::NotAPair(1))
+        );
+        assert_eq!(
+            GameEngine::from_card_numbers(&[0, 0]).err(),
+            Some(DeckError::NotAPair(0))
+        );
+        assert_eq!(
+            GameEngine::from_cards(Vec::new()).err(),
+            Some(DeckError::BadBoardSize(0))
+        );
+
+        let mut vec_cards = sorted_engine().vec_cards;
+        vec_cards[4].card_index_and_id = 3;
+        assert_eq!(
+            GameEngine::from_cards(vec_cards).err(),
+            Some(DeckError::DuplicateCardIndex(3))
+        );
+
+        let mut vec_cards = sorted_engine().vec_cards;
+        vec_cards[4].card_index_and_id = 40;
+        assert_eq!(
+            GameEngine::from_cards(vec_cards).err(),
+            Some(DeckError::WrongCardIndex(40))
+        );
+
+        let mut vec_cards = sorted_engine().vec_cards;
+        vec_cards[2].status = CardStatusCardFace::UpPermanently;
+        assert_eq!(
+            GameEngine::from_cards(vec_cards).err(),
+            Some(DeckError::CardNotFaceDown(2))
+        );
+    }
+
+    #[test]
+    fn check_board_accepts_a_running_game_only_with_valid_indexes() {
+        let mut engine = sorted_engine();
+        engine.click(1, 1).expect("a legal click");
+        engine.click(1, 2).expect("a legal click");
+        engine.click(1, 3).expect("a legal click");
+        assert_eq!(engine.check_board(), Ok(()));
+
+        let mut broken = engine.clone();
+        broken.card_index_of_first_click = 9;
+        assert_eq!(broken.check_board(), Err(DeckError::WrongCardIndex(9)));
+
+        let mut broken = engine.clone();
+        broken.vec_cards.truncate(6);
+        assert_eq!(broken.check_board(), Err(DeckError::BadBoardSize(5)));
+
+        let mut broken = engine;
+        broken.vec_cards[5].card_number_and_img_src = 1;
+        assert_eq!(broken.check_board(), Err(DeckError::NotAPair(1)));
+    }
+
+    #[test]
+    fn random_moves_keep_the_points_and_the_replicas_in_sync() {
+        let mut rng = Xorshift(0x2545_f491_4f6c_dd1d);
+        for _game in 0..200 {
+            let mut mine =
+                GameEngine::from_card_numbers(&[3, 1, 4, 2, 2, 4, 1, 3, 5, 6, 6, 5]).expect("deck");
+            let mut theirs = mine.clone();
+            for _move in 0..300 {
+                //mostly legal moves, sometimes a move of the wrong player
+                let player_number = if rng.next(5) == 0 {
+                    1 + rng.next(2)
+                } else if mine.count_click_inside_one_turn == 2 {
+                    if mine.player_turn == 1 {
+                        2
+                    } else {
+                        1
+                    }
+                } else {
+                    mine.player_turn
+                };
+                let (my_result, their_result) = if mine.count_click_inside_one_turn == 2 {
+                    (
+                        mine.take_turn(player_number),
+                        theirs.take_turn(player_number),
+                    )
+                } else {
+                    let card_index = 1 + rng.next(mine.board_size());
+                    (
+                        mine.click(player_number, card_index),
+                        theirs.click(player_number, card_index),
+                    )
+                };
+                assert_eq!(my_result, their_result);
+                assert_eq!(mine.state_hash(), theirs.state_hash());
+                assert_eq!(
+                    mine.player1_points + mine.player2_points,
+                    matched_pairs(&mine)
+                );
+                if mine.is_game_over() {
+                    break;
+                }
+            }
+        }
+    }
+}
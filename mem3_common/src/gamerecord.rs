@@ -0,0 +1,276 @@
+//! game record
+//! Every game is recorded as an ordered list of moves, so it can be replayed later.
+//! The record starts with the deck from `AcceptPlay`. Every `PlayerClick` and `PlayerChange`
+//! adds one move with the milliseconds since the start of the game.
+//! The replay gives the moves one after the other to a new `GameEngine` with the same deck.
+//!
+//! The record is json. Version 1 looks like this:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "content_folder_name": "alphabet",
+//!   "player1_nickname": "Ana",
+//!   "player2_nickname": "Bor",
+//!   "started_at_ms": 1558353600000,
+//!   "deck": [3, 17, 5, 3, 22, 9, 17, 5, 14, 22, 9, 26, 1, 14, 26, 1],
+//!   "moves": [
+//!     { "at_ms": 1500, "player_number": 1, "action": { "Click": { "card_index": 4 } } },
+//!     { "at_ms": 2600, "player_number": 1, "action": { "Click": { "card_index": 1 } } },
+//!     { "at_ms": 4100, "player_number": 2, "action": "Change" }
+//!   ]
+//! }
+//! ```
+//!
+//! - `version` is `GAME_RECORD_VERSION`. A new version adds fields with defaults.
+//! - `started_at_ms` is the start of the game in milliseconds since the unix epoch.
+//! - `deck` is the card number of every card on the board. The first number is the card with index 1.
+//! - `at_ms` is the time of the move in milliseconds since `started_at_ms`.
+//! - `player_number` is 1 for the player that invited and 2 for the player that accepted.
+//! - `Click` flips the card with `card_index`. `Change` closes the two cards that don't match,
+//!   `player_number` is the player that takes the turn.
+
+use crate::gameengine::{DeckError, GameEngine, GameEvent, MoveError};
+use std::fmt;
+
+///the version of the json format of the record
+pub const GAME_RECORD_VERSION: u32 = 1;
+
+///what the player did
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum RecordedAction {
+    ///the player flipped one card
+    Click {
+        ///index of the card, the same as in `PlayerClick`
+        card_index: usize,
+    },
+    ///the player closed the two cards that don't match and took the turn
+    Change,
+    ///the solo player closed the two cards that don't match and continued
+    Continue,
+}
+
+///one move in the record
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedMove {
+    ///milliseconds since the start of the game
+    pub at_ms: u64,
+    ///the player that made the move: 1 or 2
+    pub player_number: usize,
+    ///what the player did
+    pub action: RecordedAction,
+}
+
+impl RecordedMove {
+    ///make the same move again on the engine
+    pub fn apply(&self, engine: &mut GameEngine) -> Result<Vec<GameEvent>, MoveError> {
+        match self.action {
+            RecordedAction::Click { card_index } => engine.click(self.player_number, card_index),
+            RecordedAction::Change => engine.take_turn(self.player_number),
+            RecordedAction::Continue => engine.continue_alone(self.player_number),
+        }
+    }
+}
+
+///a record that cannot be replayed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecordError {
+    ///the deck is not a new game
+    BadDeck(DeckError),
+    ///the move with this number, starting with 1, breaks the rules
+    IllegalMove(usize, MoveError),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordError::BadDeck(err) => write!(f, "deck: {}", err),
+            RecordError::IllegalMove(move_number, err) => {
+                write!(f, "move {}: {}", move_number, err)
+            }
+        }
+    }
+}
+
+///the deck and all the moves of one game
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GameRecord {
+    ///`GAME_RECORD_VERSION` of the writer
+    pub version: u32,
+    ///content folder name is the deck
+    pub content_folder_name: String,
+    ///nickname of the player that invited
+    pub player1_nickname: String,
+    ///nickname of the player that accepted
+    pub player2_nickname: String,
+    ///milliseconds since the unix epoch
+    pub started_at_ms: u64,
+    ///card numbers in the order on the board
+    pub deck: Vec<usize>,
+    ///the moves in the order they were made
+    pub moves: Vec<RecordedMove>,
+}
+
+impl Default for GameRecord {
+    fn default() -> Self {
+        GameRecord {
+            version: GAME_RECORD_VERSION,
+            content_folder_name: String::new(),
+            player1_nickname: String::new(),
+            player2_nickname: String::new(),
+            started_at_ms: 0,
+            deck: Vec::new(),
+            moves: Vec::new(),
+        }
+    }
+}
+
+impl GameRecord {
+    ///new record with the deck of the engine before the first move
+    pub fn new(
+        content_folder_name: &str,
+        player1_nickname: &str,
+        player2_nickname: &str,
+        engine: &GameEngine,
+        started_at_ms: u64,
+    ) -> Self {
+        GameRecord {
+            version: GAME_RECORD_VERSION,
+            content_folder_name: content_folder_name.to_string(),
+            player1_nickname: player1_nickname.to_string(),
+            player2_nickname: player2_nickname.to_string(),
+            started_at_ms,
+            //the card with index 0 is not on the board
+            deck: engine
+                .vec_cards
+                .iter()
+                .skip(1)
+                .map(|card| card.card_number_and_img_src)
+                .collect(),
+            moves: Vec::new(),
+        }
+    }
+
+    ///add the move at the end
+    pub fn push(&mut self, at_ms: u64, player_number: usize, action: RecordedAction) {
+        self.moves.push(RecordedMove {
+            at_ms,
+            player_number,
+            action,
+        });
+    }
+
+    ///the engine before the first move
+    pub fn initial_engine(&self) -> Result<GameEngine, DeckError> {
+        GameEngine::from_card_numbers(&self.deck)
+    }
+
+    ///the engine after the first `count_of_moves` moves.
+    ///A bad deck or a move that breaks the rules means the record is broken.
+    pub fn engine_after(&self, count_of_moves: usize) -> Result<GameEngine, RecordError> {
+        let mut engine = self.initial_engine().map_err(RecordError::BadDeck)?;
+        for (index, recorded_move) in self.moves.iter().take(count_of_moves).enumerate() {
+            recorded_move
+                .apply(&mut engine)
+                .map_err(|err| RecordError::IllegalMove(index + 1, err))?;
+        }
+        Ok(engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///the pairs 1 and 2 are crossed, so the first turn is a mismatch
+    const DECK: [usize; 8] = [1, 2, 1, 2, 3, 3, 4, 4];
+
+    ///play the moves like the client: every legal move is recorded
+    fn play(moves: &[(usize, RecordedAction)]) -> (GameEngine, GameRecord) {
+        let mut engine = GameEngine::from_card_numbers(&DECK).expect("a valid deck");
+        let mut record = GameRecord::new("alphabet", "Ana", "Bor", &engine, 1_558_353_600_000);
+        for (at_ms, (player_number, action)) in moves.iter().enumerate() {
+            let recorded_move = RecordedMove {
+                at_ms: at_ms as u64 * 1000,
+                player_number: *player_number,
+                action: action.clone(),
+            };
+            recorded_move.apply(&mut engine).expect("a legal move");
+            record.push(recorded_move.at_ms, *player_number, action.clone());
+        }
+        (engine, record)
+    }
+
+    fn click(player_number: usize, card_index: usize) -> (usize, RecordedAction) {
+        (player_number, RecordedAction::Click { card_index })
+    }
+
+    #[test]
+    fn record_of_a_whole_game_replays_to_the_same_engine() {
+        let (engine, record) = play(&[
+            click(1, 1),
+            click(1, 2),
+            (2, RecordedAction::Change),
+            click(2, 1),
+            click(2, 3),
+            click(2, 2),
+            click(2, 4),
+            click(2, 5),
+            click(2, 6),
+            click(2, 7),
+            click(2, 8),
+        ]);
+        assert!(engine.is_game_over());
+        assert_eq!(engine.player2_points, 4);
+        assert_eq!(record.deck, DECK.to_vec());
+
+        //the record goes through the json of the local storage
+        let json = serde_json::to_string(&record).expect("json of the record");
+        let record: GameRecord = serde_json::from_str(&json).expect("the record from json");
+        let replayed = record
+            .engine_after(record.moves.len())
+            .expect("a valid record");
+        assert_eq!(replayed.state_hash(), engine.state_hash());
+        assert!(replayed.diff(&engine).is_empty());
+
+        //after the mismatch and before the change the two cards are still face up
+        let after_mismatch = record.engine_after(2).expect("a valid record");
+        assert_eq!(after_mismatch.count_click_inside_one_turn, 2);
+        assert_eq!(after_mismatch.player_turn, 1);
+    }
+
+    #[test]
+    fn record_of_a_solo_game_replays() {
+        let (engine, record) = play(&[
+            click(1, 1),
+            click(1, 2),
+            (1, RecordedAction::Continue),
+            click(1, 1),
+            click(1, 3),
+        ]);
+        let replayed = record
+            .engine_after(record.moves.len())
+            .expect("a valid record");
+        assert!(replayed.diff(&engine).is_empty());
+        assert_eq!(replayed.player1_points, 1);
+    }
+
+    #[test]
+    fn broken_record_reports_the_deck_or_the_move() {
+        let (_engine, mut record) = play(&[click(1, 1), click(1, 2)]);
+        //the wrong player takes the turn
+        record.push(3000, 1, RecordedAction::Change);
+        assert_eq!(
+            record.engine_after(record.moves.len()).err(),
+            Some(RecordError::IllegalMove(3, MoveError::NotYourTurn))
+        );
+        //the moves before the broken one are still fine
+        assert!(record.engine_after(2).is_ok());
+
+        record.deck.pop();
+        assert_eq!(
+            record.engine_after(0).err(),
+            Some(RecordError::BadDeck(DeckError::BadBoardSize(7)))
+        );
+    }
+}
@@ -33,39 +33,197 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+
+mod gameengine;
+mod gamerecord;
+
+pub use gameengine::{Card, CardStatusCardFace, DeckError, GameEngine, GameEvent, MoveError};
+pub use gamerecord::{GameRecord, RecordError, RecordedAction, RecordedMove, GAME_RECORD_VERSION};
 //endregion
 
+///nickname and avatar of one online player
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PlayerInfo {
+    ///ws client instance unique id
+    pub ws_uid: usize,
+    ///nickname chosen by the player
+    pub nickname: String,
+    ///src of the avatar image chosen from the deck images
+    pub avatar: String,
+    ///skill rating of the nickname from the WebSocket server
+    pub rating: u32,
+}
+
+///result of one finished game, sent with EndGame or EndSoloGame and stored by the server
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GameResult {
+    ///content folder name is the deck
+    pub content_folder_name: String,
+    ///count of cards on the board
+    pub board_size: usize,
+    ///nickname of player1
+    pub player1_nickname: String,
+    ///nickname of player2
+    pub player2_nickname: String,
+    ///player1 points
+    pub player1_points: usize,
+    ///player2 points
+    pub player2_points: usize,
+    ///count of flipped cards of both players
+    pub count_all_flips: usize,
+    ///duration of the game in milliseconds
+    pub duration_ms: u64,
+    ///seconds since the unix epoch. The server writes it from the start of the running game.
+    ///Zero in the results of older versions.
+    #[serde(default)]
+    pub started_at: u64,
+    ///seconds since the unix epoch. The server writes it when it stores the result.
+    pub ended_at: u64,
+    ///one player cleared the board alone. Player2 has no nickname and no points.
+    #[serde(default)]
+    pub is_solo: bool,
+}
+
+impl GameResult {
+    ///nickname of the winner, None for a draw and for a solo game
+    pub fn winner_nickname(&self) -> Option<&str> {
+        if self.is_solo {
+            None
+        } else if self.player1_points > self.player2_points {
+            Some(&self.player1_nickname)
+        } else if self.player2_points > self.player1_points {
+            Some(&self.player2_nickname)
+        } else {
+            None
+        }
+    }
+    ///count of turns with two cards that don't match. Every turn flips two cards.
+    pub fn mismatches(&self) -> usize {
+        (self.count_all_flips / 2).saturating_sub(self.player1_points + self.player2_points)
+    }
+}
+
+///one row of the leaderboard: nickname and the value to compare
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LeaderboardRow {
+    ///nickname of the player
+    pub nickname: String,
+    ///count of wins, milliseconds or count of flips
+    pub value: u64,
+}
+
+///leaderboard for one deck and board size
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Leaderboard {
+    ///content folder name is the deck
+    pub content_folder_name: String,
+    ///count of cards on the board
+    pub board_size: usize,
+    ///players with most wins
+    pub most_wins: Vec<LeaderboardRow>,
+    ///the shortest duration of a solo game for every player
+    pub best_solo_time: Vec<LeaderboardRow>,
+    ///the fewest flips of both players in a won game for every player
+    pub fewest_flips: Vec<LeaderboardRow>,
+}
+
+///the version of the messages. The server and the client must have the same.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+///the stable codes of `WsMessage::Error`. New codes are added at the end.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ErrorCode {
+    ///the message is not valid json or has missing fields
+    BadMessage,
+    ///there is no deck with this content folder name
+    UnknownDeck,
+    ///the move came when the other player is on turn
+    NotYourTurn,
+    ///the other player is not connected anymore
+    OpponentGone,
+    ///the client and the server have different `PROTOCOL_VERSION`
+    VersionMismatch,
+    ///too many messages in a short time
+    RateLimited,
+}
+
 ///`WsMessage` enum for websocket
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub enum WsMessage {
     ///Dummy
     Dummy {
         ///anything
         dummy: String,
     },
-    ///Request websocket Uid
+    ///Request websocket Uid. It is the handshake and it is sent again when the nickname changes.
     RequestWsUid {
-        ///anything
-        test: String,
+        ///nickname chosen by the player
+        nickname: String,
+        ///src of the avatar image
+        avatar: String,
+        ///`PROTOCOL_VERSION` of the client. Old clients don't send it.
+        #[serde(default)]
+        version: u32,
+        ///token of the session from the last ResponseWsUid. Empty for a new session.
+        #[serde(default)]
+        session_token: String,
     },
     ///response for ConnectionTest
     ResponseWsUid {
-        ///websocket Uid
+        ///websocket Uid. With the session token it is the same after a reconnect.
         your_ws_uid: usize,
+        ///the client sends it back with the next RequestWsUid
+        #[serde(default)]
+        session_token: String,
+        ///the session was restored, the game continues with a resync
+        #[serde(default)]
+        is_reconnect: bool,
+    },
+    ///Request the list of online players from the WebSocket server
+    RequestPlayerList {
+        ///ws client instance unique id. To not listen the echo to yourself.
+        my_ws_uid: usize,
+    },
+    ///Receive the list of online players from the WebSocket server
+    ResponsePlayerList {
+        ///all connected players
+        players: Vec<PlayerInfo>,
     },
     ///want to play
     WantToPlay {
         ///ws client instance unique id. To not listen the echo to yourself.
         my_ws_uid: usize,
+        ///invited player unique id. Zero means the invitation is for anybody.
+        other_ws_uid: usize,
+        ///nickname of the player that invites
+        my_nickname: String,
+        ///avatar of the player that invites
+        my_avatar: String,
         ///content folder name
         content_folder_name: String,
     },
+    ///decline the invitation
+    DeclinePlay {
+        ///ws client instance unique id. To not listen the echo to yourself.
+        my_ws_uid: usize,
+        ///the player that invited. Used by the WebSocket server.
+        other_ws_uid: usize,
+    },
+    ///the server removed an invitation that was not accepted in time
+    InvitationExpired {
+        ///the player that invited
+        inviter_ws_uid: usize,
+    },
     /// accept play
     AcceptPlay {
         ///ws client instance unique id. To not listen the echo to yourself.
         my_ws_uid: usize,
         ///other player unique id. Used by the WebSocket server.
         other_ws_uid: usize,
+        ///nickname of the player that accepts
+        my_nickname: String,
+        ///avatar of the player that accepts
+        my_avatar: String,
         ///act is the action to take on the receiver
         card_grid_data: String,
     },
@@ -86,6 +244,59 @@ pub enum WsMessage {
         my_ws_uid: usize,
         ///other player unique id. Used by the WebSocket server.
         other_ws_uid: usize,
+        ///hash of the game state after the change. The other player compares it.
+        state_hash: u64,
+    },
+    ///pause the game for both players
+    PlayerPause {
+        ///ws client instance unique id. To not listen the echo to yourself.
+        my_ws_uid: usize,
+        ///other player unique id. Used by the WebSocket server.
+        other_ws_uid: usize,
+    },
+    ///continue the paused game
+    PlayerResume {
+        ///ws client instance unique id. To not listen the echo to yourself.
+        my_ws_uid: usize,
+        ///other player unique id. Used by the WebSocket server.
+        other_ws_uid: usize,
+    },
+    ///the received move does not match the local state
+    Desync {
+        ///ws client instance unique id. To not listen the echo to yourself.
+        my_ws_uid: usize,
+        ///other player unique id. Used by the WebSocket server.
+        other_ws_uid: usize,
+        ///what does not match
+        reason: String,
+    },
+    ///after a refresh the player has a new ws_uid and asks the other player for the game state
+    RequestResync {
+        ///the new ws client instance unique id
+        my_ws_uid: usize,
+        ///the ws_uid before the refresh. The other player knows only this one.
+        old_ws_uid: usize,
+        ///other player unique id. Used by the WebSocket server.
+        other_ws_uid: usize,
+    },
+    ///the game state for the player after a refresh
+    ResponseResync {
+        ///ws client instance unique id. To not listen the echo to yourself.
+        my_ws_uid: usize,
+        ///other player unique id. Used by the WebSocket server.
+        other_ws_uid: usize,
+        ///json of the game data
+        game_data: String,
+    },
+    ///Request the ratings of all nicknames from the WebSocket server
+    RequestRatings {
+        ///ws client instance unique id. To not listen the echo to yourself.
+        my_ws_uid: usize,
+    },
+    ///Receive the ratings from the WebSocket server
+    ResponseRatings {
+        ///nickname and rating, the best first
+        ratings: Vec<LeaderboardRow>,
     },
     ///end game
     EndGame {
@@ -93,6 +304,59 @@ pub enum WsMessage {
         my_ws_uid: usize,
         ///other player unique id. Used by the WebSocket server.
         other_ws_uid: usize,
+        ///the result is stored by the WebSocket server for the leaderboard
+        game_result: GameResult,
+        ///hash of the final game state. The other player compares it.
+        state_hash: u64,
+    },
+    ///the player starts a game alone. The server keeps the start for the best solo time.
+    StartSoloGame {
+        ///ws client instance unique id
+        my_ws_uid: usize,
+        ///content folder name is the deck
+        content_folder_name: String,
+    },
+    ///the player cleared the board alone
+    EndSoloGame {
+        ///ws client instance unique id
+        my_ws_uid: usize,
+        ///the result is stored by the WebSocket server for the leaderboard
+        game_result: GameResult,
+    },
+    ///heartbeat from the server. The client answers with Pong.
+    Ping {},
+    ///the answer to Ping. The server knows the connection is alive.
+    Pong {},
+    ///the last message could not be handled
+    Error {
+        ///stable code for the client logic
+        code: ErrorCode,
+        ///human readable text
+        message: String,
+        ///the name of the message that caused the error. Empty if unknown.
+        related_msg: String,
+    },
+    ///information for the player. Nothing went wrong.
+    Notice {
+        ///human readable text
+        message: String,
+    },
+    ///the server stops. The connection closes after this message.
+    ServerShuttingDown {
+        ///seconds until the server is back. None if unknown.
+        restart_eta_secs: Option<u64>,
+    },
+    ///Request the leaderboard from the WebSocket server
+    RequestLeaderboard {
+        ///content folder name is the deck
+        content_folder_name: String,
+        ///count of cards on the board
+        board_size: usize,
+    },
+    ///Receive the leaderboard from the WebSocket server
+    ResponseLeaderboard {
+        ///the leaderboard for the requested deck and board size
+        leaderboard: Leaderboard,
     },
     ///Request the spelling from the WebSocket server
     RequestSpelling {
@@ -105,4 +369,3 @@ pub enum WsMessage {
         json: String,
     },
 }
-//endregion
@@ -0,0 +1,104 @@
+//! benchmark
+//! Many games send moves at the same time. Every move goes the same way as on the server:
+//! the connection marks itself alive, finds the other player and puts the message in its queue.
+//! The list of players is collected from time to time, like for the broadcast of the player list.
+//! The throughput with one shard (the old global lock) is compared with the sharded map.
+
+use crate::outgoingqueue::OutgoingQueue;
+use crate::requesterror::other_ws_uid_of;
+use crate::users::{User, UserMap, DEFAULT_SHARD_COUNT};
+use mem3_common::WsMessage;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+use warp::ws::Message;
+
+///one move of this count collects the list of players
+const BROADCAST_EVERY: usize = 100;
+
+///run the benchmark and print the messages per second for one shard and for the sharded map
+pub fn run(games: usize, moves_per_game: usize, threads: usize) {
+    println!(
+        "{} games, {} moves per game, {} threads",
+        games, moves_per_game, threads
+    );
+    for &shard_count in &[1, DEFAULT_SHARD_COUNT] {
+        let messages_per_second = measure(shard_count, games, moves_per_game, threads.max(1));
+        println!(
+            "{:>3} shards: {:>12.0} messages per second",
+            shard_count, messages_per_second
+        );
+    }
+}
+
+///messages per second of all threads together
+fn measure(shard_count: usize, games: usize, moves_per_game: usize, threads: usize) -> f64 {
+    let users = Arc::new(UserMap::new(shard_count));
+    //the streams must live, else the queues are disconnected
+    let mut streams = Vec::new();
+    //the 2 players of the game g have the ws uid 2*g+1 and 2*g+2, zero is never a ws uid
+    for ws_uid in 1..=games * 2 {
+        let (queue, stream, _closed) = OutgoingQueue::new(moves_per_game);
+        users.insert(ws_uid, User::new(queue, 0));
+        streams.push(stream);
+    }
+
+    let start = Instant::now();
+    let handles: Vec<thread::JoinHandle<usize>> = (0..threads)
+        .map(|thread_index| {
+            let users = Arc::clone(&users);
+            thread::spawn(move || {
+                let mut count = 0;
+                for game in (thread_index..games).step_by(threads) {
+                    count += play_game(&users, game, moves_per_game);
+                }
+                count
+            })
+        })
+        .collect();
+    let count: usize = handles
+        .into_iter()
+        .map(|handle| handle.join().expect("benchmark thread panicked"))
+        .sum();
+    let elapsed = start.elapsed();
+    drop(streams);
+
+    #[allow(clippy::cast_precision_loss)]
+    let messages = count as f64;
+    messages / (elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9)
+}
+
+///the players of one game send moves in turns. Returns the count of sent messages.
+fn play_game(users: &UserMap, game: usize, moves_per_game: usize) -> usize {
+    let players = [game * 2 + 1, game * 2 + 2];
+    //the server receives text, the json of the clients is not measured
+    let moves: Vec<String> = (0..2)
+        .map(|player_index| {
+            serde_json::to_string(&WsMessage::PlayerClick {
+                my_ws_uid: players[player_index],
+                other_ws_uid: players[1 - player_index],
+                card_index: 0,
+                count_click_inside_one_turn: 1,
+            })
+            .expect("serde_json::to_string(&WsMessage::PlayerClick)")
+        })
+        .collect();
+    let mut count = 0;
+    for move_index in 0..moves_per_game {
+        let new_msg = &moves[move_index % 2];
+        if let Some(me) = users.get(players[move_index % 2]) {
+            me.touch();
+        }
+        let other_ws_uid = other_ws_uid_of(new_msg).expect("other_ws_uid_of");
+        if let Some(other) = users.get(other_ws_uid) {
+            if other.queue.send(Message::text(new_msg.as_str())).is_ok() {
+                count += 1;
+            }
+        }
+        //the broadcast locks all shards one after the other
+        if move_index % BROADCAST_EVERY == 0 {
+            let _players = users.snapshot();
+        }
+    }
+    count
+}
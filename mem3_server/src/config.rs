@@ -0,0 +1,612 @@
+//! config
+//! All the settings of the server in one place.
+//! The defaults are overwritten by the toml file, then by the environment variables
+//! and at last by the command line. `--print-config` shows the result.
+
+use crate::leaderboard::GAME_LOG_FILENAME;
+use crate::outgoingqueue::DEFAULT_QUEUE_CAPACITY;
+use crate::ratings::RATINGS_FILENAME;
+use crate::sessions::SNAPSHOT_FILENAME;
+use crate::users::DEFAULT_SHARD_COUNT;
+use clap::ArgMatches;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+///the config file in the current directory. It is not an error if it is missing.
+pub const CONFIG_FILENAME: &str = "mem3_server.toml";
+///the longest timeout or interval: one day
+const MAX_SECS: u64 = 24 * 60 * 60;
+
+///settings of the server
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    ///where to listen
+    pub server: ServerConfig,
+    ///files and folders
+    pub paths: PathsConfig,
+    ///limits against slow or noisy clients
+    pub limits: LimitsConfig,
+    ///parts of the server that can be switched off
+    pub features: FeaturesConfig,
+    ///https and wss
+    pub tls: TlsConfig,
+    ///what happens on SIGINT and SIGTERM
+    pub shutdown: ShutdownConfig,
+    ///the snapshot of the sessions and the players of the running games
+    pub persistence: PersistenceConfig,
+}
+
+///where to listen and what to log
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ServerConfig {
+    ///ip addresses for listening, IPv4 or IPv6. With a port like `[::1]:8080` or without.
+    pub addresses: Vec<String>,
+    ///port for the addresses without a port
+    pub port: u16,
+    ///listen on all IPv4 and IPv6 addresses of the computer
+    pub dual_stack: bool,
+    ///listen also on the LAN address of the computer
+    pub auto_ip: bool,
+    ///route of the websocket. The wasm client connects to `/mem3ws/`.
+    pub ws_path: String,
+    ///error, warn, info, debug or trace. RUST_LOG has precedence.
+    pub log_level: String,
+}
+
+///files and folders
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PathsConfig {
+    ///the static files of the wasm client
+    pub static_root: PathBuf,
+    ///the decks with images, sounds and spellings. Served on the route `/content/`.
+    pub content_dir: PathBuf,
+    ///the finished games, one json per line
+    pub game_log: PathBuf,
+    ///the ratings of all nicknames
+    pub ratings: PathBuf,
+}
+
+///limits against slow or noisy clients
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LimitsConfig {
+    ///outgoing messages waiting for one client. A slower client is disconnected.
+    pub queue_size: usize,
+    ///a client that sends more messages in one second gets `ErrorCode::RateLimited`
+    pub max_messages_per_second: u32,
+    ///the server removes the invitation after this time
+    pub invitation_timeout_secs: u64,
+    ///the server sends a Ping to all users in this interval
+    pub heartbeat_interval_secs: u64,
+    ///a connection without any message for this long is closed
+    pub idle_timeout_secs: u64,
+    ///count of locks for the connected users
+    pub shards: usize,
+}
+
+///parts of the server that can be switched off
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FeaturesConfig {
+    ///the finished games are written to the game log for the leaderboard
+    pub game_log: bool,
+    ///the ratings are updated after every game
+    pub ratings: bool,
+    ///the route `/mem3stats` with the depth of the queues.
+    ///Off by default, because the route has no authentication.
+    pub stats: bool,
+    ///the route `/mem3export` with the finished games as csv or json.
+    ///Off by default, because the route has no authentication and shows the nicknames.
+    pub export: bool,
+}
+
+///https and wss with the certificate and key in pem files
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TlsConfig {
+    ///without tls the server speaks plain http and ws
+    pub enabled: bool,
+    ///the certificate chain
+    pub cert: PathBuf,
+    ///the private key
+    pub key: PathBuf,
+}
+
+///what happens on SIGINT and SIGTERM
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    ///the clients are told that the server is back after this time. Zero is unknown.
+    pub restart_eta_secs: u64,
+    ///the longest wait for the outgoing queues before the exit
+    pub flush_timeout_secs: u64,
+}
+
+///the sessions and the players of the running games survive the restart of the server.
+///The state of the game is only in the clients.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PersistenceConfig {
+    ///without persistence a restart ends all games
+    pub enabled: bool,
+    ///the snapshot file, read on start
+    pub file: PathBuf,
+    ///the snapshot is written in this interval and on shutdown. Only with persistence.
+    pub interval_secs: u64,
+    ///a session without a reconnect for this long is removed with its game
+    pub session_timeout_secs: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            server: ServerConfig::default(),
+            paths: PathsConfig::default(),
+            limits: LimitsConfig::default(),
+            features: FeaturesConfig::default(),
+            tls: TlsConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            persistence: PersistenceConfig::default(),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            addresses: vec!["127.0.0.22".to_string()],
+            port: 80,
+            dual_stack: false,
+            auto_ip: false,
+            ws_path: "mem3ws".to_string(),
+            log_level: "info".to_string(),
+        }
+    }
+}
+
+impl Default for PathsConfig {
+    fn default() -> Self {
+        PathsConfig {
+            static_root: PathBuf::from("./mem3/"),
+            content_dir: PathBuf::from("./mem3/content/"),
+            game_log: PathBuf::from(GAME_LOG_FILENAME),
+            ratings: PathBuf::from(RATINGS_FILENAME),
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            queue_size: DEFAULT_QUEUE_CAPACITY,
+            max_messages_per_second: 30,
+            invitation_timeout_secs: 60,
+            heartbeat_interval_secs: 10,
+            idle_timeout_secs: 35,
+            shards: DEFAULT_SHARD_COUNT,
+        }
+    }
+}
+
+impl Default for FeaturesConfig {
+    fn default() -> Self {
+        FeaturesConfig {
+            game_log: true,
+            ratings: true,
+            stats: false,
+            export: false,
+        }
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            enabled: false,
+            cert: PathBuf::from("mem3_cert.pem"),
+            key: PathBuf::from("mem3_key.pem"),
+        }
+    }
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            restart_eta_secs: 0,
+            flush_timeout_secs: 5,
+        }
+    }
+}
+
+impl Default for PersistenceConfig {
+    fn default() -> Self {
+        PersistenceConfig {
+            enabled: false,
+            file: PathBuf::from(SNAPSHOT_FILENAME),
+            interval_secs: 30,
+            session_timeout_secs: 600,
+        }
+    }
+}
+
+///why the config cannot be used
+#[derive(Debug)]
+pub enum ConfigError {
+    ///the file cannot be read
+    Io(PathBuf, io::Error),
+    ///the file is not valid toml for the config
+    Toml(PathBuf, toml::de::Error),
+    ///a value from the environment or the command line is not valid
+    BadValue(String, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            ConfigError::Toml(path, err) => write!(f, "bad config {}: {}", path.display(), err),
+            ConfigError::BadValue(name, value) => write!(f, "bad value of {}: {}", name, value),
+        }
+    }
+}
+
+impl Config {
+    ///the defaults overwritten by the file. An explicit file must exist.
+    pub fn from_file(path: &Path, is_explicit: bool) -> Result<Self, ConfigError> {
+        if !is_explicit && !path.exists() {
+            return Ok(Config::default());
+        }
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+        toml::from_str(&text).map_err(|err| ConfigError::Toml(path.to_path_buf(), err))
+    }
+
+    ///the values from the environment or the command line overwrite the file.
+    ///Clap takes the environment variable, if the flag is not on the command line.
+    pub fn apply_matches(&mut self, matches: &ArgMatches) -> Result<(), ConfigError> {
+        if let Some(addresses) = matches.values_of("prm_ip") {
+            self.server.addresses = addresses.map(str::to_string).collect();
+        }
+        parse_into(matches, "prm_port", &mut self.server.port)?;
+        parse_into(matches, "dual_stack", &mut self.server.dual_stack)?;
+        if matches.is_present("auto_ip") {
+            self.server.auto_ip = true;
+        }
+        if let Some(ws_path) = matches.value_of("ws_path") {
+            self.server.ws_path = ws_path.to_string();
+        }
+        if let Some(log_level) = matches.value_of("log_level") {
+            self.server.log_level = log_level.to_string();
+        }
+        if let Some(static_root) = matches.value_of("static_root") {
+            self.paths.static_root = PathBuf::from(static_root);
+        }
+        if let Some(content_dir) = matches.value_of("content_dir") {
+            self.paths.content_dir = PathBuf::from(content_dir);
+        }
+        if let Some(game_log) = matches.value_of("game_log_file") {
+            self.paths.game_log = PathBuf::from(game_log);
+        }
+        if let Some(ratings) = matches.value_of("ratings_file") {
+            self.paths.ratings = PathBuf::from(ratings);
+        }
+        parse_into(matches, "prm_queue_size", &mut self.limits.queue_size)?;
+        parse_into(
+            matches,
+            "max_messages_per_second",
+            &mut self.limits.max_messages_per_second,
+        )?;
+        parse_into(
+            matches,
+            "invitation_timeout",
+            &mut self.limits.invitation_timeout_secs,
+        )?;
+        parse_into(
+            matches,
+            "heartbeat_interval",
+            &mut self.limits.heartbeat_interval_secs,
+        )?;
+        parse_into(matches, "idle_timeout", &mut self.limits.idle_timeout_secs)?;
+        parse_into(matches, "shards", &mut self.limits.shards)?;
+        parse_into(matches, "game_log", &mut self.features.game_log)?;
+        parse_into(matches, "ratings", &mut self.features.ratings)?;
+        parse_into(matches, "stats", &mut self.features.stats)?;
+        parse_into(matches, "export", &mut self.features.export)?;
+        parse_into(matches, "tls", &mut self.tls.enabled)?;
+        if let Some(cert) = matches.value_of("tls_cert") {
+            self.tls.cert = PathBuf::from(cert);
+        }
+        if let Some(key) = matches.value_of("tls_key") {
+            self.tls.key = PathBuf::from(key);
+        }
+        parse_into(matches, "restart_eta", &mut self.shutdown.restart_eta_secs)?;
+        parse_into(
+            matches,
+            "flush_timeout",
+            &mut self.shutdown.flush_timeout_secs,
+        )?;
+        parse_into(matches, "persistence", &mut self.persistence.enabled)?;
+        if let Some(file) = matches.value_of("snapshot_file") {
+            self.persistence.file = PathBuf::from(file);
+        }
+        parse_into(
+            matches,
+            "snapshot_interval",
+            &mut self.persistence.interval_secs,
+        )?;
+        parse_into(
+            matches,
+            "session_timeout",
+            &mut self.persistence.session_timeout_secs,
+        )?;
+        Ok(())
+    }
+
+    ///zero intervals stop the timers with a panic and a zero queue disconnects every client.
+    ///The server does not start with such values.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        check_range(
+            "limits.queue_size",
+            self.limits.queue_size as u64,
+            1,
+            1_000_000,
+        )?;
+        check_range(
+            "limits.max_messages_per_second",
+            u64::from(self.limits.max_messages_per_second),
+            1,
+            10_000,
+        )?;
+        check_range(
+            "limits.invitation_timeout_secs",
+            self.limits.invitation_timeout_secs,
+            1,
+            MAX_SECS,
+        )?;
+        check_range(
+            "limits.heartbeat_interval_secs",
+            self.limits.heartbeat_interval_secs,
+            1,
+            MAX_SECS,
+        )?;
+        //a client answers the Ping only after the heartbeat interval
+        check_range(
+            "limits.idle_timeout_secs",
+            self.limits.idle_timeout_secs,
+            self.limits.heartbeat_interval_secs + 1,
+            MAX_SECS,
+        )?;
+        check_range("limits.shards", self.limits.shards as u64, 1, 1024)?;
+        check_range(
+            "shutdown.flush_timeout_secs",
+            self.shutdown.flush_timeout_secs,
+            0,
+            MAX_SECS,
+        )?;
+        //the sessions time out also without persistence
+        check_range(
+            "persistence.session_timeout_secs",
+            self.persistence.session_timeout_secs,
+            1,
+            MAX_SECS,
+        )?;
+        if self.persistence.enabled {
+            check_range(
+                "persistence.interval_secs",
+                self.persistence.interval_secs,
+                1,
+                MAX_SECS,
+            )?;
+        }
+        //the slashes around the path are removed on start
+        let ws_path = self.server.ws_path.trim_matches('/');
+        if ws_path.is_empty() || ws_path.contains('/') {
+            return Err(ConfigError::BadValue(
+                "server.ws_path".to_string(),
+                format!("{:?} must be one path segment", self.server.ws_path),
+            ));
+        }
+        Ok(())
+    }
+
+    ///the config as toml, the same format as the file
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("toml::to_string_pretty(&Config)")
+    }
+
+    ///all the addresses for listening without duplicates.
+    ///The detected LAN address is used only with `auto_ip`.
+    pub fn listen_addrs(&self, lan_ip: Option<IpAddr>) -> Result<Vec<SocketAddr>, ConfigError> {
+        let mut listen_addrs = Vec::new();
+        if self.server.dual_stack {
+            //IPv6 first: on Linux it takes also IPv4 and the second bind is not needed
+            listen_addrs.push(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                self.server.port,
+            ));
+            listen_addrs.push(SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                self.server.port,
+            ));
+        }
+        for address in &self.server.addresses {
+            listen_addrs.push(parse_listen_addr(address, self.server.port)?);
+        }
+        if self.server.auto_ip {
+            match lan_ip {
+                Some(lan_ip) => listen_addrs.push(SocketAddr::new(lan_ip, self.server.port)),
+                None => {
+                    return Err(ConfigError::BadValue(
+                        "auto_ip".to_string(),
+                        "no LAN address found".to_string(),
+                    ))
+                }
+            }
+        }
+        let mut unique_addrs: Vec<SocketAddr> = Vec::new();
+        for listen_addr in listen_addrs {
+            if !unique_addrs.contains(&listen_addr) {
+                unique_addrs.push(listen_addr);
+            }
+        }
+        Ok(unique_addrs)
+    }
+
+    ///the invitation timeout as duration
+    pub fn invitation_timeout(&self) -> Duration {
+        Duration::from_secs(self.limits.invitation_timeout_secs)
+    }
+
+    ///the heartbeat interval as duration
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(self.limits.heartbeat_interval_secs)
+    }
+
+    ///the restart eta for the clients
+    pub fn restart_eta_secs(&self) -> Option<u64> {
+        match self.shutdown.restart_eta_secs {
+            0 => None,
+            restart_eta_secs => Some(restart_eta_secs),
+        }
+    }
+
+    ///the flush timeout as duration
+    pub fn flush_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown.flush_timeout_secs)
+    }
+
+    ///the idle timeout as duration
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.limits.idle_timeout_secs)
+    }
+
+    ///the snapshot interval as duration
+    pub fn snapshot_interval(&self) -> Duration {
+        Duration::from_secs(self.persistence.interval_secs)
+    }
+
+    ///the session timeout as duration
+    pub fn session_timeout(&self) -> Duration {
+        Duration::from_secs(self.persistence.session_timeout_secs)
+    }
+}
+
+///`1.2.3.4`, `1.2.3.4:80`, `::1` or `[::1]:80`
+fn parse_listen_addr(address: &str, port: u16) -> Result<SocketAddr, ConfigError> {
+    address
+        .parse::<SocketAddr>()
+        .or_else(|_| {
+            address
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .map(|ip| SocketAddr::new(ip, port))
+        })
+        .map_err(|_| ConfigError::BadValue("address".to_string(), address.to_string()))
+}
+
+///the value is between min and max, both included
+fn check_range(name: &str, value: u64, min: u64, max: u64) -> Result<(), ConfigError> {
+    if value < min || value > max {
+        return Err(ConfigError::BadValue(
+            name.to_string(),
+            format!("{} is not between {} and {}", value, min, max),
+        ));
+    }
+    Ok(())
+}
+
+///parse the value of the flag, if there is one
+fn parse_into<T: std::str::FromStr>(
+    matches: &ArgMatches,
+    name: &str,
+    value: &mut T,
+) -> Result<(), ConfigError> {
+    if let Some(text) = matches.value_of(name) {
+        *value = text
+            .parse()
+            .map_err(|_| ConfigError::BadValue(name.to_string(), text.to_string()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn command_line_over_environment_over_file() {
+        let path = env::temp_dir().join(format!("mem3_server_{}.toml", process::id()));
+        fs::write(
+            &path,
+            "[server]\nport = 8001\n\n[limits]\nqueue_size = 100\nshards = 3\n",
+        )
+        .expect("write the config file");
+        //only this test reads these environment variables
+        env::set_var("MEM3_PORT", "8002");
+        env::set_var("MEM3_QUEUE_SIZE", "200");
+        let matches = crate::app().get_matches_from(vec!["mem3_server", "--queue-size", "300"]);
+        env::remove_var("MEM3_PORT");
+        env::remove_var("MEM3_QUEUE_SIZE");
+
+        let mut config = Config::from_file(&path, true).expect("the config file");
+        fs::remove_file(&path).expect("remove the config file");
+        config.apply_matches(&matches).expect("valid values");
+        assert_eq!(config.limits.shards, 3);
+        assert_eq!(config.server.port, 8002);
+        assert_eq!(config.limits.queue_size, 300);
+        assert_eq!(config.limits.heartbeat_interval_secs, 10);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn explicit_config_file_must_exist() {
+        let path = Path::new("mem3_server_that_does_not_exist.toml");
+        assert!(Config::from_file(path, false).is_ok());
+        assert!(Config::from_file(path, true).is_err());
+    }
+
+    #[test]
+    fn zero_intervals_and_queue_are_rejected() {
+        assert!(Config::default().validate().is_ok());
+
+        let mut config = Config::default();
+        config.limits.heartbeat_interval_secs = 0;
+        assert_eq!(
+            config.validate().expect_err("zero heartbeat").to_string(),
+            "bad value of limits.heartbeat_interval_secs: 0 is not between 1 and 86400"
+        );
+
+        let mut config = Config::default();
+        config.limits.queue_size = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.limits.idle_timeout_secs = config.limits.heartbeat_interval_secs;
+        assert!(config.validate().is_err());
+
+        //the snapshot interval matters only with persistence
+        let mut config = Config::default();
+        config.persistence.interval_secs = 0;
+        assert!(config.validate().is_ok());
+        config.persistence.enabled = true;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.server.ws_path = "/mem3ws/".to_string();
+        assert!(config.validate().is_ok());
+        config.server.ws_path = "/".to_string();
+        assert!(config.validate().is_err());
+        config.server.ws_path = "mem3/ws".to_string();
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,355 @@
+//! export
+//! The finished games from the game log as csv or json, for the records of the school.
+//! The same export is on the command line `mem3_server export` and on the route `/mem3export`.
+//! The games can be filtered by the date of the end of the game and by nickname.
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use mem3_common::GameResult;
+use std::fmt;
+use std::str::FromStr;
+
+///seconds in one day
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+///the columns of the csv in this order
+const CSV_HEADER: &str = "player1_nickname,player2_nickname,deck,board_size,winner,\
+player1_points,player2_points,flips,mismatches,duration_ms,started_at,ended_at,solo";
+
+///the format of the export
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    ///one game per line with a header line
+    Csv,
+    ///an array of objects with the same fields as the csv columns
+    Json,
+}
+
+impl ExportFormat {
+    ///the content type for the http response
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+    ///the extension of the downloaded file
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ExportError;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(ExportError::BadFormat(text.to_string())),
+        }
+    }
+}
+
+///why the export cannot be made
+#[derive(Debug)]
+pub enum ExportError {
+    ///only csv and json
+    BadFormat(String),
+    ///the date is not `YYYY-MM-DD`
+    BadDate(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportError::BadFormat(format) => {
+                write!(f, "bad export format {}, use csv or json", format)
+            }
+            ExportError::BadDate(date) => write!(f, "bad date {}, use YYYY-MM-DD", date),
+        }
+    }
+}
+
+///the parameters of the route `/mem3export`. Every parameter is optional.
+#[derive(Deserialize, Default)]
+pub struct ExportQuery {
+    ///csv or json, default csv
+    pub format: Option<String>,
+    ///the first day, `YYYY-MM-DD`
+    pub from: Option<String>,
+    ///the last day, `YYYY-MM-DD`
+    pub to: Option<String>,
+    ///the games of this player
+    pub nickname: Option<String>,
+}
+
+///which games are exported
+pub struct ExportFilter {
+    ///the games that ended on this day in UTC or later. Seconds since the unix epoch.
+    from: Option<u64>,
+    ///the games that ended before the end of this day in UTC. Seconds since the unix epoch.
+    to_exclusive: Option<u64>,
+    ///the games where one player has this nickname. Not case sensitive.
+    nickname: Option<String>,
+}
+
+impl ExportFilter {
+    ///the filter from the dates `YYYY-MM-DD` and the nickname. Both days are included.
+    pub fn new(
+        from: Option<&str>,
+        to: Option<&str>,
+        nickname: Option<&str>,
+    ) -> Result<Self, ExportError> {
+        Ok(ExportFilter {
+            from: from.map(parse_date).transpose()?,
+            to_exclusive: to
+                .map(|to| parse_date(to).map(|day| day + SECONDS_PER_DAY))
+                .transpose()?,
+            nickname: nickname
+                .filter(|nickname| !nickname.is_empty())
+                .map(str::to_lowercase),
+        })
+    }
+
+    ///true if the game is in the date range and has the nickname
+    fn matches(&self, game_result: &GameResult) -> bool {
+        self.from.map_or(true, |from| game_result.ended_at >= from)
+            && self
+                .to_exclusive
+                .map_or(true, |to_exclusive| game_result.ended_at < to_exclusive)
+            && self.nickname.as_ref().map_or(true, |nickname| {
+                game_result.player1_nickname.to_lowercase() == *nickname
+                    || game_result.player2_nickname.to_lowercase() == *nickname
+            })
+    }
+}
+
+///the parsed query of the route
+pub fn from_query(query: &ExportQuery) -> Result<(ExportFormat, ExportFilter), ExportError> {
+    let format = match &query.format {
+        Some(format) => format.parse()?,
+        None => ExportFormat::Csv,
+    };
+    let filter = ExportFilter::new(
+        query.from.as_ref().map(String::as_str),
+        query.to.as_ref().map(String::as_str),
+        query.nickname.as_ref().map(String::as_str),
+    )?;
+    Ok((format, filter))
+}
+
+///one exported game
+#[derive(Serialize)]
+struct ExportRow<'a> {
+    ///nickname of the player that invited
+    player1_nickname: &'a str,
+    ///nickname of the player that accepted
+    player2_nickname: &'a str,
+    ///content folder name
+    deck: &'a str,
+    ///count of cards on the board
+    board_size: usize,
+    ///nickname of the winner. Empty for a draw.
+    winner: &'a str,
+    ///player1 points
+    player1_points: usize,
+    ///player2 points
+    player2_points: usize,
+    ///count of flipped cards of both players
+    flips: usize,
+    ///count of turns with two cards that don't match
+    mismatches: usize,
+    ///duration of the game in milliseconds
+    duration_ms: u64,
+    ///start of the game in UTC, `2019-05-20T10:15:00Z`
+    started_at: String,
+    ///end of the game in UTC
+    ended_at: String,
+    ///one player alone. Player2 and the winner are empty.
+    solo: bool,
+}
+
+impl<'a> ExportRow<'a> {
+    ///the row of the game. The results of older versions have no start and it stays empty.
+    fn new(game_result: &'a GameResult) -> Self {
+        ExportRow {
+            player1_nickname: &game_result.player1_nickname,
+            player2_nickname: &game_result.player2_nickname,
+            deck: &game_result.content_folder_name,
+            board_size: game_result.board_size,
+            winner: game_result.winner_nickname().unwrap_or(""),
+            player1_points: game_result.player1_points,
+            player2_points: game_result.player2_points,
+            flips: game_result.count_all_flips,
+            mismatches: game_result.mismatches(),
+            duration_ms: game_result.duration_ms,
+            started_at: format_timestamp(game_result.started_at),
+            ended_at: format_timestamp(game_result.ended_at),
+            solo: game_result.is_solo,
+        }
+    }
+
+    ///the values in the order of `CSV_HEADER`
+    fn to_csv_line(&self) -> String {
+        [
+            csv_field(self.player1_nickname),
+            csv_field(self.player2_nickname),
+            csv_field(self.deck),
+            self.board_size.to_string(),
+            csv_field(self.winner),
+            self.player1_points.to_string(),
+            self.player2_points.to_string(),
+            self.flips.to_string(),
+            self.mismatches.to_string(),
+            self.duration_ms.to_string(),
+            self.started_at.clone(),
+            self.ended_at.clone(),
+            self.solo.to_string(),
+        ]
+        .join(",")
+    }
+}
+
+///the filtered games in the format, the oldest first
+pub fn export(game_results: &[GameResult], filter: &ExportFilter, format: ExportFormat) -> String {
+    let rows: Vec<ExportRow> = game_results
+        .iter()
+        .filter(|game_result| filter.matches(game_result))
+        .map(ExportRow::new)
+        .collect();
+    match format {
+        ExportFormat::Csv => {
+            let mut csv = String::from(CSV_HEADER);
+            csv.push_str("\r\n");
+            for row in &rows {
+                csv.push_str(&row.to_csv_line());
+                csv.push_str("\r\n");
+            }
+            csv
+        }
+        ExportFormat::Json => {
+            serde_json::to_string_pretty(&rows).expect("serde_json::to_string(&rows)")
+        }
+    }
+}
+
+///`YYYY-MM-DD` as the seconds since the unix epoch at the start of the day in UTC
+fn parse_date(text: &str) -> Result<u64, ExportError> {
+    let unix_epoch = NaiveDate::from_ymd_opt(1970, 1, 1).expect("the unix epoch is a date");
+    NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .map(|date| date.signed_duration_since(unix_epoch).num_days())
+        .filter(|days| *days >= 0)
+        .map(|days| days as u64 * SECONDS_PER_DAY)
+        .ok_or_else(|| ExportError::BadDate(text.to_string()))
+}
+
+///seconds since the unix epoch as UTC time. Zero is an unknown time and stays empty.
+fn format_timestamp(seconds: u64) -> String {
+    if seconds == 0 {
+        return String::new();
+    }
+    Utc.timestamp_opt(seconds as i64, 0)
+        .single()
+        .map_or_else(String::new, |time| {
+            time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+        })
+}
+
+///nicknames can have commas and quotes. Such a field is quoted and the quotes are doubled.
+///A spreadsheet runs a field that starts with `= + - @` as a formula,
+///so it gets a leading `'` and stays text.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(|c| c == '=' || c == '+' || c == '-' || c == '@') {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///a game of Ana and Bor that ended on 2019-05-20 at 10:15:00 UTC
+    fn game_result(player1_nickname: &str, ended_at: u64) -> GameResult {
+        GameResult {
+            content_folder_name: "alphabet".to_string(),
+            board_size: 16,
+            player1_nickname: player1_nickname.to_string(),
+            player2_nickname: "Bor".to_string(),
+            player1_points: 5,
+            player2_points: 3,
+            count_all_flips: 20,
+            duration_ms: 60_000,
+            started_at: ended_at - 60,
+            ended_at,
+            is_solo: false,
+        }
+    }
+
+    ///2019-05-20T00:00:00Z
+    const MAY_20: u64 = 1_558_310_400;
+
+    #[test]
+    fn parse_date_is_the_start_of_the_day() {
+        assert_eq!(parse_date("1970-01-01").expect("the epoch"), 0);
+        assert_eq!(parse_date("2019-05-20").expect("a date"), MAY_20);
+        for bad in &["2019-5-40", "20.05.2019", "", "1969-12-31"] {
+            assert!(parse_date(bad).is_err(), "{} is not a date", bad);
+        }
+    }
+
+    #[test]
+    fn filter_includes_both_days() {
+        let filter =
+            ExportFilter::new(Some("2019-05-20"), Some("2019-05-20"), None).expect("a filter");
+        assert!(!filter.matches(&game_result("Ana", MAY_20 - 1)));
+        assert!(filter.matches(&game_result("Ana", MAY_20)));
+        assert!(filter.matches(&game_result("Ana", MAY_20 + SECONDS_PER_DAY - 1)));
+        assert!(!filter.matches(&game_result("Ana", MAY_20 + SECONDS_PER_DAY)));
+    }
+
+    #[test]
+    fn filter_nickname_is_not_case_sensitive() {
+        let filter = ExportFilter::new(None, None, Some("ana")).expect("a filter");
+        assert!(filter.matches(&game_result("Ana", MAY_20)));
+        assert!(!filter.matches(&game_result("Eve", MAY_20)));
+        let filter = ExportFilter::new(None, None, Some("")).expect("a filter");
+        assert!(filter.matches(&game_result("Eve", MAY_20)));
+    }
+
+    #[test]
+    fn csv_field_is_quoted_and_formula_is_text() {
+        assert_eq!(csv_field("Ana"), "Ana");
+        assert_eq!(csv_field("Ana, Bor"), "\"Ana, Bor\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("-2"), "'-2");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("+1,2"), "\"'+1,2\"");
+    }
+
+    #[test]
+    fn csv_has_the_start_of_the_game() {
+        let mut old_result = game_result("Ana", MAY_20 + 3600);
+        old_result.started_at = 0;
+        let game_results = [game_result("=Ana", MAY_20 + 3600), old_result];
+        let filter = ExportFilter::new(None, None, None).expect("a filter");
+        let csv = export(&game_results, &filter, ExportFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            "'=Ana,Bor,alphabet,16,'=Ana,5,3,20,2,60000,2019-05-20T00:59:00Z,2019-05-20T01:00:00Z,false"
+        );
+        assert!(lines[2].ends_with(",60000,,2019-05-20T01:00:00Z,false"));
+    }
+}
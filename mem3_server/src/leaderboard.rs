@@ -0,0 +1,199 @@
+//! leaderboard
+//! Finished games are appended to a local file, one json per line.
+//! On start the server reads all the lines again.
+//! The leaderboards are calculated from these game results.
+
+use mem3_common::{GameResult, Leaderboard, LeaderboardRow};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::{BufReader, Result};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+///default file name of the game log in the current directory
+pub const GAME_LOG_FILENAME: &str = "mem3_game_results.jsonl";
+///only the best players are sent to the client
+const LEADERBOARD_ROWS: usize = 10;
+
+///the game log is shared between all websocket connections
+pub type SharedGameLog = Arc<Mutex<GameLog>>;
+
+///append-only log of finished games
+pub struct GameLog {
+    ///file with one json per line
+    path: PathBuf,
+    ///all results read from the file and appended later
+    results: Vec<GameResult>,
+}
+
+impl GameLog {
+    ///read the existing file. A missing file is an empty log.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let mut results = Vec::new();
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)?);
+            for line in reader.lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<GameResult>(&line) {
+                    Ok(game_result) => results.push(game_result),
+                    Err(err) => warn!("skip bad line in {}: {}", path.display(), err),
+                }
+            }
+        }
+        info!("game log {} has {} results", path.display(), results.len());
+        Ok(GameLog { path, results })
+    }
+
+    ///the log without the results of the file, when the file is off or cannot be read
+    pub fn empty(path: PathBuf) -> Self {
+        GameLog {
+            path,
+            results: Vec::new(),
+        }
+    }
+
+    ///appends the result to the file. The times are already written by the server.
+    pub fn append(&mut self, game_result: GameResult) -> Result<()> {
+        let line = serde_json::to_string(&game_result)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", line)?;
+        self.results.push(game_result);
+        Ok(())
+    }
+
+    ///all the results, the oldest first
+    pub fn results(&self) -> &[GameResult] {
+        &self.results
+    }
+
+    ///leaderboard for one deck and board size
+    pub fn leaderboard(&self, content_folder_name: &str, board_size: usize) -> Leaderboard {
+        let mut wins: HashMap<&str, u64> = HashMap::new();
+        let mut best_solo_time: HashMap<&str, u64> = HashMap::new();
+        let mut fewest_flips: HashMap<&str, u64> = HashMap::new();
+
+        for game_result in self.results.iter().filter(|game_result| {
+            game_result.content_folder_name == content_folder_name
+                && game_result.board_size == board_size
+        }) {
+            //a solo game counts only for the time
+            if game_result.is_solo {
+                let time = best_solo_time
+                    .entry(&game_result.player1_nickname)
+                    .or_insert(game_result.duration_ms);
+                *time = (*time).min(game_result.duration_ms);
+            }
+            //a draw has no winner and counts for nobody
+            if let Some(winner) = game_result.winner_nickname() {
+                *wins.entry(winner).or_insert(0) += 1;
+                let flips = game_result.count_all_flips as u64;
+                let best_flips = fewest_flips.entry(winner).or_insert(flips);
+                *best_flips = (*best_flips).min(flips);
+            }
+        }
+
+        Leaderboard {
+            content_folder_name: content_folder_name.to_string(),
+            board_size,
+            most_wins: sorted_rows(wins, true),
+            best_solo_time: sorted_rows(best_solo_time, false),
+            fewest_flips: sorted_rows(fewest_flips, false),
+        }
+    }
+}
+
+///the best rows first: descending for wins, ascending for time and flips
+fn sorted_rows(map: HashMap<&str, u64>, is_descending: bool) -> Vec<LeaderboardRow> {
+    let mut rows: Vec<LeaderboardRow> = map
+        .into_iter()
+        .map(|(nickname, value)| LeaderboardRow {
+            nickname: nickname.to_string(),
+            value,
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        if is_descending {
+            b.value
+                .cmp(&a.value)
+                .then_with(|| a.nickname.cmp(&b.nickname))
+        } else {
+            a.value
+                .cmp(&b.value)
+                .then_with(|| a.nickname.cmp(&b.nickname))
+        }
+    });
+    rows.truncate(LEADERBOARD_ROWS);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    ///Ana wins against Bor in a minute
+    fn game_result() -> GameResult {
+        GameResult {
+            content_folder_name: "alphabet".to_string(),
+            board_size: 16,
+            player1_nickname: "Ana".to_string(),
+            player2_nickname: "Bor".to_string(),
+            player1_points: 5,
+            player2_points: 3,
+            count_all_flips: 20,
+            duration_ms: 60_000,
+            started_at: 1_558_347_240,
+            ended_at: 1_558_347_300,
+            is_solo: false,
+        }
+    }
+
+    ///Bor clears the board alone
+    fn solo_result(duration_ms: u64) -> GameResult {
+        GameResult {
+            player1_nickname: "Bor".to_string(),
+            player2_nickname: String::new(),
+            player1_points: 8,
+            player2_points: 0,
+            duration_ms,
+            is_solo: true,
+            ..game_result()
+        }
+    }
+
+    #[test]
+    fn solo_game_counts_only_for_the_best_solo_time() {
+        let game_log = GameLog {
+            path: PathBuf::new(),
+            results: vec![game_result(), solo_result(50_000), solo_result(40_000)],
+        };
+        let leaderboard = game_log.leaderboard("alphabet", 16);
+        assert_eq!(leaderboard.most_wins.len(), 1);
+        assert_eq!(leaderboard.most_wins[0].nickname, "Ana");
+        assert_eq!(leaderboard.fewest_flips.len(), 1);
+        assert_eq!(leaderboard.best_solo_time.len(), 1);
+        assert_eq!(leaderboard.best_solo_time[0].nickname, "Bor");
+        assert_eq!(leaderboard.best_solo_time[0].value, 40_000);
+    }
+
+    #[test]
+    fn appended_result_keeps_the_end_of_the_game() {
+        let path = env::temp_dir().join(format!("mem3_game_log_{}.jsonl", std::process::id()));
+        let _missing = fs::remove_file(&path);
+        let mut game_log = GameLog::open(path.clone()).expect("no file is an empty log");
+        game_log.append(game_result()).expect("write the game log");
+        let game_log = GameLog::open(path.clone()).expect("read the game log");
+        let _removed = fs::remove_file(&path);
+        assert_eq!(game_log.results().len(), 1);
+        assert_eq!(game_log.results()[0].started_at, 1_558_347_240);
+        assert_eq!(game_log.results()[0].ended_at, 1_558_347_300);
+    }
+}
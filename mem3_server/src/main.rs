@@ -33,36 +33,68 @@
 
 //region: extern and use statements
 extern crate ansi_term;
+extern crate chrono;
 extern crate clap;
 extern crate env_logger;
 extern crate futures;
+extern crate get_if_addrs;
 #[macro_use]
 extern crate log;
 extern crate mem3_common;
-extern crate regex;
+extern crate pem;
+extern crate rand;
+extern crate ring;
 extern crate serde;
+#[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate tokio;
+extern crate tokio_signal;
+extern crate toml;
+extern crate untrusted;
 extern crate warp;
+extern crate yasna;
+
+mod benchmark;
+mod config;
+mod export;
+mod leaderboard;
+mod outgoingqueue;
+mod ratings;
+mod requesterror;
+mod sessions;
+mod shutdown;
+mod tls;
+mod users;
 
-use clap::{App, Arg};
+use crate::config::{Config, CONFIG_FILENAME};
+use crate::export::{ExportFilter, ExportFormat, ExportQuery};
+use crate::leaderboard::{GameLog, SharedGameLog};
+use crate::outgoingqueue::{OutgoingQueue, QueueError};
+use crate::ratings::{Ratings, SharedRatings, INITIAL_RATING};
+use crate::requesterror::{
+    check_content_filename, check_content_folder_name, lock, other_ws_uid_of, parse_message,
+    RequestError,
+};
+use crate::sessions::{now_secs, RunningGame, Sessions, SharedSessions, SoloGame};
+use crate::users::{Profile, User, UserMap};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use env_logger::Env;
-use futures::sync::mpsc;
-use futures::{Future, Stream};
-use mem3_common::WsMessage;
-use regex::Regex;
+use futures::sync::oneshot;
+use futures::{Future, Sink, Stream};
+use mem3_common::{GameResult, PlayerInfo, WsMessage, PROTOCOL_VERSION};
 use std::collections::HashMap;
-use std::convert::TryFrom;
-use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
-use std::net::SocketAddr;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::process::Command;
+use std::net::{IpAddr, Ipv4Addr};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Arc, Mutex,
 };
+use std::time::{Duration, Instant};
+use tokio::timer::{Delay, Interval};
 use warp::ws::{Message, WebSocket};
 use warp::Filter;
 //endregion
@@ -73,11 +105,331 @@ static NEXT_USER_ID: AtomicUsize = AtomicUsize::new(1);
 
 /// Our state of currently connected users.
 /// - Key is their id
-/// - Value is a sender of `warp::ws::Message`
-type Users = Arc<Mutex<HashMap<usize, mpsc::UnboundedSender<Message>>>>;
+/// - Value is the user with a sender of `warp::ws::Message`
+/// The map is sharded, every shard has its own lock.
+type Users = Arc<UserMap>;
+
+/// Invitations that are waiting for an answer.
+/// - Key is the ws uid of the player that invited
+/// - Value is the invitation
+type Invitations = Arc<Mutex<HashMap<usize, Invitation>>>;
+
+/// One invitation waits for `AcceptPlay` or `DeclinePlay` until it expires.
+struct Invitation {
+    ///invited player unique id. Zero means the invitation is for anybody.
+    other_ws_uid: usize,
+    ///the deck of the game
+    content_folder_name: String,
+    ///when the invitation was sent. Used to recognize the invitation in the timer.
+    created: Instant,
+}
 
 //endregion
 
+///the command line. Every flag can be also an environment variable.
+///Without both the config file is used.
+fn app() -> App<'static, 'static> {
+    App::new("mem3_server")
+        .version("1.0.0")
+        .author("bestia.dev")
+        .about("server http and websocket for mem3 game")
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("file")
+                .env("MEM3_CONFIG")
+                .help("toml config file, default mem3_server.toml if it exists"),
+        )
+        .arg(
+            Arg::with_name("print_config")
+                .long("print-config")
+                .help("print the config as toml and exit"),
+        )
+        .arg(
+            Arg::with_name("prm_ip")
+                .value_name("ip")
+                .env("MEM3_IP")
+                .use_delimiter(true)
+                .help("ip addresses for listening, separated by comma. IPv4 or IPv6."),
+        )
+        .arg(
+            Arg::with_name("prm_port")
+                .value_name("port")
+                .env("MEM3_PORT")
+                .help("port for listening"),
+        )
+        .arg(
+            Arg::with_name("dual_stack")
+                .long("dual-stack")
+                .value_name("true|false")
+                .env("MEM3_DUAL_STACK")
+                .help("listen on all IPv4 and IPv6 addresses"),
+        )
+        .arg(
+            Arg::with_name("auto_ip")
+                .long("auto-ip")
+                .help("listen also on the LAN address of this computer"),
+        )
+        .arg(
+            Arg::with_name("ws_path")
+                .long("ws-path")
+                .value_name("path")
+                .env("MEM3_WS_PATH")
+                .help("route of the websocket"),
+        )
+        .arg(
+            Arg::with_name("log_level")
+                .long("log-level")
+                .value_name("level")
+                .env("MEM3_LOG_LEVEL")
+                .help("error, warn, info, debug or trace"),
+        )
+        .arg(
+            Arg::with_name("static_root")
+                .long("static-root")
+                .value_name("folder")
+                .env("MEM3_STATIC_ROOT")
+                .help("folder with the files of the wasm client"),
+        )
+        .arg(
+            Arg::with_name("content_dir")
+                .long("content-dir")
+                .value_name("folder")
+                .env("MEM3_CONTENT_DIR")
+                .help("folder with the decks"),
+        )
+        .arg(
+            Arg::with_name("game_log_file")
+                .long("game-log-file")
+                .value_name("file")
+                .env("MEM3_GAME_LOG_FILE")
+                .help("file with the finished games"),
+        )
+        .arg(
+            Arg::with_name("ratings_file")
+                .long("ratings-file")
+                .value_name("file")
+                .env("MEM3_RATINGS_FILE")
+                .help("file with the ratings"),
+        )
+        .arg(
+            Arg::with_name("prm_queue_size")
+                .long("queue-size")
+                .value_name("messages")
+                .env("MEM3_QUEUE_SIZE")
+                .help("outgoing messages waiting for one client. A slower client is disconnected."),
+        )
+        .arg(
+            Arg::with_name("max_messages_per_second")
+                .long("max-messages-per-second")
+                .value_name("count")
+                .env("MEM3_MAX_MESSAGES_PER_SECOND")
+                .help("more messages from one client are answered with an error"),
+        )
+        .arg(
+            Arg::with_name("invitation_timeout")
+                .long("invitation-timeout")
+                .value_name("seconds")
+                .env("MEM3_INVITATION_TIMEOUT")
+                .help("the invitation expires after this time"),
+        )
+        .arg(
+            Arg::with_name("heartbeat_interval")
+                .long("heartbeat-interval")
+                .value_name("seconds")
+                .env("MEM3_HEARTBEAT_INTERVAL")
+                .help("interval of the Ping to all clients"),
+        )
+        .arg(
+            Arg::with_name("idle_timeout")
+                .long("idle-timeout")
+                .value_name("seconds")
+                .env("MEM3_IDLE_TIMEOUT")
+                .help("a connection without messages is closed after this time"),
+        )
+        .arg(
+            Arg::with_name("shards")
+                .long("shards")
+                .value_name("count")
+                .env("MEM3_SHARDS")
+                .help("count of locks for the connected users"),
+        )
+        .arg(
+            Arg::with_name("game_log")
+                .long("game-log")
+                .value_name("true|false")
+                .env("MEM3_GAME_LOG")
+                .help("write the finished games for the leaderboard"),
+        )
+        .arg(
+            Arg::with_name("ratings")
+                .long("ratings")
+                .value_name("true|false")
+                .env("MEM3_RATINGS")
+                .help("update the ratings after every game"),
+        )
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .value_name("true|false")
+                .env("MEM3_STATS")
+                .help("the route /mem3stats with the depth of the queues, default false"),
+        )
+        .arg(
+            Arg::with_name("export")
+                .long("export")
+                .value_name("true|false")
+                .env("MEM3_EXPORT")
+                .help("the route /mem3export with the finished games as csv or json, default false"),
+        )
+        .arg(
+            Arg::with_name("tls")
+                .long("tls")
+                .value_name("true|false")
+                .env("MEM3_TLS")
+                .help("https and wss instead of http and ws"),
+        )
+        .arg(
+            Arg::with_name("tls_cert")
+                .long("tls-cert")
+                .value_name("file")
+                .env("MEM3_TLS_CERT")
+                .help("certificate in a pem file"),
+        )
+        .arg(
+            Arg::with_name("tls_key")
+                .long("tls-key")
+                .value_name("file")
+                .env("MEM3_TLS_KEY")
+                .help("private key in a pem file"),
+        )
+        .arg(
+            Arg::with_name("restart_eta")
+                .long("restart-eta")
+                .value_name("seconds")
+                .env("MEM3_RESTART_ETA")
+                .help("on shutdown the clients are told when the server is back"),
+        )
+        .arg(
+            Arg::with_name("flush_timeout")
+                .long("flush-timeout")
+                .value_name("seconds")
+                .env("MEM3_FLUSH_TIMEOUT")
+                .help("on shutdown the longest wait for the outgoing queues"),
+        )
+        .arg(
+            Arg::with_name("persistence")
+                .long("persistence")
+                .value_name("true|false")
+                .env("MEM3_PERSISTENCE")
+                .help("write the sessions and the players of the running games to the snapshot file and read it on start"),
+        )
+        .arg(
+            Arg::with_name("snapshot_file")
+                .long("snapshot-file")
+                .value_name("file")
+                .env("MEM3_SNAPSHOT_FILE")
+                .help("file with the sessions and the players of the running games"),
+        )
+        .arg(
+            Arg::with_name("snapshot_interval")
+                .long("snapshot-interval")
+                .value_name("seconds")
+                .env("MEM3_SNAPSHOT_INTERVAL")
+                .help("interval of writing the snapshot file"),
+        )
+        .arg(
+            Arg::with_name("session_timeout")
+                .long("session-timeout")
+                .value_name("seconds")
+                .env("MEM3_SESSION_TIMEOUT")
+                .help("a session without a reconnect is removed after this time"),
+        )
+        .subcommand(
+            SubCommand::with_name("benchmark")
+                .about("messages per second of many games with one lock and with sharded locks")
+                .arg(
+                    Arg::with_name("games")
+                        .long("games")
+                        .value_name("count")
+                        .validator(is_count)
+                        .default_value("1000")
+                        .help("games that are played at the same time"),
+                )
+                .arg(
+                    Arg::with_name("moves")
+                        .long("moves")
+                        .value_name("count")
+                        .validator(is_count)
+                        .default_value("200")
+                        .help("moves in every game"),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .long("threads")
+                        .value_name("count")
+                        .validator(is_count)
+                        .default_value("8")
+                        .help("threads that play the games"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("generate-self-signed")
+                .about("write a self-signed certificate and key to the tls files of the config")
+                .arg(
+                    Arg::with_name("names")
+                        .long("name")
+                        .value_name("host or ip")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("name in the url of the game, default localhost and the LAN address"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("write the finished games from the game log as csv or json")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("csv|json")
+                        .default_value("csv")
+                        .help("format of the export"),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .long("from")
+                        .value_name("YYYY-MM-DD")
+                        .help("the games that ended on this day in UTC or later"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("YYYY-MM-DD")
+                        .help("the games that ended on this day in UTC or earlier"),
+                )
+                .arg(
+                    Arg::with_name("nickname")
+                        .long("nickname")
+                        .value_name("nickname")
+                        .help("the games of this player"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("file")
+                        .help("write to the file instead of stdout"),
+                ),
+        )
+}
+
+///the counts of the benchmark are positive numbers
+fn is_count(count: String) -> Result<(), String> {
+    match count.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(()),
+        _ => Err(format!("{} is not a positive number", count)),
+    }
+}
+
 ///main function of the binary
 fn main() {
     //region: ansi terminal color output (for log also)
@@ -100,157 +452,480 @@ fn main() {
     */
     //endregion
 
+    //region: cmdline parameters
+    let matches = app().get_matches();
+
+    //defaults, then the file, then the environment and the command line
+    let config_path = matches.value_of("config");
+    let mut config = Config::from_file(
+        Path::new(config_path.unwrap_or(CONFIG_FILENAME)),
+        config_path.is_some(),
+    )
+    .and_then(|mut config| config.apply_matches(&matches).map(|()| config))
+    .and_then(|config| config.validate().map(|()| config))
+    .unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1)
+    });
+    if matches.is_present("print_config") {
+        print!("{}", config.to_toml());
+        return;
+    }
+    //endregion
+
     //region: env_logger log text to stdout depend on ENV variable
     //in Linux : RUST_LOG=info ./mem3_server.exe
     //in Windows I don't know yet.
-    //default for env variable info
-    let mut builder = env_logger::from_env(Env::default().default_filter_or("info"));
+    //RUST_LOG has precedence over the log level of the config
+    let mut builder =
+        env_logger::from_env(Env::default().default_filter_or(config.server.log_level.as_str()));
     //nanoseconds in the logger
     builder.default_format_timestamp_nanos(true);
     builder.init();
     //endregion
 
-    //region: cmdline parameters
-    //TODO: is this a clear case for shadowing? The same value in different types
-    //default ip and port
-    //let df_local_ip = local_ip_get().expect("cannot get local ip");
-    let df_local_ip = "127.0.0.22";
-    let df_local_port = 80;
-    //string representation of defaults
-    let prm_ip = df_local_ip.to_string();
-    let prm_port = df_local_port.to_string();
-
-    let matches = App::new("mem3_server")
-        .version("1.0.0")
-        .author("bestia.dev")
-        .about("server http and websocket for mem3 game")
-        .arg(
-            Arg::with_name("prm_ip")
-                .value_name("ip")
-                .default_value(&prm_ip)
-                .help("ip address for listening"),
-        )
-        .arg(
-            Arg::with_name("prm_port")
-                .value_name("port")
-                .default_value(&prm_port)
-                .help("port for listening"),
-        )
-        .get_matches();
+    if let Some(matches) = matches.subcommand_matches("generate-self-signed") {
+        let names: Vec<String> = match matches.values_of("names") {
+            Some(names) => names.map(str::to_string).collect(),
+            None => {
+                let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+                names.extend(local_ip_get().map(|ip| ip.to_string()));
+                names
+            }
+        };
+        match tls::generate_self_signed(&names, &config.tls.cert, &config.tls.key) {
+            Ok(()) => info!(
+                "certificate for {:?} written to {} and {}. Start the server with --tls true.",
+                names,
+                config.tls.cert.display(),
+                config.tls.key.display()
+            ),
+            Err(err) => {
+                error!("cannot write the certificate: {}", err);
+                std::process::exit(1)
+            }
+        }
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("export") {
+        if let Err(err) = export_game_log(matches, &config) {
+            eprintln!("{}", err);
+            std::process::exit(1)
+        }
+        return;
+    }
+
+    if let Some(matches) = matches.subcommand_matches("benchmark") {
+        //the validator of clap checked the counts
+        let count_of = |name: &str| {
+            matches
+                .value_of(name)
+                .expect("error on benchmark parameter")
+                .parse::<usize>()
+                .expect("not a number")
+        };
+        benchmark::run(count_of("games"), count_of("moves"), count_of("threads"));
+        return;
+    }
 
     //from string parameters to strong types
-    let fnl_prm_ip = matches.value_of("prm_ip").expect("error on prm_ip");
-    let fnl_prm_port = matches.value_of("prm_port").expect("error on prm_port");
-    let local_ip = IpAddr::V4(fnl_prm_ip.parse::<Ipv4Addr>().expect("not an ip address"));
-    let local_port = u16::from_str_radix(fnl_prm_port, 10).expect("not a number");
-    let local_addr = SocketAddr::new(local_ip, local_port);
-
-    info!(
-        "mem3 http server listening on {} and websocket on /mem3ws/",
-        ansi_term::Colour::Red.paint(local_addr.to_string())
-    );
-    //endregion
+    let lan_ip = if config.server.auto_ip {
+        local_ip_get()
+    } else {
+        None
+    };
+    let listen_addrs = config.listen_addrs(lan_ip).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1)
+    });
+    config.server.ws_path = config.server.ws_path.trim_matches('/').to_string();
+    let config = Arc::new(config);
+
+    // The sessions of the last run wait for the reconnect of their players.
+    // The new ws uids must not collide with the ws uids of the sessions.
+    let sessions = if config.persistence.enabled {
+        let (sessions, next_ws_uid) =
+            Sessions::open(&config.persistence.file).unwrap_or_else(|err| {
+                error!(
+                    "cannot read the snapshot {}: {}",
+                    config.persistence.file.display(),
+                    err
+                );
+                std::process::exit(1)
+            });
+        NEXT_USER_ID.store(next_ws_uid.max(1), Ordering::Relaxed);
+        sessions
+    } else {
+        Sessions::default()
+    };
+    let sessions: SharedSessions = Arc::new(Mutex::new(sessions));
+    // The sessions are pruned and written to the snapshot file in the interval.
+    let snapshot = snapshot_task(Arc::clone(&sessions), &config);
+    // On shutdown the last snapshot is written.
+    let sessions_for_shutdown = Arc::clone(&sessions);
+    let sessions = warp::any().map(move || Arc::clone(&sessions));
 
     // Keep track of all connected users, key is usize, value
     // is a websocket sender.
-    let users = Arc::new(Mutex::new(HashMap::new()));
+    let users: Users = Arc::new(UserMap::new(config.limits.shards));
+    // The heartbeat pings all users and reaps the idle ones.
+    let heartbeat = heartbeat_task(Arc::clone(&users), &config);
+    // The depth of the outgoing queues for sizing the server.
+    let users_for_stats = Arc::clone(&users);
+    // On shutdown all users are informed.
+    let users_for_shutdown = Arc::clone(&users);
     // Turn our "state" into a new Filter...
     //let users = warp::any().map(move || users.clone());
     //Clippy recommands this crazyness instead of just users.clone()
-    let users = warp::any().map(move || {
-        Arc::<
-            std::sync::Mutex<
-                std::collections::HashMap<
-                    usize,
-                    futures::sync::mpsc::UnboundedSender<warp::ws::Message>,
-                >,
-            >,
-        >::clone(&users)
+    let users = warp::any().map(move || Arc::<UserMap>::clone(&users));
+    // The same for the invitations that wait for an answer.
+    let invitations: Invitations = Arc::new(Mutex::new(HashMap::new()));
+    let invitations = warp::any().map(move || Arc::clone(&invitations));
+    // The finished games are stored in a file for the leaderboard.
+    // Without the game log the file is not read and not created.
+    // A file that cannot be read does not stop the server, the same as the ratings.
+    let game_log = if config.features.game_log {
+        GameLog::open(config.paths.game_log.clone()).unwrap_or_else(|err| {
+            let broken_path = config.paths.game_log.with_extension("broken");
+            warn!(
+                "cannot read the game log {}: {}. The game log starts empty, the old file is {}.",
+                config.paths.game_log.display(),
+                err,
+                broken_path.display()
+            );
+            if let Err(err) = fs::rename(&config.paths.game_log, &broken_path) {
+                warn!("cannot rename the game log: {}", err);
+            }
+            GameLog::empty(config.paths.game_log.clone())
+        })
+    } else {
+        GameLog::empty(config.paths.game_log.clone())
+    };
+    let game_log: SharedGameLog = Arc::new(Mutex::new(game_log));
+    // The export reads the same game log.
+    let game_log_for_export = Arc::clone(&game_log);
+    let game_log = warp::any().map(move || Arc::clone(&game_log));
+    // The ratings of all nicknames survive the restart of the server.
+    // A broken file does not stop the server. It is kept for the admin and the ratings start again.
+    let ratings = Ratings::open(config.paths.ratings.clone()).unwrap_or_else(|err| {
+        let broken_path = config.paths.ratings.with_extension("broken");
+        warn!(
+            "cannot read the ratings {}: {}. The ratings start empty, the old file is {}.",
+            config.paths.ratings.display(),
+            err,
+            broken_path.display()
+        );
+        if let Err(err) = fs::rename(&config.paths.ratings, &broken_path) {
+            warn!("cannot rename the ratings: {}", err);
+        }
+        Ratings::empty(config.paths.ratings.clone())
     });
+    let ratings: SharedRatings = Arc::new(Mutex::new(ratings));
+    let ratings = warp::any().map(move || Arc::clone(&ratings));
+    let config2 = Arc::clone(&config);
+    let config3 = Arc::clone(&config);
+    let config = warp::any().map(move || Arc::clone(&config3));
 
     //websocket server
     // GET from route /mem3ws/ -> websocket upgrade
-    let websocket = warp::path("mem3ws")
+    //warp::path needs a static str. The config lives as long as the server.
+    let ws_path: &'static str = Box::leak(config2.server.ws_path.clone().into_boxed_str());
+    let websocket = warp::path(ws_path)
         // The `ws2()` filter will prepare Websocket handshake...
         .and(warp::ws2())
         .and(users)
-        .map(|ws: warp::ws::Ws2, users| {
-            // This will call our function if the handshake succeeds.
-            ws.on_upgrade(move |socket| user_connected(socket, users))
+        .and(invitations)
+        .and(game_log)
+        .and(ratings)
+        .and(sessions)
+        .and(config)
+        .map(
+            move |ws: warp::ws::Ws2, users, invitations, game_log, ratings, sessions, config| {
+                // This will call our function if the handshake succeeds.
+                ws.on_upgrade(move |socket| {
+                    user_connected(
+                        socket,
+                        users,
+                        invitations,
+                        game_log,
+                        ratings,
+                        sessions,
+                        config,
+                    )
+                })
+            },
+        );
+
+    // GET /mem3stats -> json with the depth of the outgoing queues
+    let is_stats_enabled = config2.features.stats;
+    let stats = warp::path("mem3stats")
+        .and(warp::any().map(move || Arc::clone(&users_for_stats)))
+        .and_then(move |users: Users| {
+            if is_stats_enabled {
+                Ok(warp::reply::with_header(
+                    queue_stats_json(&users),
+                    "content-type",
+                    "application/json",
+                ))
+            } else {
+                Err(warp::reject::not_found())
+            }
+        });
+
+    // GET /mem3export?format=csv&from=2019-05-01&to=2019-05-31&nickname=Ana
+    // -> the finished games as csv or json. All the parameters are optional.
+    let is_export_enabled = config2.features.export;
+    let game_log_for_export = warp::any().map(move || Arc::clone(&game_log_for_export));
+    //without a query string the query filter rejects, so the default query is a second route
+    let export_with_query = warp::path("mem3export")
+        .and(warp::path::end())
+        .and(warp::query::<ExportQuery>())
+        .and(game_log_for_export.clone())
+        .and_then(move |query, game_log| export_reply(is_export_enabled, &query, &game_log));
+    let export_without_query = warp::path("mem3export")
+        .and(warp::path::end())
+        .and(game_log_for_export)
+        .and_then(move |game_log| {
+            export_reply(is_export_enabled, &ExportQuery::default(), &game_log)
         });
+    let export = export_with_query.or(export_without_query);
 
     //static file server
-    // GET files of route / -> are from folder /mem3/
-    let fileserver = warp::fs::dir("./mem3/");
+    // GET files of route / -> are from the static root folder
+    // GET files of route /content/ -> are from the content folder
+    let content = warp::path("content").and(warp::fs::dir(config2.paths.content_dir.clone()));
+    let fileserver = content.or(warp::fs::dir(config2.paths.static_root.clone()));
 
-    let routes = fileserver.or(websocket);
-    warp::serve(routes).run(local_addr);
+    let routes = fileserver.or(websocket).or(stats).or(export);
+    if config2.tls.enabled && !(config2.tls.cert.exists() && config2.tls.key.exists()) {
+        error!(
+            "tls needs {} and {}. Create them with `mem3_server generate-self-signed`.",
+            config2.tls.cert.display(),
+            config2.tls.key.display()
+        );
+        std::process::exit(1)
+    }
+    // One server for every address. They share the routes and the state.
+    // On shutdown they stop accepting, the open connections continue until the exit.
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+    let stop_rx = stop_rx.shared();
+    let mut servers = Vec::new();
+    for listen_addr in listen_addrs {
+        let bound = if config2.tls.enabled {
+            let tls_server = warp::serve(routes.clone()).tls(&config2.tls.cert, &config2.tls.key);
+            try_bind_tls(move || tls_server.bind_ephemeral(listen_addr)).map(
+                |(bound_addr, server)| {
+                    let server: Box<dyn Future<Item = (), Error = ()> + Send> = Box::new(server);
+                    (bound_addr, server)
+                },
+            )
+        } else {
+            warp::serve(routes.clone())
+                .try_bind_ephemeral(listen_addr)
+                .map(|(bound_addr, server)| {
+                    let server: Box<dyn Future<Item = (), Error = ()> + Send> = Box::new(server);
+                    (bound_addr, server)
+                })
+                .map_err(|err| err.to_string())
+        };
+        match bound {
+            Ok((bound_addr, server)) => {
+                info!(
+                    "mem3 {} server listening on {} and websocket on /{}/",
+                    if config2.tls.enabled { "https" } else { "http" },
+                    ansi_term::Colour::Red.paint(bound_addr.to_string()),
+                    config2.server.ws_path
+                );
+                servers.push(
+                    server
+                        .select(stop_rx.clone().then(|_stop| Ok(())))
+                        .then(|_select| Ok(())),
+                );
+            }
+            // On Linux the IPv6 socket of the dual stack takes also IPv4
+            Err(err)
+                if config2.server.dual_stack
+                    && listen_addr.ip() == IpAddr::V4(Ipv4Addr::UNSPECIFIED) =>
+            {
+                info!("IPv4 is already served by the IPv6 socket: {}", err)
+            }
+            Err(err) => {
+                error!("cannot listen on {}: {}", listen_addr, err);
+                std::process::exit(1)
+            }
+        }
+    }
+    let restart_eta_secs = config2.restart_eta_secs();
+    let flush_timeout = config2.flush_timeout();
+    let snapshot_file = snapshot_file_of(&config2);
+    tokio::run(futures::lazy(move || {
+        tokio::spawn(heartbeat);
+        tokio::spawn(snapshot);
+        for server in servers {
+            tokio::spawn(server);
+        }
+        tokio::spawn(
+            shutdown::signal()
+                .and_then(move |()| {
+                    info!("signal received, the server stops accepting connections");
+                    let _already_stopped = stop_tx.send(());
+                    shutdown::notify_and_flush(&users_for_shutdown, restart_eta_secs, flush_timeout)
+                })
+                .map(move |()| {
+                    if let Some(snapshot_file) = snapshot_file {
+                        save_snapshot(&lock(&sessions_for_shutdown), &snapshot_file);
+                    }
+                    info!("mem3 server stopped");
+                    std::process::exit(0)
+                }),
+        );
+        Ok(())
+    }));
 }
 
 //region: websocket callbacks: connect, msg, disconnect
 ///new user connects
-fn user_connected(ws: WebSocket, users: Users) -> impl Future<Item = (), Error = ()> {
+fn user_connected(
+    ws: WebSocket,
+    users: Users,
+    invitations: Invitations,
+    game_log: SharedGameLog,
+    ratings: SharedRatings,
+    sessions: SharedSessions,
+    config: Arc<Config>,
+) -> impl Future<Item = (), Error = ()> {
     // Use a counter to assign a new unique ID for this user.
-    let my_id = NEXT_USER_ID.fetch_add(1, Ordering::Relaxed);
+    // A resumed session changes it to the ws uid of the session.
+    let my_id = Arc::new(AtomicUsize::new(
+        NEXT_USER_ID.fetch_add(1, Ordering::Relaxed),
+    ));
 
-    info!("new websocket user: {}", my_id);
+    info!("new websocket user: {}", my_id.load(Ordering::Relaxed));
 
     // Split the socket into a sender and receive of messages.
     let (user_ws_tx, user_ws_rx) = ws.split();
 
-    // Use an unbounded channel to handle buffering and flushing of messages
+    // Use a bounded queue to handle buffering and flushing of messages
     // to the websocket...
-    let (tx, rx) = mpsc::unbounded();
+    let (queue, rx, closed) = OutgoingQueue::new(config.limits.queue_size);
     warp::spawn(
-        rx.map_err(|()| -> warp::Error { unreachable!("unbounded rx never errors") })
-            .forward(user_ws_tx)
+        rx.forward(user_ws_tx.sink_map_err(|ws_err| info!("websocket send error: {}", ws_err)))
             .map(|_tx_rx| ())
-            .map_err(|ws_err| info!("websocket send error: {}", ws_err)),
+            // a closed queue stops the task also if the client does not read
+            .select(closed)
+            .map(|_select| ())
+            .map_err(|_select| ()),
     );
 
     // Save the sender in our list of connected users.
-    users.lock().expect("error uses.lock()").insert(my_id, tx);
+    // The nickname and avatar come later with the handshake.
+    // The connection keeps its own handle and does not look itself up in the map.
+    let me = users.insert(
+        my_id.load(Ordering::Relaxed),
+        User::new(queue, INITIAL_RATING as u32),
+    );
+    //all the players must know about the new player
+    broadcast_player_list(&users);
 
     // Return a `Future` that is basically a state machine managing
     // this specific user's connection.
     // Make an extra clone to give to our disconnection handler...
     //let users2 = users.clone();
     //Clippy reccomands this crazyness insted of users.clone()
-    let users2 = Arc::<
-        std::sync::Mutex<
-            std::collections::HashMap<
-                usize,
-                futures::sync::mpsc::UnboundedSender<warp::ws::Message>,
-            >,
-        >,
-    >::clone(&users);
+    let users2 = Arc::<UserMap>::clone(&users);
+    let invitations2 = Arc::clone(&invitations);
+    let sessions2 = Arc::clone(&sessions);
 
+    let me2 = Arc::clone(&me);
+    let me3 = Arc::clone(&me);
+    let users3 = Arc::clone(&users);
+    let my_id2 = Arc::clone(&my_id);
+    let my_id3 = Arc::clone(&my_id);
+    let my_id4 = Arc::clone(&my_id);
     user_ws_rx
+        // A user removed by the heartbeat or replaced by a reconnect
+        // is disconnected on the next message.
+        // Only the shard of this user is locked.
+        .take_while(move |_msg| {
+            Ok(!me2.queue.is_closed()
+                && users3
+                    .get(my_id2.load(Ordering::Relaxed))
+                    .map_or(false, |user| Arc::ptr_eq(&user, &me2)))
+        })
         // Every time the user sends a message, broadcast it to
         // all other users...
         .for_each(move |msg| {
-            user_message(my_id, &msg, &users);
+            //any message, also a pong, proves that the connection is alive
+            me.touch();
+            //a bad message is answered with an error, the connection continues
+            let result = if me.is_rate_limited(config.limits.max_messages_per_second) {
+                Err(RequestError::RateLimited)
+            } else {
+                user_message(
+                    &my_id,
+                    &me,
+                    &msg,
+                    &users,
+                    &invitations,
+                    &game_log,
+                    &ratings,
+                    &sessions,
+                    &config,
+                )
+            };
+            if let Err(err) = result {
+                let ws_uid = my_id.load(Ordering::Relaxed);
+                warn!("message from {}: {}", ws_uid, err);
+                send_to_user(
+                    &users,
+                    ws_uid,
+                    &err.to_json(msg.to_str().unwrap_or_default()),
+                );
+            }
             Ok(())
         })
         // for_each will keep processing as long as the user stays
         // connected. Once they disconnect, then...
         .then(move |result| {
-            user_disconnected(my_id, &users2);
+            user_disconnected(
+                my_id3.load(Ordering::Relaxed),
+                &me3,
+                &users2,
+                &invitations2,
+                &sessions2,
+            );
             result
         })
         // If at any time, there was a websocket error, log here...
         .map_err(move |e| {
-            info!("websocket error(uid={}): {}", my_id, e);
+            info!(
+                "websocket error(uid={}): {}",
+                my_id4.load(Ordering::Relaxed),
+                e
+            );
         })
 }
 
 ///on receive websocket message
-fn user_message(ws_uid_of_message: usize, messg: &Message, users: &Users) {
+#[allow(clippy::too_many_arguments)]
+fn user_message(
+    my_id: &AtomicUsize,
+    me: &Arc<User>,
+    messg: &Message,
+    users: &Users,
+    invitations: &Invitations,
+    game_log: &SharedGameLog,
+    ratings: &SharedRatings,
+    sessions: &SharedSessions,
+    config: &Config,
+) -> Result<(), RequestError> {
+    let ws_uid_of_message = my_id.load(Ordering::Relaxed);
     // Skip any non-Text messages...
     let msg = if let Ok(s) = messg.to_str() {
         s
     } else {
-        return;
+        return Ok(());
     };
 
     let new_msg = msg.to_string();
@@ -258,196 +933,771 @@ fn user_message(ws_uid_of_message: usize, messg: &Message, users: &Users) {
 
     //There are different messages coming from wasm
     //ConnectionTest returns a message YourWebSocketUid
-    //WantToPlay must be broadcasted to all users
+    //RequestPlayerList returns the list of all connected users
+    //WantToPlay must be broadcasted to all users or sent only to the invited player
+    //AcceptPlay and DeclinePlay answer the invitation and are forwarded to the inviter
     //RequestSpelling must return a message ResponseSpellingJson to the same user
+    //EndGame is stored in the game log for the leaderboard and updates the ratings
+    //StartSoloGame and EndSoloGame time the solo game for the leaderboard
+    //RequestRatings must return a message ResponseRatings to the same user
+    //RequestLeaderboard must return a message ResponseLeaderboard to the same user
     //all others must be forwarded to exactly the other player.
 
-    let msg: WsMessage = serde_json::from_str(&new_msg).unwrap_or_else(|_x| WsMessage::Dummy {
-        dummy: String::from("error"),
-    });
+    let msg = parse_message(&new_msg)?;
 
     match msg {
         WsMessage::Dummy { dummy } => info!("Dummy: {}", dummy),
-        WsMessage::RequestWsUid { test } => {
-            info!("RequestWsUid: {}", test);
-            let j = serde_json::to_string(&WsMessage::ResponseWsUid { your_ws_uid: ws_uid_of_message })
-                .expect("serde_json::to_string(&WsMessage::ResponseWsUid { your_ws_uid: ws_uid_of_message })");
-            info!("send ResponseWsUid: {}", j);
-            match users
-                .lock()
-                .expect("error users.lock()")
-                .get(&ws_uid_of_message)
-                .unwrap()
-                .unbounded_send(Message::text(j))
-            {
-                Ok(()) => (),
-                Err(_disconnected) => {}
+        WsMessage::RequestWsUid {
+            nickname,
+            avatar,
+            version,
+            session_token,
+        } => {
+            info!("RequestWsUid: {} {} {}", nickname, avatar, version);
+            if version != PROTOCOL_VERSION {
+                return Err(RequestError::VersionMismatch(version));
             }
+            //a known session token brings back the ws uid of the game
+            let (my_ws_uid, session_token, is_reconnect) =
+                resume_session(my_id, session_token, users, sessions);
+            //the same nickname can play in two tabs, but it is confusing for the other players
+            let is_nickname_online = users
+                .snapshot()
+                .iter()
+                .any(|(ws_uid, user)| *ws_uid != my_ws_uid && user.profile().nickname == nickname);
+            if is_nickname_online {
+                let j = serde_json::to_string(&WsMessage::Notice {
+                    message: format!("The nickname {} is already online.", nickname),
+                })
+                .expect("serde_json::to_string(&WsMessage::Notice)");
+                send_to_user(users, my_ws_uid, &j);
+            }
+            lock(&sessions).connected(&session_token, &nickname, &avatar);
+            let rating = lock(&ratings).rating(&nickname);
+            me.set_profile(Profile {
+                nickname,
+                avatar,
+                rating,
+            });
+            //the other players must see the new nickname
+            broadcast_player_list(users);
+            let j = serde_json::to_string(&WsMessage::ResponseWsUid {
+                your_ws_uid: my_ws_uid,
+                session_token,
+                is_reconnect,
+            })
+            .expect("serde_json::to_string(&WsMessage::ResponseWsUid)");
+            info!("send ResponseWsUid: {}", j);
+            send_to_user(users, my_ws_uid, &j);
         }
         WsMessage::RequestSpelling { filename } => {
             info!("RequestSpelling: {}", filename);
-            // read the file
-            let mut pathbuf = env::current_dir().expect("env::current_dir()");
-            pathbuf.push("mem3");
-            pathbuf.push(filename);
-            let filename =
-                String::from(pathbuf.as_path().to_str().expect("path.as_path().to_str()"));
-            info!("filename: {}", filename);
-            let mut file = File::open(filename).expect("Unable to open the file");
+            check_content_filename(&filename)?;
+            // read the file from the content folder
+            let mut pathbuf = config.paths.content_dir.clone();
+            pathbuf.push(filename.trim_start_matches("content/"));
+            info!("filename: {}", pathbuf.display());
             let mut contents = String::new();
-            file.read_to_string(&mut contents)
-                .expect("Unable to read the file");
+            File::open(&pathbuf)
+                .and_then(|mut file| file.read_to_string(&mut contents))
+                .map_err(|_| RequestError::BadFile(filename))?;
             info!("read file : {}", contents);
             let j = serde_json::to_string(&WsMessage::ResponseSpellingJson { json: contents })
                 .expect(
                     "serde_json::to_string(&WsMessage::ResponseSpellingJson { json: contents })",
                 );
             info!("send ResponseSpellingJson: {}", j);
-            match users
-                .lock()
-                .expect("error users.lock()")
-                .get(&ws_uid_of_message)
-                .unwrap()
-                .unbounded_send(Message::text(j))
-            {
-                Ok(()) => (),
-                Err(_disconnected) => {}
+            send_to_user(users, ws_uid_of_message, &j);
+        }
+        WsMessage::RequestLeaderboard {
+            content_folder_name,
+            board_size,
+        } => {
+            info!("RequestLeaderboard: {} {}", content_folder_name, board_size);
+            let leaderboard = lock(&game_log).leaderboard(&content_folder_name, board_size);
+            let j = serde_json::to_string(&WsMessage::ResponseLeaderboard { leaderboard })
+                .expect("serde_json::to_string(&WsMessage::ResponseLeaderboard)");
+            send_to_user(users, ws_uid_of_message, &j);
+        }
+        WsMessage::RequestRatings { my_ws_uid } => {
+            info!("RequestRatings: {}", my_ws_uid);
+            let ratings = lock(&ratings).all();
+            let j = serde_json::to_string(&WsMessage::ResponseRatings { ratings })
+                .expect("serde_json::to_string(&WsMessage::ResponseRatings)");
+            send_to_user(users, ws_uid_of_message, &j);
+        }
+        WsMessage::EndGame {
+            other_ws_uid,
+            game_result,
+            ..
+        } => {
+            //only a running game has a result. The players and the deck are known on the server.
+            let running_game = lock(&sessions)
+                .game_of(ws_uid_of_message)
+                .cloned()
+                .ok_or(RequestError::NotInGame)?;
+            let game_result = checked_game_result(
+                &lock(&sessions),
+                &running_game,
+                ws_uid_of_message,
+                other_ws_uid,
+                game_result,
+            )?;
+            //the result counts only if the other player gets it too
+            send_to_other_player(users, &new_msg)?;
+            lock(&sessions).end_game(ws_uid_of_message);
+            if config.features.ratings {
+                update_ratings(users, ratings, &game_result);
+            }
+            if config.features.game_log {
+                if let Err(err) = lock(&game_log).append(game_result) {
+                    error!("cannot write the game log: {}", err);
+                }
+            }
+            //the lobby shows the new ratings
+            broadcast_player_list(users);
+        }
+        WsMessage::StartSoloGame {
+            content_folder_name,
+            ..
+        } => {
+            check_content_folder_name(&config.paths.content_dir, &content_folder_name)?;
+            lock(&sessions).start_solo_game(ws_uid_of_message, &content_folder_name);
+        }
+        WsMessage::EndSoloGame { game_result, .. } => {
+            //the server knows the start and the deck of the solo game
+            let solo_game = lock(&sessions)
+                .solo_game_of(ws_uid_of_message)
+                .cloned()
+                .ok_or(RequestError::NotInGame)?;
+            let game_result =
+                checked_solo_result(&lock(&sessions), &solo_game, ws_uid_of_message, game_result)?;
+            lock(&sessions).end_game(ws_uid_of_message);
+            //there is no opponent and no rating
+            if config.features.game_log {
+                if let Err(err) = lock(&game_log).append(game_result) {
+                    error!("cannot write the game log: {}", err);
+                }
+            }
+        }
+        WsMessage::RequestPlayerList { my_ws_uid } => {
+            info!("RequestPlayerList: {}", my_ws_uid);
+            let j = player_list_json(users);
+            send_to_user(users, ws_uid_of_message, &j);
+        }
+        WsMessage::WantToPlay {
+            other_ws_uid,
+            content_folder_name,
+            ..
+        } => {
+            check_content_folder_name(&config.paths.content_dir, &content_folder_name)?;
+            //the invitation is stored before the send, because the answer can come at once
+            let created = Instant::now();
+            lock(&invitations).insert(
+                ws_uid_of_message,
+                Invitation {
+                    other_ws_uid,
+                    content_folder_name,
+                    created,
+                },
+            );
+            if other_ws_uid == 0 {
+                broadcast(users, ws_uid_of_message, &new_msg);
+            } else if let Err(err) = send_to_other_player(users, &new_msg) {
+                //an invitation for a player that is gone is an error for the inviter
+                lock(&invitations).remove(&ws_uid_of_message);
+                return Err(err);
+            }
+            spawn_invitation_timer(
+                ws_uid_of_message,
+                created,
+                config.invitation_timeout(),
+                users,
+                invitations,
+            );
+        }
+        WsMessage::AcceptPlay { other_ws_uid, .. } => {
+            let invitation =
+                accepted_invitation(&mut lock(&invitations), other_ws_uid, ws_uid_of_message);
+            if let Some(invitation) = invitation {
+                lock(&sessions).start_game(
+                    other_ws_uid,
+                    ws_uid_of_message,
+                    &invitation.content_folder_name,
+                );
+                send_to_other_player(users, &new_msg)?;
+            } else {
+                info!("AcceptPlay without invitation from: {}", other_ws_uid);
+                let j = serde_json::to_string(&WsMessage::InvitationExpired {
+                    inviter_ws_uid: other_ws_uid,
+                })
+                .expect("serde_json::to_string(&WsMessage::InvitationExpired)");
+                send_to_user(users, ws_uid_of_message, &j);
+            }
+        }
+        WsMessage::DeclinePlay { other_ws_uid, .. } => {
+            //only the invited player can cancel a private invitation
+            let mut invitations = lock(&invitations);
+            let is_private = invitations.get(&other_ws_uid).map_or(false, |invitation| {
+                invitation.other_ws_uid == ws_uid_of_message
+            });
+            if is_private {
+                invitations.remove(&other_ws_uid);
             }
+            send_to_other_player(users, &new_msg)?;
         }
-        WsMessage::WantToPlay { .. } => broadcast(users, ws_uid_of_message, &new_msg),
         mem3_common::WsMessage::ResponseWsUid { .. }
         | mem3_common::WsMessage::ResponseSpellingJson { .. }
-        | mem3_common::WsMessage::AcceptPlay { .. }
-        | mem3_common::WsMessage::PlayerClick { .. }
-        | mem3_common::WsMessage::EndGame { .. }
-        | mem3_common::WsMessage::PlayerChange { .. } => send_to_other_player(users, &new_msg),
+        | mem3_common::WsMessage::ResponsePlayerList { .. }
+        | mem3_common::WsMessage::InvitationExpired { .. }
+        | mem3_common::WsMessage::ResponseLeaderboard { .. }
+        | mem3_common::WsMessage::ResponseRatings { .. }
+        | mem3_common::WsMessage::PlayerPause { .. }
+        | mem3_common::WsMessage::PlayerResume { .. }
+        | mem3_common::WsMessage::Desync { .. }
+        | mem3_common::WsMessage::RequestResync { .. } => send_to_other_player(users, &new_msg)?,
+        WsMessage::PlayerClick { .. } => {
+            if !lock(&sessions).is_move_on_turn(ws_uid_of_message, false) {
+                return Err(RequestError::NotYourTurn);
+            }
+            send_to_other_player(users, &new_msg)?;
+        }
+        WsMessage::PlayerChange { .. } => {
+            if !lock(&sessions).is_move_on_turn(ws_uid_of_message, true) {
+                return Err(RequestError::NotYourTurn);
+            }
+            send_to_other_player(users, &new_msg)?;
+        }
+        WsMessage::ResponseResync { .. } => {
+            lock(&sessions).forget_turn(ws_uid_of_message);
+            send_to_other_player(users, &new_msg)?;
+        }
+        //the heartbeat: last_seen is already updated
+        WsMessage::Pong {} => {}
+        WsMessage::Ping {} => {
+            let j = serde_json::to_string(&WsMessage::Pong {})
+                .expect("serde_json::to_string(&WsMessage::Pong)");
+            send_to_user(users, ws_uid_of_message, &j);
+        }
+        //only the server sends errors and notices
+        mem3_common::WsMessage::Error { message, .. }
+        | mem3_common::WsMessage::Notice { message } => info!("from client: {}", message),
+        mem3_common::WsMessage::ServerShuttingDown { .. } => info!("from client: shutdown"),
     }
+    Ok(())
 }
-///New message from this user send only to the other player.
-fn send_to_other_player(users: &Users, new_msg: &str) {
-    //info!("send_to_other_player: {}", new_msg);
-    //the other user is in the other_ws_uid field
-
-    //serde_json knows: 1. map (key+value pair) and 2. array
-    let json_map: serde_json::Map<String, serde_json::Value> =
-        serde_json::from_str(new_msg).expect("error serde_json::from_str(new_msg)");
-    //info!("js_other_ws_uid: {}", v);
-    let tuple = json_map
+///json with the count of connections and the depth of their outgoing queues
+fn queue_stats_json(users: &Users) -> String {
+    let users = users.snapshot();
+    let depths: Vec<usize> = users.iter().map(|(_, user)| user.queue.depth()).collect();
+    let max_depth_ever = users
         .iter()
-        .next()
-        .expect("error json_map.iter().next()");
-
-    let object = tuple.1;
-    info!("object: {}", object);
-
-    let js_other_ws_uid = object
-        .get("other_ws_uid")
-        .expect("error object.get(other_ws_uid)");
-    info!("js_other_ws_uid: {}", js_other_ws_uid);
+        .map(|(_, user)| user.queue.max_depth())
+        .max()
+        .unwrap_or(0);
+    format!(
+        r#"{{"connections":{},"total_depth":{},"max_depth":{},"max_depth_ever":{}}}"#,
+        depths.len(),
+        depths.iter().sum::<usize>(),
+        depths.iter().max().unwrap_or(&0),
+        max_depth_ever
+    )
+}
 
-    let i64_other_ws_uid = js_other_ws_uid.as_i64().expect("js_other_ws_uid.as_i64()");
-    info!("i64_other_ws_uid: {}", i64_other_ws_uid);
-    let other_ws_uid =
-        usize::try_from(i64_other_ws_uid).expect("usize::try_from(i64_other_ws_uid)");
+///the subcommand `export` writes the finished games to stdout or to the file
+fn export_game_log(matches: &ArgMatches, config: &Config) -> Result<(), String> {
+    let format: ExportFormat = matches
+        .value_of("format")
+        .unwrap_or("csv")
+        .parse()
+        .map_err(|err: export::ExportError| err.to_string())?;
+    let filter = ExportFilter::new(
+        matches.value_of("from"),
+        matches.value_of("to"),
+        matches.value_of("nickname"),
+    )
+    .map_err(|err| err.to_string())?;
+    let game_log = GameLog::open(config.paths.game_log.clone()).map_err(|err| {
+        format!(
+            "cannot read the game log {}: {}",
+            config.paths.game_log.display(),
+            err
+        )
+    })?;
+    let text = export::export(game_log.results(), &filter, format);
+    match matches.value_of("output") {
+        Some(output) => {
+            fs::write(output, text).map_err(|err| format!("cannot write {}: {}", output, err))
+        }
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
 
-    match users
-        .lock()
-        .expect("error users.lock()")
-        .get(&other_ws_uid)
-        .unwrap()
-        .unbounded_send(Message::text(String::from(new_msg)))
-    {
-        Ok(()) => (),
-        Err(_disconnected) => {}
+///the response of the route `/mem3export`. A bad parameter is 400 Bad Request.
+fn export_reply(
+    is_export_enabled: bool,
+    query: &ExportQuery,
+    game_log: &SharedGameLog,
+) -> Result<warp::http::Response<String>, warp::Rejection> {
+    if !is_export_enabled {
+        return Err(warp::reject::not_found());
+    }
+    let mut builder = warp::http::Response::builder();
+    let response = match export::from_query(query) {
+        Ok((format, filter)) => {
+            let body = export::export(lock(game_log).results(), &filter, format);
+            builder
+                .header("content-type", format.content_type())
+                .header(
+                    "content-disposition",
+                    format!("attachment; filename=\"mem3_games.{}\"", format.extension()),
+                )
+                .body(body)
+        }
+        Err(err) => builder
+            .status(warp::http::StatusCode::BAD_REQUEST)
+            .header("content-type", "text/plain; charset=utf-8")
+            .body(err.to_string()),
+    };
+    Ok(response.expect("error building the export response"))
+}
+///New message from this user send only to the other player.
+fn send_to_other_player(users: &Users, new_msg: &str) -> Result<(), RequestError> {
+    //the other user is in the other_ws_uid field
+    let other_ws_uid = other_ws_uid_of(new_msg)?;
+    match users.get(other_ws_uid) {
+        Some(user) => enqueue(other_ws_uid, &user, Message::text(String::from(new_msg)))
+            .map_err(|_queue_error| RequestError::PlayerGone(other_ws_uid)),
+        None => Err(RequestError::PlayerGone(other_ws_uid)),
+    }
+}
+///put the message in the queue of the user.
+///A full queue means a slow client: the queue is closed and the heartbeat removes the user.
+fn enqueue(ws_uid: usize, user: &User, msg: Message) -> Result<(), QueueError> {
+    let result = user.queue.send(msg);
+    if let Err(QueueError::Full) = result {
+        warn!(
+            "client {} does not read, {} messages in the queue. Disconnected.",
+            ws_uid,
+            user.queue.depth()
+        );
+        user.queue.close();
+    }
+    result
+}
+///send the message only to one user
+fn send_to_user(users: &Users, ws_uid: usize, new_msg: &str) {
+    if let Some(user) = users.get(ws_uid) {
+        let _queue_error = enqueue(ws_uid, &user, Message::text(String::from(new_msg)));
     }
 }
 ///broadcast is the simplest
 fn broadcast(users: &Users, ws_uid_of_message: usize, new_msg: &str) {
     // New message from this user, send it to everyone else (except same uid)...
-    // We use `retain` instead of a for loop so that we can reap any user that
-    // appears to have disconnected.
     info!("broadcast: {}", new_msg);
-    for (&uid, tx) in users.lock().expect("error users.lock()").iter() {
+    // No lock is held while the messages are queued.
+    for (uid, user) in users.snapshot() {
         if ws_uid_of_message != uid {
-            match tx.unbounded_send(Message::text(String::from(new_msg))) {
-                Ok(()) => (),
-                Err(_disconnected) => {
-                    // The tx is disconnected, our `user_disconnected` code
-                    // should be happening in another task, nothing more to
-                    // do here.
-                }
-            }
+            // A disconnected user is removed in `user_disconnected` in another task
+            // and a slow user in the heartbeat, nothing more to do here.
+            let _queue_error = enqueue(uid, &user, Message::text(String::from(new_msg)));
         }
     }
 }
 
 ///disconnect user
-fn user_disconnected(my_id: usize, users: &Users) {
+fn user_disconnected(
+    my_id: usize,
+    me: &Arc<User>,
+    users: &Users,
+    invitations: &Invitations,
+    sessions: &SharedSessions,
+) {
     info!("good bye user: {}", my_id);
 
     // Stream closed up, so remove from the user list
-    users.lock().expect("users.lock").remove(&my_id);
+    let _is_removed = users.remove_user(my_id, me);
+    // After a reconnect the ws uid belongs to the new connection.
+    if users.get(my_id).is_none() {
+        // The session waits for the reconnect until the session timeout.
+        lock(&sessions).disconnected(my_id);
+        // nobody can accept the invitations of a disconnected user
+        lock(&invitations).remove(&my_id);
+    }
+    broadcast_player_list(users);
 }
-//endregion
 
-//region: local ip (linux and windows distict versions)
-#[cfg(target_family = "unix")]
-///get local ip for unix with ifconfig
-pub fn local_ip_get() -> Option<IpAddr> {
-    info!("local_ip_get for unix{}", "");
-    let output = Command::new("ifconfig")
-        .output()
-        .expect("failed to execute `ifconfig`");
-
-    let stdout = String::from_utf8(output.stdout).unwrap();
-
-    let re = Regex::new(r#"(?m)^.*inet (addr:)?(([0-9]*\.){3}[0-9]*).*$"#).unwrap();
-    for cap in re.captures_iter(&stdout) {
-        let host = cap.get(2).map_or("", |m| m.as_str());
-        if host != "127.0.0.1" {
-            if let Ok(addr) = host.parse::<Ipv4Addr>() {
-                return Some(IpAddr::V4(addr));
-            }
-            if let Ok(addr) = host.parse::<Ipv6Addr>() {
-                return Some(IpAddr::V6(addr));
+///the ws uid for the session token. A known token moves this connection to the ws uid
+///of the session, an unknown token gets a new session.
+///Returns the ws uid, the session token and if the session is resumed on a new connection.
+fn resume_session(
+    my_id: &AtomicUsize,
+    session_token: String,
+    users: &Users,
+    sessions: &SharedSessions,
+) -> (usize, String, bool) {
+    let current_ws_uid = my_id.load(Ordering::Relaxed);
+    let session_ws_uid = lock(&sessions).ws_uid(&session_token);
+    match session_ws_uid {
+        Some(session_ws_uid) if session_ws_uid != current_ws_uid => {
+            info!(
+                "session of {} resumed by {}",
+                session_ws_uid, current_ws_uid
+            );
+            if let Some(replaced) = users.rekey(current_ws_uid, session_ws_uid) {
+                //the old connection of the session is still open, for example after a network change.
+                //The close frame ends it also on the client side.
+                replaced.queue.finish();
             }
+            my_id.store(session_ws_uid, Ordering::Relaxed);
+            (session_ws_uid, session_token, true)
         }
+        //the same connection sends the handshake again with a new nickname
+        Some(session_ws_uid) => (session_ws_uid, session_token, false),
+        None => (
+            current_ws_uid,
+            lock(&sessions).create(current_ws_uid),
+            false,
+        ),
     }
-    return None;
 }
+//endregion
 
-#[cfg(target_family = "windows")]
-///get local ip for windows with ipconfig
-pub fn local_ip_get() -> Option<IpAddr> {
-    info!("local_ip_get for windows{}", "");
-    let output = Command::new("ipconfig")
-        .output()
-        .expect("failed to execute `ipconfig`");
-
-    let stdout = String::from_utf8(output.stdout).expect("failed stdout");
-    //variables are block scope and will not interfere with the other block
-    {
-        let re =
-            Regex::new(r"(?m)^   IPv4 Address\. \. \. \. \. \. \. \. \. \. \. : ([0-9\.]*)\s*$")
-                .expect("failed regex");
-        let cap = re.captures(&stdout).expect("failed capture");
-        let host = cap.get(1).map_or("", |m| m.as_str());
-        if let Ok(addr) = host.parse::<Ipv4Addr>() {
-            return Some(IpAddr::V4(addr));
+//region: player list and invitations
+///json of the message with all connected players
+fn player_list_json(users: &Users) -> String {
+    let mut players: Vec<PlayerInfo> = users
+        .snapshot()
+        .into_iter()
+        .map(|(ws_uid, user)| {
+            let profile = user.profile();
+            PlayerInfo {
+                ws_uid,
+                nickname: profile.nickname,
+                avatar: profile.avatar,
+                rating: profile.rating,
+            }
+        })
+        .collect();
+    players.sort_by_key(|player| player.ws_uid);
+    serde_json::to_string(&WsMessage::ResponsePlayerList { players })
+        .expect("serde_json::to_string(&WsMessage::ResponsePlayerList)")
+}
+
+///new ratings after the game for the file and for the connected users
+fn update_ratings(users: &Users, ratings: &SharedRatings, game_result: &GameResult) {
+    let mut ratings = lock(&ratings);
+    if let Err(err) = ratings.update(game_result) {
+        error!("cannot write the ratings: {}", err);
+    }
+    for (_, user) in users.snapshot() {
+        let nickname = user.profile().nickname;
+        if nickname == game_result.player1_nickname || nickname == game_result.player2_nickname {
+            user.set_rating(ratings.rating(&nickname));
         }
     }
-    //variables are block scope and will not interfere with the other block
-    {
-        let re =
-            Regex::new(r"(?m)^   Link-local IPv6 Address \. \. \. \. \. : ([:%a-f0-9\.]*)\s*$")
-                .expect("failed regex");
-        let cap = re.captures(&stdout).expect("capture");
-        let host = cap.get(1).map_or("", |m| m.as_str());
-        if let Ok(addr) = host.parse::<Ipv6Addr>() {
-            return Some(IpAddr::V6(addr));
+}
+
+///the result of the running game for the game log and the ratings.
+///The nicknames and the deck come from the server. The scores from the client must fit the board.
+fn checked_game_result(
+    sessions: &Sessions,
+    running_game: &RunningGame,
+    ws_uid: usize,
+    other_ws_uid: usize,
+    client_result: GameResult,
+) -> Result<GameResult, RequestError> {
+    if running_game.other_ws_uid(ws_uid) != Some(other_ws_uid) {
+        return Err(RequestError::BadResult(format!(
+            "{} is not the other player",
+            other_ws_uid
+        )));
+    }
+    check_scores(&client_result)?;
+    let nickname_of = |ws_uid| sessions.nickname(ws_uid).unwrap_or_default().to_string();
+    let ended_at = now_secs();
+    //a game from the snapshot of an older version has no deck
+    let content_folder_name = if running_game.content_folder_name.is_empty() {
+        client_result.content_folder_name
+    } else {
+        running_game.content_folder_name.clone()
+    };
+    Ok(GameResult {
+        content_folder_name,
+        board_size: client_result.board_size,
+        player1_nickname: nickname_of(running_game.player1_ws_uid),
+        player2_nickname: nickname_of(running_game.player2_ws_uid),
+        player1_points: client_result.player1_points,
+        player2_points: client_result.player2_points,
+        count_all_flips: client_result.count_all_flips,
+        duration_ms: client_result
+            .duration_ms
+            .min(max_duration_ms(running_game.started_at, ended_at)),
+        started_at: running_game.started_at,
+        ended_at,
+        is_solo: false,
+    })
+}
+
+///the result of the solo game for the game log.
+///The nickname and the deck come from the server. The player alone finds all the pairs.
+fn checked_solo_result(
+    sessions: &Sessions,
+    solo_game: &SoloGame,
+    ws_uid: usize,
+    client_result: GameResult,
+) -> Result<GameResult, RequestError> {
+    check_scores(&client_result)?;
+    if client_result.player2_points != 0 {
+        return Err(RequestError::BadResult(format!(
+            "{} points of nobody in a solo game",
+            client_result.player2_points
+        )));
+    }
+    let ended_at = now_secs();
+    Ok(GameResult {
+        content_folder_name: solo_game.content_folder_name.clone(),
+        board_size: client_result.board_size,
+        player1_nickname: sessions.nickname(ws_uid).unwrap_or_default().to_string(),
+        player2_nickname: String::new(),
+        player1_points: client_result.player1_points,
+        player2_points: 0,
+        count_all_flips: client_result.count_all_flips,
+        duration_ms: client_result
+            .duration_ms
+            .min(max_duration_ms(solo_game.started_at, ended_at)),
+        started_at: solo_game.started_at,
+        ended_at,
+        is_solo: true,
+    })
+}
+
+///the board, the points and the flips from the client must fit together
+fn check_scores(client_result: &GameResult) -> Result<(), RequestError> {
+    let board_size = client_result.board_size;
+    if board_size == 0 || board_size % 2 != 0 {
+        return Err(RequestError::BadResult(format!(
+            "a board of {} cards",
+            board_size
+        )));
+    }
+    if client_result.player1_points + client_result.player2_points != board_size / 2 {
+        return Err(RequestError::BadResult(format!(
+            "{} and {} points on a board of {} cards",
+            client_result.player1_points, client_result.player2_points, board_size
+        )));
+    }
+    //every turn flips two cards and every card is flipped at least once
+    let count_all_flips = client_result.count_all_flips;
+    if count_all_flips < board_size || count_all_flips % 2 != 0 {
+        return Err(RequestError::BadResult(format!(
+            "{} flips on a board of {} cards",
+            count_all_flips, board_size
+        )));
+    }
+    Ok(())
+}
+
+///the game cannot be longer than on the server. One second for the rounding.
+fn max_duration_ms(started_at: u64, ended_at: u64) -> u64 {
+    (ended_at.saturating_sub(started_at) + 1) * 1000
+}
+
+///when a player connects or disconnects, all players receive the new list
+fn broadcast_player_list(users: &Users) {
+    let j = player_list_json(users);
+    //zero is never a ws uid, so nobody is excluded
+    broadcast(users, 0, &j);
+}
+
+///ping all users in the heartbeat interval
+fn heartbeat_task(users: Users, config: &Config) -> impl Future<Item = (), Error = ()> {
+    let idle_timeout = config.idle_timeout();
+    Interval::new_interval(config.heartbeat_interval())
+        .for_each(move |_instant| {
+            heartbeat(&users, idle_timeout);
+            Ok(())
+        })
+        .map_err(|timer_err| error!("heartbeat timer error: {}", timer_err))
+}
+
+///close the idle connections and ping the others
+fn heartbeat(users: &Users, idle_timeout: Duration) {
+    let ping = serde_json::to_string(&WsMessage::Ping {})
+        .expect("serde_json::to_string(&WsMessage::Ping)");
+    let now = Instant::now();
+    let mut is_any_reaped = false;
+    for (ws_uid, user) in users.snapshot() {
+        if user.idle_for(now) > idle_timeout || user.queue.is_closed() {
+            //the connection could be already gone in another task
+            //a reconnect could have replaced it already
+            if users.remove_user(ws_uid, &user) {
+                info!("idle connection closed: {}", ws_uid);
+                is_any_reaped = true;
+            }
+            //the close frame ends the connection also on the client side
+            user.queue.finish();
+        } else {
+            let _queue_error = enqueue(ws_uid, &user, Message::text(ping.as_str()));
         }
     }
-    None
+    if is_any_reaped {
+        broadcast_player_list(users);
+    }
+}
+
+///prune the sessions and write the snapshot file in the snapshot interval.
+///Without persistence there is no snapshot and the sessions are pruned in the heartbeat interval.
+fn snapshot_task(sessions: SharedSessions, config: &Config) -> impl Future<Item = (), Error = ()> {
+    let session_timeout = config.session_timeout();
+    let snapshot_file = snapshot_file_of(config);
+    let interval = if snapshot_file.is_some() {
+        config.snapshot_interval()
+    } else {
+        config.heartbeat_interval()
+    };
+    Interval::new_interval(interval)
+        .for_each(move |_instant| {
+            let mut sessions = lock(&sessions);
+            sessions.prune(session_timeout);
+            if let Some(snapshot_file) = &snapshot_file {
+                save_snapshot(&sessions, snapshot_file);
+            }
+            Ok(())
+        })
+        .map_err(|timer_err| error!("snapshot timer error: {}", timer_err))
+}
+
+///The tls server of warp has no try_bind and panics if the address is in use or the pem files are bad.
+///The panic is caught, so the address is bound only once and the error is logged like the others.
+fn try_bind_tls<T>(bind: impl FnOnce() -> T) -> Result<T, String> {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_panic_info| {}));
+    let bound = panic::catch_unwind(AssertUnwindSafe(bind));
+    panic::set_hook(default_hook);
+    bound.map_err(|panic_payload| {
+        panic_payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| {
+                panic_payload
+                    .downcast_ref::<&str>()
+                    .map(|text| text.to_string())
+            })
+            .unwrap_or_else(|| "cannot bind the tls server".to_string())
+    })
+}
+
+///the snapshot file, if the persistence is enabled
+fn snapshot_file_of(config: &Config) -> Option<PathBuf> {
+    if config.persistence.enabled {
+        Some(config.persistence.file.clone())
+    } else {
+        None
+    }
+}
+
+///write the sessions and the running games. An error is only logged, the games continue.
+fn save_snapshot(sessions: &Sessions, snapshot_file: &Path) {
+    if let Err(err) = sessions.save(snapshot_file, NEXT_USER_ID.load(Ordering::Relaxed)) {
+        error!(
+            "cannot write the snapshot {}: {}",
+            snapshot_file.display(),
+            err
+        );
+    }
+}
+
+///the invitation expires if nobody answered it in time
+fn spawn_invitation_timer(
+    inviter_ws_uid: usize,
+    created: Instant,
+    timeout: Duration,
+    users: &Users,
+    invitations: &Invitations,
+) {
+    let users = Arc::clone(users);
+    let invitations = Arc::clone(invitations);
+    warp::spawn(
+        Delay::new(created + timeout)
+            .map(move |()| invitation_expired(inviter_ws_uid, created, &users, &invitations))
+            .map_err(|timer_err| info!("invitation timer error: {}", timer_err)),
+    );
+}
+
+///the invitation of the inviter, if it is still valid and is for the player that accepts.
+///An invitation for somebody else stays, so nobody can cancel it with a wrong AcceptPlay.
+fn accepted_invitation(
+    invitations: &mut HashMap<usize, Invitation>,
+    inviter_ws_uid: usize,
+    ws_uid: usize,
+) -> Option<Invitation> {
+    let is_valid = invitations
+        .get(&inviter_ws_uid)
+        .map_or(false, |invitation| {
+            invitation.other_ws_uid == 0 || invitation.other_ws_uid == ws_uid
+        });
+    if is_valid {
+        invitations.remove(&inviter_ws_uid)
+    } else {
+        None
+    }
+}
+
+///remove the invitation if it is still the same and inform the players
+fn invitation_expired(
+    inviter_ws_uid: usize,
+    created: Instant,
+    users: &Users,
+    invitations: &Invitations,
+) {
+    let mut invitations = lock(&invitations);
+    //a newer invitation from the same player has its own timer
+    let other_ws_uid = match invitations.get(&inviter_ws_uid) {
+        Some(invitation) if invitation.created == created => invitation.other_ws_uid,
+        _ => return,
+    };
+    invitations.remove(&inviter_ws_uid);
+    info!("invitation expired: {}", inviter_ws_uid);
+
+    let j = serde_json::to_string(&WsMessage::InvitationExpired { inviter_ws_uid })
+        .expect("serde_json::to_string(&WsMessage::InvitationExpired)");
+    send_to_user(users, inviter_ws_uid, &j);
+    if other_ws_uid == 0 {
+        broadcast(users, inviter_ws_uid, &j);
+    } else {
+        send_to_user(users, other_ws_uid, &j);
+    }
+}
+//endregion
+
+//region: local ip
+///the LAN address of this computer from the network interfaces.
+///IPv4 first, because it is easier to type in the browser. No loopback and no link-local.
+pub fn local_ip_get() -> Option<IpAddr> {
+    let interfaces = match get_if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces,
+        Err(err) => {
+            error!("cannot read the network interfaces: {}", err);
+            return None;
+        }
+    };
+    let mut lan_ips: Vec<IpAddr> = interfaces
+        .iter()
+        .filter(|interface| !interface.is_loopback())
+        .map(get_if_addrs::Interface::ip)
+        .filter(|ip| match ip {
+            IpAddr::V4(ip) => !ip.is_link_local(),
+            //fe80::/10 needs the scope id in the url
+            IpAddr::V6(ip) => ip.segments()[0] & 0xffc0 != 0xfe80,
+        })
+        .collect();
+    lan_ips.sort_by_key(IpAddr::is_ipv6);
+    info!("LAN addresses: {:?}", lan_ips);
+    lan_ips.first().cloned()
 }
 //endregion
 
@@ -462,3 +1712,140 @@ pub fn enable_ansi_support() {
 pub fn enable_ansi_support() {
     //do nothing
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///Ana invited Bor to the alphabet. Ana is 1 and Bor is 2.
+    fn sessions_with_game() -> (Sessions, RunningGame) {
+        let mut sessions = Sessions::default();
+        let token_ana = sessions.create(1);
+        sessions.connected(&token_ana, "Ana", "1");
+        let token_bor = sessions.create(2);
+        sessions.connected(&token_bor, "Bor", "2");
+        sessions.start_game(1, 2, "alphabet");
+        let running_game = sessions.game_of(2).cloned().expect("the running game");
+        (sessions, running_game)
+    }
+
+    #[test]
+    fn solo_result_has_the_player_and_the_deck_of_the_server() {
+        let (mut sessions, _running_game) = sessions_with_game();
+        sessions.start_solo_game(2, "alphabet");
+        let solo_game = sessions.solo_game_of(2).cloned().expect("the solo game");
+        assert!(sessions.game_of(2).is_none());
+        let game_result = checked_solo_result(&sessions, &solo_game, 2, client_result(8, 0))
+            .expect("a valid result");
+        assert!(game_result.is_solo);
+        assert_eq!(game_result.player1_nickname, "Bor");
+        assert_eq!(game_result.player2_nickname, "");
+        assert_eq!(game_result.content_folder_name, "alphabet");
+        assert!(game_result.duration_ms <= 1000);
+        assert!(checked_solo_result(&sessions, &solo_game, 2, client_result(5, 3)).is_err());
+    }
+
+    #[test]
+    fn only_the_player_on_turn_clicks() {
+        let (mut sessions, _running_game) = sessions_with_game();
+        //Ana is player1 and starts
+        assert!(!sessions.is_move_on_turn(2, false));
+        assert!(sessions.is_move_on_turn(1, false));
+        assert!(!sessions.is_move_on_turn(1, true));
+        //Bor takes the turn and then Ana cannot click
+        assert!(sessions.is_move_on_turn(2, true));
+        assert!(!sessions.is_move_on_turn(1, false));
+        assert!(sessions.is_move_on_turn(2, false));
+        //after a resync the next move tells the turn
+        sessions.forget_turn(2);
+        assert!(sessions.is_move_on_turn(1, true));
+        assert!(!sessions.is_move_on_turn(2, false));
+        //a player without a game has no turn
+        assert!(sessions.is_move_on_turn(3, false));
+    }
+
+    #[test]
+    fn benchmark_counts_are_positive_numbers() {
+        assert!(is_count("8".to_string()).is_ok());
+        for bad in &["0", "-1", "many", ""] {
+            assert!(is_count(bad.to_string()).is_err(), "{} is not a count", bad);
+        }
+        assert!(app()
+            .get_matches_from_safe(vec!["mem3_server", "benchmark", "--games", "0"])
+            .is_err());
+    }
+
+    fn invitation_for(other_ws_uid: usize) -> Invitation {
+        Invitation {
+            other_ws_uid,
+            content_folder_name: "alphabet".to_string(),
+            created: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn wrong_accept_does_not_remove_the_invitation() {
+        let mut invitations = HashMap::new();
+        invitations.insert(1, invitation_for(2));
+        assert!(accepted_invitation(&mut invitations, 1, 3).is_none());
+        assert!(invitations.contains_key(&1));
+        assert!(accepted_invitation(&mut invitations, 1, 2).is_some());
+        assert!(invitations.is_empty());
+    }
+
+    #[test]
+    fn invitation_for_anybody_is_accepted_once() {
+        let mut invitations = HashMap::new();
+        invitations.insert(1, invitation_for(0));
+        assert!(accepted_invitation(&mut invitations, 1, 3).is_some());
+        assert!(accepted_invitation(&mut invitations, 1, 2).is_none());
+    }
+
+    ///the result as the client sends it
+    fn client_result(player1_points: usize, player2_points: usize) -> GameResult {
+        GameResult {
+            content_folder_name: "animals".to_string(),
+            board_size: 16,
+            player1_nickname: "Mallory".to_string(),
+            player2_nickname: "Eve".to_string(),
+            player1_points,
+            player2_points,
+            count_all_flips: 24,
+            duration_ms: 60_000,
+            started_at: 0,
+            ended_at: 0,
+            is_solo: false,
+        }
+    }
+
+    #[test]
+    fn game_result_has_the_players_and_the_deck_of_the_server() {
+        let (sessions, running_game) = sessions_with_game();
+        let game_result = checked_game_result(&sessions, &running_game, 2, 1, client_result(3, 5))
+            .expect("a valid result");
+        assert_eq!(game_result.player1_nickname, "Ana");
+        assert_eq!(game_result.player2_nickname, "Bor");
+        assert_eq!(game_result.content_folder_name, "alphabet");
+        assert_eq!(game_result.player2_points, 5);
+        //the game started just now, not a minute ago
+        assert!(game_result.duration_ms <= 2000);
+    }
+
+    #[test]
+    fn game_result_that_does_not_fit_is_rejected() {
+        let (sessions, running_game) = sessions_with_game();
+        let is_rejected = |other_ws_uid, game_result| {
+            checked_game_result(&sessions, &running_game, 1, other_ws_uid, game_result).is_err()
+        };
+        assert!(is_rejected(3, client_result(3, 5)));
+        assert!(is_rejected(2, client_result(8, 1)));
+        assert!(is_rejected(2, client_result(0, 0)));
+        let mut game_result = client_result(4, 4);
+        game_result.board_size = 15;
+        assert!(is_rejected(2, game_result));
+        let mut game_result = client_result(4, 4);
+        game_result.count_all_flips = 10;
+        assert!(is_rejected(2, game_result));
+        assert!(!is_rejected(2, client_result(4, 4)));
+    }
+}
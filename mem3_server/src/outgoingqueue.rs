@@ -0,0 +1,220 @@
+//! outgoing queue
+//! Every connection has a bounded queue of messages that wait for the socket.
+//! A client that does not read fast enough fills its queue and is disconnected.
+//! The depth of the queues is counted, so the capacity can be sized from real numbers.
+
+use crate::requesterror::lock;
+use futures::sync::{mpsc, oneshot};
+use futures::{future, Future, Stream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use warp::ws::Message;
+
+///default count of messages waiting for one client
+pub const DEFAULT_QUEUE_CAPACITY: usize = 256;
+
+///why the message was not queued
+#[derive(Debug, PartialEq)]
+pub enum QueueError {
+    ///the client does not read fast enough
+    Full,
+    ///the connection is gone or closed
+    Disconnected,
+}
+
+///the sending side of the queue of one connection
+pub struct OutgoingQueue {
+    ///the channel to the socket task. None ends the stream after the waiting messages.
+    tx: mpsc::UnboundedSender<Option<Message>>,
+    ///messages in the channel now
+    depth: Arc<AtomicUsize>,
+    ///the highest depth since the connection started
+    max_depth: AtomicUsize,
+    ///true after close
+    closed: AtomicBool,
+    ///true after finish
+    finished: AtomicBool,
+    ///stops the socket task also if it waits for a stalled client
+    kill: Mutex<Option<oneshot::Sender<()>>>,
+    ///the limit of waiting messages
+    capacity: usize,
+}
+
+impl OutgoingQueue {
+    ///new queue, the stream of messages for the socket and the future that resolves on close.
+    ///The socket task must stop when the future resolves.
+    ///A dropped queue does not resolve it, so the waiting messages are still sent.
+    ///The stream ends after finish, then the socket sends the close frame.
+    pub fn new(
+        capacity: usize,
+    ) -> (
+        Self,
+        impl Stream<Item = Message, Error = ()>,
+        impl Future<Item = (), Error = ()>,
+    ) {
+        let (tx, rx) = mpsc::unbounded();
+        let (kill_tx, kill_rx) = oneshot::channel();
+        let depth = Arc::new(AtomicUsize::new(0));
+        let depth2 = Arc::clone(&depth);
+        let stream = rx
+            .take_while(|msg: &Option<Message>| Ok(msg.is_some()))
+            .filter_map(move |msg| {
+                depth2.fetch_sub(1, Ordering::AcqRel);
+                msg
+            });
+        let closed = kill_rx.or_else(|_canceled| future::empty());
+        let queue = OutgoingQueue {
+            tx,
+            depth,
+            max_depth: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            kill: Mutex::new(Some(kill_tx)),
+            capacity,
+        };
+        (queue, stream, closed)
+    }
+
+    ///put the message in the queue if there is space
+    pub fn send(&self, msg: Message) -> Result<(), QueueError> {
+        if self.is_closed() || self.finished.load(Ordering::Relaxed) {
+            return Err(QueueError::Disconnected);
+        }
+        //the slot is counted before the send, so the stream never counts below zero
+        let depth = self.reserve()?;
+        if self.tx.unbounded_send(Some(msg)).is_err() {
+            self.depth.fetch_sub(1, Ordering::AcqRel);
+            return Err(QueueError::Disconnected);
+        }
+        self.update_max_depth(depth);
+        Ok(())
+    }
+
+    ///count one more message, if it is not over the capacity. Returns the new depth.
+    fn reserve(&self) -> Result<usize, QueueError> {
+        let mut depth = self.depth.load(Ordering::Acquire);
+        loop {
+            if depth >= self.capacity {
+                return Err(QueueError::Full);
+            }
+            match self.depth.compare_exchange_weak(
+                depth,
+                depth + 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Ok(depth + 1),
+                Err(actual) => depth = actual,
+            }
+        }
+    }
+
+    ///the max depth only grows, also if two threads send at the same time
+    fn update_max_depth(&self, depth: usize) {
+        let mut max_depth = self.max_depth.load(Ordering::Relaxed);
+        while depth > max_depth {
+            match self.max_depth.compare_exchange_weak(
+                max_depth,
+                depth,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(actual) => max_depth = actual,
+            }
+        }
+    }
+
+    ///messages waiting now
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Acquire)
+    }
+
+    ///the highest depth since the connection started
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.load(Ordering::Relaxed)
+    }
+
+    ///send the waiting messages and then the close frame. Nothing is queued after it.
+    pub fn finish(&self) {
+        if !self.finished.swap(true, Ordering::Relaxed) {
+            let _disconnected = self.tx.unbounded_send(None);
+        }
+    }
+
+    ///stop sending. The waiting messages are dropped.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        let kill = lock(&self.kill).take();
+        if let Some(kill) = kill {
+            let _already_stopped = kill.send(());
+        }
+    }
+
+    ///true after close
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_queue_rejects_the_message_and_keeps_the_depth() {
+        let (queue, _stream, _closed) = OutgoingQueue::new(2);
+        assert_eq!(queue.send(Message::text("1")), Ok(()));
+        assert_eq!(queue.send(Message::text("2")), Ok(()));
+        assert_eq!(queue.send(Message::text("3")), Err(QueueError::Full));
+        assert_eq!(queue.depth(), 2);
+        assert_eq!(queue.max_depth(), 2);
+    }
+
+    #[test]
+    fn depth_follows_the_stream_and_max_depth_stays() {
+        let (queue, stream, _closed) = OutgoingQueue::new(4);
+        for text in &["1", "2", "3"] {
+            queue
+                .send(Message::text(*text))
+                .expect("space in the queue");
+        }
+        queue.finish();
+        let texts: Vec<String> = stream
+            .wait()
+            .map(|msg| msg.expect("a message").to_str().expect("text").to_string())
+            .collect();
+        assert_eq!(texts, vec!["1", "2", "3"]);
+        assert_eq!(queue.depth(), 0);
+        assert_eq!(queue.max_depth(), 3);
+        //nothing is queued after the close frame
+        assert_eq!(
+            queue.send(Message::text("4")),
+            Err(QueueError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn gone_connection_is_disconnected_and_not_counted() {
+        let (queue, stream, _closed) = OutgoingQueue::new(4);
+        drop(stream);
+        assert_eq!(
+            queue.send(Message::text("1")),
+            Err(QueueError::Disconnected)
+        );
+        assert_eq!(queue.depth(), 0);
+        assert_eq!(queue.max_depth(), 0);
+    }
+
+    #[test]
+    fn closed_queue_is_disconnected_and_resolves_the_future() {
+        let (queue, _stream, closed) = OutgoingQueue::new(4);
+        queue.close();
+        assert!(queue.is_closed());
+        assert_eq!(
+            queue.send(Message::text("1")),
+            Err(QueueError::Disconnected)
+        );
+        assert_eq!(closed.wait(), Ok(()));
+    }
+}
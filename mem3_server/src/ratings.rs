@@ -0,0 +1,199 @@
+//! ratings
+//! Elo-style skill rating for every nickname.
+//! The ratings are updated after every finished game and written to a local json file.
+
+use mem3_common::{GameResult, LeaderboardRow};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Result};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+///default file name of the ratings in the current directory
+pub const RATINGS_FILENAME: &str = "mem3_ratings.json";
+///every new nickname starts with this rating
+pub const INITIAL_RATING: f64 = 1200.0;
+///how much one game can change the rating
+const K_FACTOR: f64 = 32.0;
+
+///the ratings are shared between all websocket connections
+pub type SharedRatings = Arc<Mutex<Ratings>>;
+
+///rating for every nickname
+pub struct Ratings {
+    ///json file with the map nickname: rating
+    path: PathBuf,
+    ///ratings by nickname
+    ratings: HashMap<String, f64>,
+}
+
+impl Ratings {
+    ///read the existing file. A missing file means no ratings yet.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let ratings = if path.exists() {
+            serde_json::from_reader(BufReader::new(File::open(&path)?))?
+        } else {
+            HashMap::new()
+        };
+        Ok(Ratings { path, ratings })
+    }
+
+    ///rating of one nickname, rounded for the client
+    pub fn rating(&self, nickname: &str) -> u32 {
+        round_rating(
+            self.ratings
+                .get(nickname)
+                .cloned()
+                .unwrap_or(INITIAL_RATING),
+        )
+    }
+
+    ///all ratings, the best first
+    pub fn all(&self) -> Vec<LeaderboardRow> {
+        let mut rows: Vec<LeaderboardRow> = self
+            .ratings
+            .iter()
+            .map(|(nickname, rating)| LeaderboardRow {
+                nickname: nickname.clone(),
+                value: u64::from(round_rating(*rating)),
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            b.value
+                .cmp(&a.value)
+                .then_with(|| a.nickname.cmp(&b.nickname))
+        });
+        rows
+    }
+
+    ///update both players after a two-player game and write the file
+    pub fn update(&mut self, game_result: &GameResult) -> Result<()> {
+        let nickname1 = &game_result.player1_nickname;
+        let nickname2 = &game_result.player2_nickname;
+        //players without nickname or playing against themselves are not rated
+        if nickname1.is_empty() || nickname2.is_empty() || nickname1 == nickname2 {
+            return Ok(());
+        }
+        let rating1 = self
+            .ratings
+            .get(nickname1)
+            .cloned()
+            .unwrap_or(INITIAL_RATING);
+        let rating2 = self
+            .ratings
+            .get(nickname2)
+            .cloned()
+            .unwrap_or(INITIAL_RATING);
+        //1 for a win, 0.5 for a draw, 0 for a loss
+        let score1 = if game_result.player1_points > game_result.player2_points {
+            1.0
+        } else if game_result.player1_points < game_result.player2_points {
+            0.0
+        } else {
+            0.5
+        };
+        let expected1 = expected_score(rating1, rating2);
+        let change = K_FACTOR * (score1 - expected1);
+        self.ratings.insert(nickname1.clone(), rating1 + change);
+        self.ratings.insert(nickname2.clone(), rating2 - change);
+        self.save()
+    }
+
+    ///no ratings yet. The file is written after the first game.
+    pub fn empty(path: PathBuf) -> Self {
+        Ratings {
+            path,
+            ratings: HashMap::new(),
+        }
+    }
+
+    ///write all the ratings to the file. The old file is replaced only by a complete new file.
+    fn save(&self) -> Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer_pretty(&mut writer, &self.ratings)?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+///probability that the player with rating_a wins against rating_b
+fn expected_score(rating_a: f64, rating_b: f64) -> f64 {
+    1.0 / (1.0 + 10_f64.powf((rating_b - rating_a) / 400.0))
+}
+
+///the client does not need decimals
+fn round_rating(rating: f64) -> u32 {
+    //ratings are always positive and small
+    rating.max(0.0).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    ///ratings in a new file in the temp folder
+    fn ratings_in_temp(name: &str) -> Ratings {
+        let path =
+            env::temp_dir().join(format!("mem3_ratings_{}_{}.json", name, std::process::id()));
+        let _missing = fs::remove_file(&path);
+        Ratings::open(path).expect("no file is no ratings")
+    }
+
+    ///Ana is player1, Bor is player2
+    fn game_result(player1_points: usize, player2_points: usize) -> GameResult {
+        GameResult {
+            content_folder_name: "alphabet".to_string(),
+            board_size: 16,
+            player1_nickname: "Ana".to_string(),
+            player2_nickname: "Bor".to_string(),
+            player1_points,
+            player2_points,
+            count_all_flips: 20,
+            duration_ms: 60_000,
+            started_at: 0,
+            ended_at: 0,
+            is_solo: false,
+        }
+    }
+
+    #[test]
+    fn winner_gets_what_the_loser_loses() {
+        let mut ratings = ratings_in_temp("win");
+        ratings
+            .update(&game_result(5, 3))
+            .expect("write the ratings");
+        let ana = ratings.ratings["Ana"];
+        let bor = ratings.ratings["Bor"];
+        //equal ratings expect a draw, so the winner gets half of the K factor
+        assert!((ana - (INITIAL_RATING + K_FACTOR / 2.0)).abs() < 1e-9);
+        assert!(((ana - INITIAL_RATING) + (bor - INITIAL_RATING)).abs() < 1e-9);
+        assert_eq!(ratings.rating("Ana"), 1216);
+        assert_eq!(ratings.rating("Bor"), 1184);
+
+        //the file has the same ratings after the restart
+        let reopened = Ratings::open(ratings.path.clone()).expect("read the ratings");
+        assert_eq!(reopened.rating("Ana"), 1216);
+        let _cleanup = fs::remove_file(&ratings.path);
+    }
+
+    #[test]
+    fn draw_between_equal_ratings_changes_nothing() {
+        let mut ratings = ratings_in_temp("draw");
+        ratings
+            .update(&game_result(4, 4))
+            .expect("write the ratings");
+        assert!((ratings.ratings["Ana"] - INITIAL_RATING).abs() < 1e-9);
+        assert!((ratings.ratings["Bor"] - INITIAL_RATING).abs() < 1e-9);
+        let _cleanup = fs::remove_file(&ratings.path);
+    }
+
+    #[test]
+    fn expected_score_is_symmetric() {
+        let stronger = expected_score(1400.0, 1200.0);
+        let weaker = expected_score(1200.0, 1400.0);
+        assert!((stronger + weaker - 1.0).abs() < 1e-9);
+        assert!(stronger > 0.75 && stronger < 0.77);
+    }
+}
@@ -0,0 +1,253 @@
+//! request error
+//! A bad message from one client must not stop the server.
+//! Every failure is logged and sent back to the sender as a `WsMessage::Error` with a stable code.
+
+use mem3_common::{ErrorCode, WsMessage, PROTOCOL_VERSION};
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+///the largest message is the ResponseResync with the whole game. Bigger texts are not parsed.
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+///why the server could not handle one message
+#[derive(Debug)]
+pub enum RequestError {
+    ///the text is not a json of `WsMessage`
+    BadJson(String),
+    ///the text is longer than `MAX_MESSAGE_LEN`
+    TooLarge(usize),
+    ///the message must be forwarded, but has no usable `other_ws_uid`
+    MissingOtherWsUid,
+    ///the other player is not connected anymore
+    PlayerGone(usize),
+    ///the requested file is not in the content folder or cannot be read
+    BadFile(String),
+    ///there is no deck with this content folder name
+    UnknownDeck(String),
+    ///the client has another `PROTOCOL_VERSION`
+    VersionMismatch(u32),
+    ///the client sends too many messages
+    RateLimited,
+    ///the message belongs to a game, but the player is not in a running game
+    NotInGame,
+    ///a click or a change of the turn from the player that is not on turn
+    NotYourTurn,
+    ///the result of the game does not fit the running game
+    BadResult(String),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestError::BadJson(err) => write!(f, "the message is not valid: {}", err),
+            RequestError::TooLarge(len) => write!(
+                f,
+                "the message has {} bytes, the limit is {}",
+                len, MAX_MESSAGE_LEN
+            ),
+            RequestError::MissingOtherWsUid => write!(f, "the message has no other_ws_uid"),
+            RequestError::PlayerGone(ws_uid) => {
+                write!(f, "the player {} is not connected", ws_uid)
+            }
+            RequestError::BadFile(filename) => write!(f, "cannot read the file {}", filename),
+            RequestError::UnknownDeck(content_folder_name) => {
+                write!(f, "there is no deck {}", content_folder_name)
+            }
+            RequestError::VersionMismatch(version) => write!(
+                f,
+                "the client version {} does not match the server version {}. Reload the page.",
+                version, PROTOCOL_VERSION
+            ),
+            RequestError::RateLimited => write!(f, "too many messages, the message is ignored"),
+            RequestError::NotInGame => write!(f, "the player is not in a running game"),
+            RequestError::NotYourTurn => write!(f, "it is not your turn, the move is ignored"),
+            RequestError::BadResult(reason) => write!(f, "the game result is wrong: {}", reason),
+        }
+    }
+}
+
+impl RequestError {
+    ///the stable code for the client
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            RequestError::BadJson(_)
+            | RequestError::TooLarge(_)
+            | RequestError::MissingOtherWsUid
+            | RequestError::NotInGame
+            | RequestError::BadResult(_) => ErrorCode::BadMessage,
+            RequestError::NotYourTurn => ErrorCode::NotYourTurn,
+            RequestError::PlayerGone(_) => ErrorCode::OpponentGone,
+            RequestError::BadFile(_) | RequestError::UnknownDeck(_) => ErrorCode::UnknownDeck,
+            RequestError::VersionMismatch(_) => ErrorCode::VersionMismatch,
+            RequestError::RateLimited => ErrorCode::RateLimited,
+        }
+    }
+    ///json of the message for the sender. The related message is the one that failed.
+    pub fn to_json(&self, related_msg: &str) -> String {
+        serde_json::to_string(&WsMessage::Error {
+            code: self.code(),
+            message: self.to_string(),
+            related_msg: msg_name(related_msg),
+        })
+        .expect("serde_json::to_string(&WsMessage::Error)")
+    }
+}
+
+///the text of one frame as a message
+pub fn parse_message(new_msg: &str) -> Result<WsMessage, RequestError> {
+    if new_msg.len() > MAX_MESSAGE_LEN {
+        return Err(RequestError::TooLarge(new_msg.len()));
+    }
+    serde_json::from_str(new_msg).map_err(|err| RequestError::BadJson(err.to_string()))
+}
+
+///the `other_ws_uid` field of any message that is forwarded to the other player
+pub fn other_ws_uid_of(new_msg: &str) -> Result<usize, RequestError> {
+    //serde_json knows: 1. map (key+value pair) and 2. array
+    //the json of an enum variant is a map with one key: the name of the variant
+    let json_map: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(new_msg).map_err(|err| RequestError::BadJson(err.to_string()))?;
+    json_map
+        .values()
+        .next()
+        .and_then(|fields| fields.get("other_ws_uid"))
+        .and_then(serde_json::Value::as_u64)
+        .and_then(|other_ws_uid| usize::try_from(other_ws_uid).ok())
+        .ok_or(RequestError::MissingOtherWsUid)
+}
+
+///the name of the message is the only key of the json map. Empty if it is not a message.
+pub fn msg_name(new_msg: &str) -> String {
+    serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(new_msg)
+        .ok()
+        .and_then(|json_map| json_map.keys().next().cloned())
+        .unwrap_or_default()
+}
+
+///the deck is a folder directly inside the content folder
+pub fn check_content_folder_name(
+    content_dir: &Path,
+    content_folder_name: &str,
+) -> Result<(), RequestError> {
+    let is_plain_name = !content_folder_name.is_empty()
+        && content_folder_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if is_plain_name && content_dir.join(content_folder_name).is_dir() {
+        Ok(())
+    } else {
+        Err(RequestError::UnknownDeck(content_folder_name.to_string()))
+    }
+}
+
+///the spelling files are only inside the content folder. No absolute paths and no `..`.
+pub fn check_content_filename(filename: &str) -> Result<(), RequestError> {
+    if filename.starts_with("content/")
+        && !filename.contains("..")
+        && !filename.contains('\\')
+        && filename.ends_with(".json")
+    {
+        Ok(())
+    } else {
+        Err(RequestError::BadFile(filename.to_string()))
+    }
+}
+
+///lock the mutex even if a thread panicked while holding it.
+///The data of the server is still usable and the other connections continue.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    #[test]
+    fn other_ws_uid_is_read_from_any_message() {
+        let new_msg = r#"{"PlayerClick":{"my_ws_uid":11,"other_ws_uid":22,"card_index":3,"count_click_inside_one_turn":1}}"#;
+        assert_eq!(other_ws_uid_of(new_msg).ok(), Some(22));
+        //the server forwards also messages that it does not know
+        assert_eq!(
+            other_ws_uid_of(r#"{"NewMessage":{"other_ws_uid":5}}"#).ok(),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn message_without_other_ws_uid_is_a_bad_message() {
+        for new_msg in &[
+            r#"{"PlayerClick":{"my_ws_uid":11}}"#,
+            r#"{"PlayerClick":{"other_ws_uid":"22"}}"#,
+            r#"{"PlayerClick":{"other_ws_uid":-1}}"#,
+            r#"{}"#,
+        ] {
+            let err = other_ws_uid_of(new_msg).expect_err(new_msg);
+            assert_eq!(err.to_string(), "the message has no other_ws_uid");
+            assert_eq!(err.code(), ErrorCode::BadMessage);
+        }
+        let err = other_ws_uid_of("not json").expect_err("not json");
+        assert_eq!(err.code(), ErrorCode::BadMessage);
+    }
+
+    #[test]
+    fn frames_that_are_not_messages_are_bad_messages() {
+        let too_large = format!(
+            r#"{{"Dummy":{{"dummy":"{}"}}}}"#,
+            "x".repeat(MAX_MESSAGE_LEN)
+        );
+        for new_msg in &[
+            "not json",
+            r#"{"PlayerClick":{"my_ws_uid":11,"#,
+            r#"{"UnknownVariant":{"other_ws_uid":22}}"#,
+            r#"{"PlayerClick":{"my_ws_uid":11}}"#,
+            too_large.as_str(),
+        ] {
+            let err = parse_message(new_msg).err().expect("a bad message");
+            assert_eq!(err.code(), ErrorCode::BadMessage);
+        }
+        assert!(parse_message(r#"{"Dummy":{"dummy":"x"}}"#).is_ok());
+    }
+
+    #[test]
+    fn error_json_has_the_code_and_the_name_of_the_failed_message() {
+        let json = RequestError::PlayerGone(22).to_json(r#"{"PlayerChange":{"other_ws_uid":22}}"#);
+        match serde_json::from_str(&json).expect("json of WsMessage") {
+            WsMessage::Error {
+                code,
+                message,
+                related_msg,
+            } => {
+                assert_eq!(code, ErrorCode::OpponentGone);
+                assert_eq!(message, "the player 22 is not connected");
+                assert_eq!(related_msg, "PlayerChange");
+            }
+            _ => panic!("not an Error: {}", json),
+        }
+        assert_eq!(
+            RequestError::TooLarge(MAX_MESSAGE_LEN + 1).to_json("{"),
+            format!(
+                r#"{{"Error":{{"code":"BadMessage","message":"the message has {} bytes, the limit is {}","related_msg":""}}}}"#,
+                MAX_MESSAGE_LEN + 1,
+                MAX_MESSAGE_LEN
+            )
+        );
+    }
+
+    #[test]
+    fn deck_is_a_folder_in_the_content_folder() {
+        let content_dir = env::temp_dir().join(format!("mem3_content_{}", process::id()));
+        fs::create_dir_all(content_dir.join("animals")).expect("create the deck folder");
+        fs::write(content_dir.join("text.json"), "{}").expect("create a file");
+        assert!(check_content_folder_name(&content_dir, "animals").is_ok());
+        for content_folder_name in &["", "birds", "text.json", "..", "animals/..", "/tmp"] {
+            let err = check_content_folder_name(&content_dir, content_folder_name)
+                .expect_err(content_folder_name);
+            assert_eq!(err.code(), ErrorCode::UnknownDeck);
+        }
+        fs::remove_dir_all(&content_dir).expect("remove the content folder");
+    }
+}
@@ -0,0 +1,304 @@
+//! sessions
+//! Every player gets a session token with the first ResponseWsUid.
+//! After a reconnect the client sends the token and gets the same ws_uid again,
+//! so the other player in the game can still reach it.
+//! The sessions, the players of the running games and the starts of the solo games
+//! are written to a snapshot file and read on start.
+//! The cards and the points are only in the clients:
+//! after a restart of the server they resync the game from each other.
+
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Result};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+///the sessions are shared between all websocket connections
+pub type SharedSessions = Arc<Mutex<Sessions>>;
+
+///the default snapshot file in the current directory
+pub const SNAPSHOT_FILENAME: &str = "mem3_snapshot.json";
+
+///count of characters of the token
+const TOKEN_LENGTH: usize = 32;
+
+///one player, connected or waiting for the reconnect
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Session {
+    ///the ws_uid stays the same for all the connections of the session
+    pub ws_uid: usize,
+    ///nickname from the last RequestWsUid
+    pub nickname: String,
+    ///avatar from the last RequestWsUid
+    pub avatar: String,
+}
+
+///two players after AcceptPlay and before EndGame
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct RunningGame {
+    ///the player that invited
+    pub player1_ws_uid: usize,
+    ///the player that accepted
+    pub player2_ws_uid: usize,
+    ///seconds since the unix epoch
+    pub started_at: u64,
+    ///the deck from the invitation. Empty in a snapshot of an older version.
+    #[serde(default)]
+    pub content_folder_name: String,
+    ///the player number on turn, 1 or 2. Zero is unknown after a resync
+    ///and in a snapshot of an older version.
+    #[serde(default)]
+    pub player_on_turn: usize,
+}
+
+impl RunningGame {
+    ///the opponent of the player. None if the player is not in this game.
+    pub fn other_ws_uid(&self, ws_uid: usize) -> Option<usize> {
+        if ws_uid == self.player1_ws_uid {
+            Some(self.player2_ws_uid)
+        } else if ws_uid == self.player2_ws_uid {
+            Some(self.player1_ws_uid)
+        } else {
+            None
+        }
+    }
+}
+
+///one player alone after StartSoloGame and before EndSoloGame
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SoloGame {
+    ///seconds since the unix epoch
+    pub started_at: u64,
+    ///the deck of the game
+    pub content_folder_name: String,
+}
+
+///the content of the snapshot file
+#[derive(Serialize, Deserialize, Default)]
+struct Snapshot {
+    ///the next ws_uid, so a new player does not get the ws_uid of a restored session
+    next_ws_uid: usize,
+    ///key is the session token
+    sessions: HashMap<String, Session>,
+    ///the games that were running
+    games: Vec<RunningGame>,
+    ///key is the ws_uid. Empty in a snapshot of an older version.
+    #[serde(default)]
+    solo_games: HashMap<usize, SoloGame>,
+}
+
+///all sessions and running games
+#[derive(Default)]
+pub struct Sessions {
+    ///key is the session token
+    sessions: HashMap<String, Session>,
+    ///key is the session token. Value is the time of the disconnect.
+    disconnected: HashMap<String, Instant>,
+    ///the games that are running
+    games: Vec<RunningGame>,
+    ///the solo games that are running. Key is the ws_uid.
+    solo_games: HashMap<usize, SoloGame>,
+}
+
+impl Sessions {
+    ///read the snapshot file and the next ws_uid. A missing file means no sessions.
+    ///All restored sessions wait for the reconnect.
+    pub fn open(path: &Path) -> Result<(Self, usize)> {
+        let mut sessions = Sessions::default();
+        if !path.exists() {
+            return Ok((sessions, 0));
+        }
+        let snapshot: Snapshot = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let now = Instant::now();
+        sessions.disconnected = snapshot
+            .sessions
+            .keys()
+            .map(|token| (token.clone(), now))
+            .collect();
+        sessions.sessions = snapshot.sessions;
+        sessions.games = snapshot.games;
+        sessions.solo_games = snapshot.solo_games;
+        info!(
+            "snapshot {} has {} sessions and {} running games",
+            path.display(),
+            sessions.sessions.len(),
+            sessions.games.len()
+        );
+        Ok((sessions, snapshot.next_ws_uid))
+    }
+
+    ///write the snapshot file. The old file is replaced only by a complete new file.
+    pub fn save(&self, path: &Path, next_ws_uid: usize) -> Result<()> {
+        let snapshot = Snapshot {
+            next_ws_uid,
+            sessions: self.sessions.clone(),
+            games: self.games.clone(),
+            solo_games: self.solo_games.clone(),
+        };
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_string(&snapshot)?)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    ///the ws_uid of the session
+    pub fn ws_uid(&self, session_token: &str) -> Option<usize> {
+        self.sessions
+            .get(session_token)
+            .map(|session| session.ws_uid)
+    }
+
+    ///nickname of the session with this ws_uid
+    pub fn nickname(&self, ws_uid: usize) -> Option<&str> {
+        self.sessions
+            .values()
+            .find(|session| session.ws_uid == ws_uid)
+            .map(|session| session.nickname.as_str())
+    }
+
+    ///new session for the ws_uid. Returns the token.
+    pub fn create(&mut self, ws_uid: usize) -> String {
+        let session_token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .collect();
+        self.sessions.insert(
+            session_token.clone(),
+            Session {
+                ws_uid,
+                nickname: String::new(),
+                avatar: String::new(),
+            },
+        );
+        session_token
+    }
+
+    ///the session has a connection again, with a new nickname and avatar
+    pub fn connected(&mut self, session_token: &str, nickname: &str, avatar: &str) {
+        if let Some(session) = self.sessions.get_mut(session_token) {
+            session.nickname = nickname.to_string();
+            session.avatar = avatar.to_string();
+        }
+        self.disconnected.remove(session_token);
+    }
+
+    ///the connection of the ws_uid is closed. The session waits for the reconnect.
+    pub fn disconnected(&mut self, ws_uid: usize) {
+        let now = Instant::now();
+        for (session_token, session) in &self.sessions {
+            if session.ws_uid == ws_uid {
+                self.disconnected.insert(session_token.clone(), now);
+            }
+        }
+    }
+
+    ///the players accepted the game with this deck
+    pub fn start_game(
+        &mut self,
+        player1_ws_uid: usize,
+        player2_ws_uid: usize,
+        content_folder_name: &str,
+    ) {
+        self.end_game(player1_ws_uid);
+        self.end_game(player2_ws_uid);
+        self.games.push(RunningGame {
+            player1_ws_uid,
+            player2_ws_uid,
+            started_at: now_secs(),
+            content_folder_name: content_folder_name.to_string(),
+            //the player that invited starts
+            player_on_turn: 1,
+        });
+    }
+
+    ///the player plays alone with this deck
+    pub fn start_solo_game(&mut self, ws_uid: usize, content_folder_name: &str) {
+        self.end_game(ws_uid);
+        self.solo_games.insert(
+            ws_uid,
+            SoloGame {
+                started_at: now_secs(),
+                content_folder_name: content_folder_name.to_string(),
+            },
+        );
+    }
+
+    ///the running solo game of this player
+    pub fn solo_game_of(&self, ws_uid: usize) -> Option<&SoloGame> {
+        self.solo_games.get(&ws_uid)
+    }
+
+    ///false if the move comes from the player that is not on turn.
+    ///A click comes from the player on turn, the change of the turn from the other player.
+    ///An unknown turn is taken from the move. A player without a game has nothing to check.
+    pub fn is_move_on_turn(&mut self, ws_uid: usize, is_turn_change: bool) -> bool {
+        let game = match self
+            .games
+            .iter_mut()
+            .find(|game| game.other_ws_uid(ws_uid).is_some())
+        {
+            Some(game) => game,
+            None => return true,
+        };
+        let player_number = if ws_uid == game.player1_ws_uid { 1 } else { 2 };
+        let is_on_turn = game.player_on_turn == player_number;
+        if game.player_on_turn != 0 && is_on_turn == is_turn_change {
+            return false;
+        }
+        game.player_on_turn = player_number;
+        true
+    }
+
+    ///after a resync the turn comes from the state of the other player. It is unknown till the next move.
+    pub fn forget_turn(&mut self, ws_uid: usize) {
+        if let Some(game) = self
+            .games
+            .iter_mut()
+            .find(|game| game.other_ws_uid(ws_uid).is_some())
+        {
+            game.player_on_turn = 0;
+        }
+    }
+
+    ///the running game of this player
+    pub fn game_of(&self, ws_uid: usize) -> Option<&RunningGame> {
+        self.games
+            .iter()
+            .find(|game| game.other_ws_uid(ws_uid).is_some())
+    }
+
+    ///the game or the solo game of this player ended
+    pub fn end_game(&mut self, ws_uid: usize) {
+        self.games
+            .retain(|game| game.player1_ws_uid != ws_uid && game.player2_ws_uid != ws_uid);
+        self.solo_games.remove(&ws_uid);
+    }
+
+    ///remove the sessions without a reconnect in time and their games
+    pub fn prune(&mut self, session_timeout: Duration) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .disconnected
+            .iter()
+            .filter(|(_, disconnected_at)| now.duration_since(**disconnected_at) > session_timeout)
+            .map(|(session_token, _)| session_token.clone())
+            .collect();
+        for session_token in expired {
+            self.disconnected.remove(&session_token);
+            if let Some(session) = self.sessions.remove(&session_token) {
+                info!("session expired: {}", session.ws_uid);
+                self.end_game(session.ws_uid);
+            }
+        }
+    }
+}
+
+///seconds since the unix epoch
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
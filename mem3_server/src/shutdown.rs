@@ -0,0 +1,75 @@
+//! shutdown
+//! On SIGINT or SIGTERM the server stops accepting connections and tells the clients.
+//! The messages that wait in the queues are still sent, then the process exits.
+
+use crate::users::UserMap;
+use futures::{future, Future, Stream};
+use mem3_common::WsMessage;
+use std::time::{Duration, Instant};
+use tokio::timer::{Delay, Interval};
+use warp::ws::Message;
+
+///how often the queues are checked while flushing
+const FLUSH_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+///resolves on the first SIGINT (ctrl+c) or SIGTERM
+#[cfg(target_family = "unix")]
+pub fn signal() -> Box<dyn Future<Item = (), Error = ()> + Send> {
+    let ctrl_c = tokio_signal::ctrl_c().flatten_stream();
+    let sigterm = tokio_signal::unix::Signal::new(tokio_signal::unix::SIGTERM)
+        .flatten_stream()
+        .map(|_signal| ());
+    Box::new(
+        ctrl_c
+            .select(sigterm)
+            .into_future()
+            .map(|_first_signal| ())
+            .map_err(|(err, _signals)| error!("cannot listen to signals: {}", err)),
+    )
+}
+
+///resolves on the first ctrl+c. Windows has no SIGTERM.
+#[cfg(target_family = "windows")]
+pub fn signal() -> Box<dyn Future<Item = (), Error = ()> + Send> {
+    Box::new(
+        tokio_signal::ctrl_c()
+            .flatten_stream()
+            .into_future()
+            .map(|_first_signal| ())
+            .map_err(|(err, _signals)| error!("cannot listen to signals: {}", err)),
+    )
+}
+
+///send ServerShuttingDown and the close frame to all users.
+///Resolves when all the queues are empty or after the flush timeout.
+pub fn notify_and_flush(
+    users: &UserMap,
+    restart_eta_secs: Option<u64>,
+    flush_timeout: Duration,
+) -> impl Future<Item = (), Error = ()> {
+    let j = serde_json::to_string(&WsMessage::ServerShuttingDown { restart_eta_secs })
+        .expect("serde_json::to_string(&WsMessage::ServerShuttingDown)");
+    let all_users = users.snapshot();
+    info!("shutting down, {} users are informed", all_users.len());
+    for (_ws_uid, user) in &all_users {
+        //a full queue is not closed here, the timeout ends the waiting
+        let _queue_error = user.queue.send(Message::text(j.as_str()));
+        user.queue.finish();
+    }
+    let is_flushed = move || {
+        all_users
+            .iter()
+            .all(|(_ws_uid, user)| user.queue.depth() == 0 || user.queue.is_closed())
+    };
+    Interval::new_interval(FLUSH_CHECK_INTERVAL)
+        .map_err(|timer_err| error!("flush timer error: {}", timer_err))
+        .take_while(move |_instant| future::ok(!is_flushed()))
+        .for_each(|_instant| Ok(()))
+        .select(
+            Delay::new(Instant::now() + flush_timeout)
+                .map(|()| warn!("the queues are not empty after the flush timeout"))
+                .map_err(|timer_err| error!("flush timer error: {}", timer_err)),
+        )
+        .map(|_select| ())
+        .map_err(|_select| ())
+}
@@ -0,0 +1,214 @@
+//! tls
+//! A self-signed certificate for https and wss in the LAN.
+//! The browser shows a warning once, then the page has all the features of a secure context.
+//! The certificate is written with ring, the same version that the tls of warp uses.
+
+use chrono::{Duration, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use yasna::models::ObjectIdentifier;
+use yasna::tags::TAG_UTCTIME;
+use yasna::{DERWriter, Tag};
+
+///ecdsa-with-SHA256
+const OID_ECDSA_WITH_SHA256: &[u64] = &[1, 2, 840, 10045, 4, 3, 2];
+///id-ecPublicKey
+const OID_EC_PUBLIC_KEY: &[u64] = &[1, 2, 840, 10045, 2, 1];
+///the curve prime256v1
+const OID_PRIME256V1: &[u64] = &[1, 2, 840, 10045, 3, 1, 7];
+///commonName
+const OID_COMMON_NAME: &[u64] = &[2, 5, 4, 3];
+///subjectAltName
+const OID_SUBJECT_ALT_NAME: &[u64] = &[2, 5, 29, 17];
+///the SAN dNSName is the context tag 2
+const TAG_DNS_NAME: u64 = 2;
+///the SAN iPAddress is the context tag 7
+const TAG_IP_ADDRESS: u64 = 7;
+///the certificate is valid for the maximum that browsers accept
+const VALIDITY_DAYS: i64 = 825;
+
+///write a new certificate and its private key as pem files.
+///The names are the host names and ip addresses in the url of the game.
+pub fn generate_self_signed(names: &[String], cert_path: &Path, key_path: &Path) -> io::Result<()> {
+    let rng = SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng)
+        .map_err(|_| to_io_error("cannot generate the private key"))?;
+    let key_pair = EcdsaKeyPair::from_pkcs8(
+        &ECDSA_P256_SHA256_ASN1_SIGNING,
+        untrusted::Input::from(pkcs8.as_ref()),
+    )
+    .map_err(|_| to_io_error("cannot read the private key"))?;
+    let mut serial_number = [0_u8; 8];
+    rng.fill(&mut serial_number)
+        .map_err(|_| to_io_error("cannot generate the serial number"))?;
+    let serial_number = u64::from_be_bytes(serial_number);
+
+    let tbs_certificate = yasna::construct_der(|writer| {
+        write_tbs_certificate(writer, names, serial_number, key_pair.public_key().as_ref())
+    });
+    let signature = key_pair
+        .sign(&rng, untrusted::Input::from(&tbs_certificate))
+        .map_err(|_| to_io_error("cannot sign the certificate"))?;
+    let certificate = yasna::construct_der(|writer| {
+        writer.write_sequence(|writer| {
+            writer.next().write_der(&tbs_certificate);
+            write_algorithm(writer.next(), OID_ECDSA_WITH_SHA256);
+            let signature = signature.as_ref();
+            writer
+                .next()
+                .write_bitvec_bytes(signature, signature.len() * 8);
+        })
+    });
+
+    fs::write(cert_path, to_pem("CERTIFICATE", certificate))?;
+    write_private_key(key_path, &to_pem("PRIVATE KEY", pkcs8.as_ref().to_vec()))?;
+    Ok(())
+}
+
+///the private key file is readable only by the owner
+#[cfg(unix)]
+fn write_private_key(key_path: &Path, pem: &str) -> io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(key_path)?;
+    //mode() is only for a new file, a key generated again overwrites the old file
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(pem.as_bytes())
+}
+
+///other systems have no unix permissions
+#[cfg(not(unix))]
+fn write_private_key(key_path: &Path, pem: &str) -> io::Result<()> {
+    fs::write(key_path, pem)
+}
+
+///the signed part of the certificate. The issuer is the subject.
+fn write_tbs_certificate(
+    writer: DERWriter,
+    names: &[String],
+    serial_number: u64,
+    public_key: &[u8],
+) {
+    writer.write_sequence(|writer| {
+        //version 3 is the value 2
+        writer
+            .next()
+            .write_tagged(Tag::context(0), |writer| writer.write_u8(2));
+        writer.next().write_u64(serial_number);
+        write_algorithm(writer.next(), OID_ECDSA_WITH_SHA256);
+        write_name(writer.next());
+        writer.next().write_sequence(|writer| {
+            let now = Utc::now();
+            write_utctime(writer.next(), &now.format("%y%m%d%H%M%SZ").to_string());
+            let not_after = now + Duration::days(VALIDITY_DAYS);
+            write_utctime(
+                writer.next(),
+                &not_after.format("%y%m%d%H%M%SZ").to_string(),
+            );
+        });
+        write_name(writer.next());
+        writer.next().write_sequence(|writer| {
+            writer.next().write_sequence(|writer| {
+                writer
+                    .next()
+                    .write_oid(&ObjectIdentifier::from_slice(OID_EC_PUBLIC_KEY));
+                writer
+                    .next()
+                    .write_oid(&ObjectIdentifier::from_slice(OID_PRIME256V1));
+            });
+            writer
+                .next()
+                .write_bitvec_bytes(public_key, public_key.len() * 8);
+        });
+        writer.next().write_tagged(Tag::context(3), |writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_sequence(|writer| {
+                    writer
+                        .next()
+                        .write_oid(&ObjectIdentifier::from_slice(OID_SUBJECT_ALT_NAME));
+                    writer.next().write_bytes(&subject_alt_names(names));
+                });
+            });
+        });
+    });
+}
+
+///the browser compares ip addresses only with an ip SAN, not with a dns SAN
+fn subject_alt_names(names: &[String]) -> Vec<u8> {
+    yasna::construct_der(|writer| {
+        writer.write_sequence(|writer| {
+            for name in names {
+                match name.parse::<IpAddr>() {
+                    Ok(IpAddr::V4(ip)) => writer
+                        .next()
+                        .write_tagged_implicit(Tag::context(TAG_IP_ADDRESS), |writer| {
+                            writer.write_bytes(&ip.octets())
+                        }),
+                    Ok(IpAddr::V6(ip)) => writer
+                        .next()
+                        .write_tagged_implicit(Tag::context(TAG_IP_ADDRESS), |writer| {
+                            writer.write_bytes(&ip.octets())
+                        }),
+                    Err(_) => writer
+                        .next()
+                        .write_tagged_implicit(Tag::context(TAG_DNS_NAME), |writer| {
+                            writer.write_ia5_string(name)
+                        }),
+                }
+            }
+        })
+    })
+}
+
+///the algorithm of the signature has no parameters
+fn write_algorithm(writer: DERWriter, oid: &[u64]) {
+    writer.write_sequence(|writer| {
+        writer.next().write_oid(&ObjectIdentifier::from_slice(oid));
+    });
+}
+
+///the issuer and the subject are the same
+fn write_name(writer: DERWriter) {
+    writer.write_sequence(|writer| {
+        writer.next().write_set(|writer| {
+            writer.next().write_sequence(|writer| {
+                writer
+                    .next()
+                    .write_oid(&ObjectIdentifier::from_slice(OID_COMMON_NAME));
+                writer.next().write_utf8_string("mem3");
+            });
+        });
+    });
+}
+
+///UTCTime `YYMMDDHHMMSSZ` is valid until 2049
+fn write_utctime(writer: DERWriter, time: &str) {
+    writer.write_tagged_implicit(TAG_UTCTIME, |writer| writer.write_bytes(time.as_bytes()));
+}
+
+///base64 lines between the BEGIN and END lines
+fn to_pem(tag: &str, contents: Vec<u8>) -> String {
+    pem::encode_config(
+        &pem::Pem {
+            tag: tag.to_string(),
+            contents,
+        },
+        &pem::EncodeConfig {
+            line_ending: pem::LineEnding::LF,
+        },
+    )
+}
+
+///the errors of ring have no description
+fn to_io_error(description: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, description)
+}
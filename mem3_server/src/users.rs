@@ -0,0 +1,160 @@
+//! users
+//! The connected users are split in shards. Every shard has its own lock,
+//! so the messages of many games don't wait on one global lock.
+//! The map gives out `Arc<User>` and the messages are sent after the shard is unlocked.
+//! The mutable data of one user has its own lock, that only this connection and its opponent use.
+
+use crate::outgoingqueue::OutgoingQueue;
+use crate::requesterror::lock;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+///default count of shards. More shards than cores don't help.
+pub const DEFAULT_SHARD_COUNT: usize = 16;
+
+///nickname, avatar and rating of one user
+#[derive(Clone)]
+pub struct Profile {
+    ///nickname from the handshake RequestWsUid
+    pub nickname: String,
+    ///avatar from the handshake RequestWsUid
+    pub avatar: String,
+    ///rating of the nickname
+    pub rating: u32,
+}
+
+///the counters of the rate limit and the heartbeat
+struct Activity {
+    ///the start of the current second for the rate limit
+    rate_window_start: Instant,
+    ///count of messages in the current second
+    rate_count: u32,
+    ///the last message from this user. Idle connections are reaped.
+    last_seen: Instant,
+}
+
+/// One connected user
+pub struct User {
+    ///bounded queue of `warp::ws::Message` for the socket
+    pub queue: OutgoingQueue,
+    ///changes with the handshake and after a game
+    profile: Mutex<Profile>,
+    ///changes with every message
+    activity: Mutex<Activity>,
+}
+
+impl User {
+    ///new user before the handshake
+    pub fn new(queue: OutgoingQueue, rating: u32) -> Self {
+        let now = Instant::now();
+        User {
+            queue,
+            profile: Mutex::new(Profile {
+                nickname: String::new(),
+                avatar: String::new(),
+                rating,
+            }),
+            activity: Mutex::new(Activity {
+                rate_window_start: now,
+                rate_count: 0,
+                last_seen: now,
+            }),
+        }
+    }
+    ///copy of the profile
+    pub fn profile(&self) -> Profile {
+        lock(&self.profile).clone()
+    }
+    ///the data from the handshake
+    pub fn set_profile(&self, profile: Profile) {
+        *lock(&self.profile) = profile;
+    }
+    ///the new rating after a game
+    pub fn set_rating(&self, rating: u32) {
+        lock(&self.profile).rating = rating;
+    }
+    ///any message, also a pong, proves that the connection is alive
+    pub fn touch(&self) {
+        lock(&self.activity).last_seen = Instant::now();
+    }
+    ///time since the last message
+    pub fn idle_for(&self, now: Instant) -> Duration {
+        now.duration_since(lock(&self.activity).last_seen)
+    }
+    ///count the message and check the limit of messages per second
+    pub fn is_rate_limited(&self, max_messages_per_second: u32) -> bool {
+        let mut activity = lock(&self.activity);
+        let now = Instant::now();
+        if now.duration_since(activity.rate_window_start) >= Duration::from_secs(1) {
+            activity.rate_window_start = now;
+            activity.rate_count = 0;
+        }
+        activity.rate_count += 1;
+        activity.rate_count > max_messages_per_second
+    }
+}
+
+///all connected users. The key is the ws uid.
+pub struct UserMap {
+    ///the ws uid modulo the count of shards is the index
+    shards: Vec<Mutex<HashMap<usize, Arc<User>>>>,
+}
+
+impl UserMap {
+    ///empty map. One shard is the same as one global lock.
+    pub fn new(shard_count: usize) -> Self {
+        UserMap {
+            shards: (0..shard_count.max(1))
+                .map(|_| Mutex::new(HashMap::new()))
+                .collect(),
+        }
+    }
+    ///the shard of the ws uid
+    fn shard(&self, ws_uid: usize) -> &Mutex<HashMap<usize, Arc<User>>> {
+        &self.shards[ws_uid % self.shards.len()]
+    }
+    ///add the user and return the handle for its connection
+    pub fn insert(&self, ws_uid: usize, user: User) -> Arc<User> {
+        let user = Arc::new(user);
+        lock(self.shard(ws_uid)).insert(ws_uid, Arc::clone(&user));
+        user
+    }
+    ///remove the user. None if it is already removed.
+    pub fn remove(&self, ws_uid: usize) -> Option<Arc<User>> {
+        lock(self.shard(ws_uid)).remove(&ws_uid)
+    }
+    ///remove the user only if it is still this connection.
+    ///After a reconnect the same ws uid belongs to the new connection.
+    pub fn remove_user(&self, ws_uid: usize, user: &Arc<User>) -> bool {
+        let mut shard = lock(self.shard(ws_uid));
+        let is_same = shard
+            .get(&ws_uid)
+            .map_or(false, |current| Arc::ptr_eq(current, user));
+        if is_same {
+            shard.remove(&ws_uid);
+        }
+        is_same
+    }
+    ///move the user to another ws uid. Returns the old connection that had this ws uid.
+    pub fn rekey(&self, from_ws_uid: usize, to_ws_uid: usize) -> Option<Arc<User>> {
+        let user = self.remove(from_ws_uid)?;
+        lock(self.shard(to_ws_uid)).insert(to_ws_uid, user)
+    }
+    ///the handle of one user. Only its shard is locked and only for the lookup.
+    pub fn get(&self, ws_uid: usize) -> Option<Arc<User>> {
+        lock(self.shard(ws_uid)).get(&ws_uid).cloned()
+    }
+    ///handles of all users. The shards are locked one after the other, never together.
+    pub fn snapshot(&self) -> Vec<(usize, Arc<User>)> {
+        let mut users = Vec::new();
+        for shard in &self.shards {
+            users.extend(
+                lock(shard)
+                    .iter()
+                    .map(|(&ws_uid, user)| (ws_uid, Arc::clone(user))),
+            );
+        }
+        users
+    }
+}